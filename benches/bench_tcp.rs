@@ -5,7 +5,9 @@
 //! cargo bench --bench tcp_bench -- --mode client
 
 use clap::Parser;
-use maestro_rs::{NetworkInterface, Result, Supervisor, TcpHandler, async_trait};
+use maestro_rs::{
+    CancellationToken, NetworkInterface, Result, Supervisor, TcpHandler, async_trait,
+};
 use std::{
     net::SocketAddr,
     str::FromStr,
@@ -40,7 +42,13 @@ impl TcpHandler for BenchmarkServer {
         9998
     }
 
-    async fn on_connection(&self, mut stream: TcpStream, _peer: &SocketAddr) {
+    async fn on_connection(
+        &self,
+        mut stream: TcpStream,
+        _peer: &SocketAddr,
+        _local: &SocketAddr,
+        _token: CancellationToken,
+    ) {
         self.counter.fetch_add(1, Ordering::Relaxed);
         let mut buf = [0u8; 1];
         let _ = stream.read_exact(&mut buf).await;
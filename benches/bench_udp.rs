@@ -5,7 +5,7 @@
 //! cargo bench --bench udp_bench -- --mode client
 
 use clap::Parser;
-use maestro_rs::{NetworkInterface, Result, Supervisor, UdpHandler, async_trait};
+use maestro_rs::{NetworkInterface, PacketInfo, Result, Supervisor, UdpHandler, async_trait};
 use std::{
     net::SocketAddr,
     str::FromStr,
@@ -40,7 +40,13 @@ impl UdpHandler for BenchmarkServer {
         9999
     }
 
-    async fn on_packet(&self, _data: &[u8], _sock: Arc<UdpSocket>, _peer: &SocketAddr) {
+    async fn on_packet(
+        &self,
+        _data: &[u8],
+        _sock: Arc<UdpSocket>,
+        _peer: &SocketAddr,
+        _info: &PacketInfo,
+    ) {
         self.counter.fetch_add(1, Ordering::Relaxed);
     }
 }
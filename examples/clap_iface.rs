@@ -3,7 +3,10 @@
 //! cargo run --example clap_iface -- -i lo
 
 use clap::{Parser, value_parser};
-use maestro_rs::{NetworkInterface, Result, Supervisor, TcpHandler, UdpHandler, async_trait};
+use maestro_rs::{
+    CancellationToken, NetworkInterface, PacketInfo, Result, Supervisor, TcpHandler, UdpHandler,
+    async_trait,
+};
 use std::{net::SocketAddr, sync::Arc};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -34,12 +37,22 @@ impl TcpHandler for MyTcp {
         8080
     }
 
-    async fn on_connection(&self, mut stream: TcpStream, peer: &SocketAddr) {
+    async fn on_connection(
+        &self,
+        mut stream: TcpStream,
+        peer: &SocketAddr,
+        _local: &SocketAddr,
+        token: CancellationToken,
+    ) {
         info!("TCP client connected: {}", peer);
         let mut buf = [0u8; 1024];
 
         loop {
-            let n = match stream.read(&mut buf).await {
+            let read = tokio::select! {
+                read = stream.read(&mut buf) => read,
+                _ = token.cancelled() => return,
+            };
+            let n = match read {
                 Ok(0) => return,
                 Ok(n) => n,
                 Err(e) => {
@@ -67,7 +80,13 @@ impl UdpHandler for MyUdp {
         5353
     }
 
-    async fn on_packet(&self, data: &[u8], socket: Arc<UdpSocket>, peer: &SocketAddr) {
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        _info: &PacketInfo,
+    ) {
         info!("UDP packet from {}: {:?}", peer, data);
         let _ = socket.send_to(b"ACK", peer).await;
     }
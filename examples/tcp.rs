@@ -1,6 +1,8 @@
 #![forbid(unsafe_code)]
 
-use maestro_rs::{NetworkInterface, Result, Supervisor, TcpHandler, async_trait};
+use maestro_rs::{
+    CancellationToken, NetworkInterface, Result, Supervisor, TcpHandler, async_trait,
+};
 use std::{net::SocketAddr, str::FromStr};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -20,13 +22,26 @@ impl TcpHandler for EchoTcp {
         8080
     }
 
-    async fn on_connection(&self, mut stream: TcpStream, peer: &SocketAddr) {
+    async fn on_connection(
+        &self,
+        mut stream: TcpStream,
+        peer: &SocketAddr,
+        _local: &SocketAddr,
+        token: CancellationToken,
+    ) {
         info!("New TCP client: {}", peer);
 
         let mut buf = [0u8; 1024];
 
         loop {
-            match stream.read(&mut buf).await {
+            let read = tokio::select! {
+                read = stream.read(&mut buf) => read,
+                _ = token.cancelled() => {
+                    info!("Closing connection from {}: shutting down", peer);
+                    return;
+                }
+            };
+            match read {
                 Ok(0) => {
                     info!("Client {} disconnected", peer);
                     return;
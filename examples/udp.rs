@@ -2,7 +2,7 @@
 //! Run with:
 //! cargo run --example udp
 
-use maestro_rs::{NetworkInterface, Result, Supervisor, UdpHandler, async_trait};
+use maestro_rs::{NetworkInterface, PacketInfo, Result, Supervisor, UdpHandler, async_trait};
 use std::{net::SocketAddr, str::FromStr, sync::Arc};
 use tokio::net::UdpSocket;
 use tracing::{error, info};
@@ -19,7 +19,13 @@ impl UdpHandler for EchoUdp {
         5353
     }
 
-    async fn on_packet(&self, data: &[u8], socket: Arc<UdpSocket>, peer: &SocketAddr) {
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        _info: &PacketInfo,
+    ) {
         info!("UDP packet from {}: {:?}", peer, data);
 
         if let Err(e) = socket.send_to(b"ACK", peer).await {
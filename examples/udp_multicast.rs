@@ -2,7 +2,7 @@
 //! Run with:
 //! cargo run --example udp_multicast
 
-use maestro_rs::{NetworkInterface, Result, Supervisor, UdpHandler, async_trait};
+use maestro_rs::{NetworkInterface, PacketInfo, Result, Supervisor, UdpHandler, async_trait};
 use std::{
     net::{IpAddr, Ipv4Addr, SocketAddr},
     str::FromStr,
@@ -28,7 +28,13 @@ impl UdpHandler for MulticastUdp {
         &GROUPS
     }
 
-    async fn on_packet(&self, data: &[u8], socket: Arc<UdpSocket>, peer: &SocketAddr) {
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        _info: &PacketInfo,
+    ) {
         info!("Received multicast packet from {}: {:?}", peer, data);
         if let Err(e) = socket.send_to(b"ACK", peer).await {
             error!("Failed to send UDP response: {:?}", e);
@@ -0,0 +1,292 @@
+use std::{
+    future::Future,
+    net::IpAddr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use tokio::net::UdpSocket;
+use tokio_util::sync::{CancellationToken, DropGuard};
+
+use crate::{
+    NetworkInterface, Result,
+    network::{join_group, leave_group, multicast_join_failed},
+};
+
+/// Handle given to a running service instance through
+/// [`on_started`](crate::TcpHandler::on_started).
+///
+/// It lives as long as the instance: when the instance stops (shutdown, crash or
+/// restart), its token is cancelled and every task spawned through it ends.
+#[derive(Debug)]
+pub struct ServiceContext {
+    name: String,
+    token: CancellationToken,
+    multicast: Option<MulticastMembership>,
+    _stop: DropGuard,
+}
+
+impl ServiceContext {
+    pub(crate) fn new(name: impl Into<String>) -> Self {
+        let token = CancellationToken::new();
+        Self {
+            name: name.into(),
+            multicast: None,
+            _stop: token.clone().drop_guard(),
+            token,
+        }
+    }
+
+    pub(crate) fn with_multicast(mut self, multicast: MulticastMembership) -> Self {
+        self.multicast = Some(multicast);
+        self
+    }
+
+    /// Returns the display name of the service.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a token cancelled when the service instance stops.
+    pub fn token(&self) -> CancellationToken {
+        self.token.clone()
+    }
+
+    /// Runs `task` in the background until it completes or the service instance stops.
+    ///
+    /// Use it for work tied to the service, such as a heartbeat or a periodic
+    /// cache refresh.
+    pub fn spawn<F>(&self, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let token = self.token.clone();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = task => {}
+                _ = token.cancelled() => {}
+            }
+        });
+    }
+
+    /// Returns the multicast membership of a UDP service, or `None` for TCP services.
+    ///
+    /// The returned handle can be kept to change the groups while the instance runs.
+    pub fn multicast(&self) -> Option<MulticastMembership> {
+        self.multicast.clone()
+    }
+}
+
+/// Multicast groups joined by the sockets of a running UDP service instance.
+///
+/// Starts with the handler's [`multicast_addrs`](crate::UdpHandler::multicast_addrs).
+/// Changes apply to every socket of the instance and are lost when it restarts.
+#[derive(Debug, Clone)]
+pub struct MulticastMembership {
+    sockets: Vec<Arc<UdpSocket>>,
+    iface: Arc<NetworkInterface>,
+    groups: Arc<Mutex<Vec<IpAddr>>>,
+}
+
+impl MulticastMembership {
+    pub(crate) fn new(
+        sockets: Vec<Arc<UdpSocket>>,
+        iface: Arc<NetworkInterface>,
+        groups: Vec<IpAddr>,
+    ) -> Self {
+        Self {
+            sockets,
+            iface,
+            groups: Arc::new(Mutex::new(groups)),
+        }
+    }
+
+    /// Returns the groups currently joined.
+    pub fn groups(&self) -> Vec<IpAddr> {
+        self.groups
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Joins `group` on every socket. Joining a group twice is a no-op.
+    pub fn join(&self, group: IpAddr) -> Result<()> {
+        let mut groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        if groups.contains(&group) {
+            return Ok(());
+        }
+        for socket in &self.sockets {
+            join_group(socket, &group, &self.iface)
+                .map_err(|e| multicast_join_failed(&group, &self.iface.name, e))?;
+        }
+        groups.push(group);
+        Ok(())
+    }
+
+    /// Leaves `group` on every socket. Leaving a group that was not joined is a no-op.
+    pub fn leave(&self, group: IpAddr) -> Result<()> {
+        let mut groups = self.groups.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(pos) = groups.iter().position(|g| *g == group) else {
+            return Ok(());
+        };
+        let remaining: Vec<_> = groups.iter().filter(|g| **g != group).copied().collect();
+        for socket in &self.sockets {
+            leave_group(socket, &group, &self.iface, &remaining)?;
+        }
+        groups.remove(pos);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        BindMode, PacketInfo, TcpHandler, TcpRuntime, UdpHandler, UdpRuntime, async_trait,
+    };
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tokio::{
+        net::TcpStream,
+        sync::{mpsc, oneshot},
+        time::{sleep, timeout},
+    };
+
+    struct TickingTcp(Arc<AtomicUsize>);
+    #[async_trait]
+    impl TcpHandler for TickingTcp {
+        fn name(&self) -> &'static str {
+            "TickingTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_started(&self, ctx: &ServiceContext) {
+            let ticks = self.0.clone();
+            ctx.spawn(async move {
+                loop {
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                    sleep(Duration::from_millis(10)).await;
+                }
+            });
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_background_task_stops_with_service() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let runtime = TcpRuntime::new(TickingTcp(ticks.clone()), iface);
+        let token = CancellationToken::new();
+        let serve = tokio::spawn({
+            let token = token.clone();
+            async move { runtime.serve(token).await }
+        });
+
+        sleep(Duration::from_millis(100)).await;
+        assert!(ticks.load(Ordering::SeqCst) > 0);
+
+        token.cancel();
+        serve.await.unwrap().unwrap();
+        sleep(Duration::from_millis(20)).await;
+        let stopped_at = ticks.load(Ordering::SeqCst);
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), stopped_at);
+    }
+
+    struct SubscriberUdp {
+        bind_mode: BindMode,
+        membership: Mutex<Option<oneshot::Sender<MulticastMembership>>>,
+        received: mpsc::UnboundedSender<Vec<u8>>,
+    }
+    #[async_trait]
+    impl UdpHandler for SubscriberUdp {
+        fn name(&self) -> &'static str {
+            "SubscriberUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            self.bind_mode.clone()
+        }
+        async fn on_started(&self, ctx: &ServiceContext) {
+            if let Some(tx) = self.membership.lock().unwrap().take() {
+                let _ = tx.send(ctx.multicast().unwrap());
+            }
+        }
+        async fn on_packet(
+            &self,
+            data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            let _ = self.received.send(data.to_vec());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_multicast_join_and_leave_at_runtime() {
+        let group = IpAddr::V4(Ipv4Addr::new(239, 255, 42, 7));
+        let server = std::net::UdpSocket::bind("0.0.0.0:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let (membership_tx, membership_rx) = oneshot::channel();
+        let (received_tx, mut received) = mpsc::unbounded_channel();
+        let handler = SubscriberUdp {
+            bind_mode: BindMode::PreBound(server.into()),
+            membership: Mutex::new(Some(membership_tx)),
+            received: received_tx,
+        };
+
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let runtime = UdpRuntime::new(handler, iface);
+        let token = CancellationToken::new();
+        let serve = tokio::spawn({
+            let token = token.clone();
+            async move { runtime.serve(token).await }
+        });
+        let membership = membership_rx.await.unwrap();
+        assert!(membership.groups().is_empty());
+
+        let sender = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket2::SockRef::from(&sender)
+            .set_multicast_if_v4(&Ipv4Addr::LOCALHOST)
+            .unwrap();
+        let target = SocketAddr::new(group, port);
+
+        membership.join(group).unwrap();
+        assert_eq!(membership.groups(), [group]);
+        sender.send_to(b"joined", target).await.unwrap();
+        let data = timeout(Duration::from_secs(1), received.recv())
+            .await
+            .unwrap();
+        assert_eq!(data.as_deref(), Some(&b"joined"[..]));
+
+        membership.leave(group).unwrap();
+        assert!(membership.groups().is_empty());
+        sender.send_to(b"left", target).await.unwrap();
+        assert!(
+            timeout(Duration::from_millis(200), received.recv())
+                .await
+                .is_err()
+        );
+
+        token.cancel();
+        serve.await.unwrap().unwrap();
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    fmt,
+    future::Future,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+#[cfg(feature = "tracing")]
+use tracing::{Instrument, info_span};
+
+/// Source of the default IDs, shared by every service so IDs are unique per process.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+tokio::task_local! {
+    static CURRENT: ConnectionId;
+}
+
+/// Correlation ID of one accepted connection or received datagram.
+///
+/// Handlers read it with [`ConnectionId::current`] (or [`PacketInfo::id`](crate::PacketInfo::id)
+/// for datagrams), and with the `tracing` feature it is recorded on the
+/// `connection` span wrapping the handler, so every log line it emits carries it.
+/// Override [`TcpHandler::connection_id`](crate::TcpHandler::connection_id) or
+/// [`UdpHandler::connection_id`](crate::UdpHandler::connection_id) to use IDs
+/// from another scheme, e.g. to match upstream request IDs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct ConnectionId(u64);
+
+impl ConnectionId {
+    /// Wraps an ID produced by a custom generator.
+    pub const fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// Returns the next ID of the default generator, a process-wide counter
+    /// starting at 1.
+    pub fn next() -> Self {
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+
+    /// Returns the ID of the connection or datagram handled by the current task,
+    /// or `None` outside of a handler.
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(|id| *id).ok()
+    }
+
+    /// Returns the raw ID.
+    pub const fn get(self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for ConnectionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Runs `future` as the handler of connection `id` from `peer`, making the ID
+/// available to [`ConnectionId::current`] and to the `connection` span.
+pub(crate) fn in_connection<F>(
+    id: ConnectionId,
+    peer: &SocketAddr,
+    future: F,
+) -> impl Future<Output = F::Output>
+where
+    F: Future,
+{
+    #[cfg(feature = "tracing")]
+    let future = future.instrument(info_span!("connection", id = %id, peer = %peer));
+    #[cfg(not(feature = "tracing"))]
+    let _ = peer;
+    CURRENT.scope(id, future)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_current_id_is_scoped() {
+        let peer = "127.0.0.1:53".parse().unwrap();
+        assert_eq!(ConnectionId::current(), None);
+
+        let id = ConnectionId::new(42);
+        let seen = in_connection(id, &peer, async { ConnectionId::current() }).await;
+        assert_eq!(seen, Some(id));
+        assert_eq!(ConnectionId::current(), None);
+
+        assert_ne!(ConnectionId::next(), ConnectionId::next());
+        assert_eq!(id.to_string(), "42");
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    io::{self, IoSliceMut},
+    net::{SocketAddr, SocketAddrV4, SocketAddrV6},
+    os::fd::AsRawFd,
+};
+
+use nix::sys::socket::{
+    ControlMessageOwned, MsgFlags, SockaddrStorage, recvmsg, setsockopt, sockopt,
+};
+use socket2::Socket;
+use tokio::{io::Interest, net::UdpSocket};
+
+use crate::Ecn;
+
+/// Asks the kernel to pass the TOS byte (IPv4) or traffic class (IPv6) of each
+/// datagram received on `socket`.
+///
+/// A dual-stack IPv6 socket also receives IPv4 datagrams, which carry a TOS byte.
+pub fn enable(socket: &Socket, v6: bool, v6_only: bool) -> io::Result<()> {
+    if v6 {
+        setsockopt(socket, sockopt::Ipv6RecvTClass, &true)?;
+        if v6_only {
+            return Ok(());
+        }
+    }
+    setsockopt(socket, sockopt::IpRecvTos, &true)?;
+    Ok(())
+}
+
+/// Receives a datagram into `buf` with `recvmsg`, along with its ECN codepoint.
+///
+/// The codepoint is `None` when the kernel passed no TOS or traffic class, i.e.
+/// when [`enable`] was not called on the socket.
+pub async fn recv_from(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+) -> io::Result<(usize, SocketAddr, Option<Ecn>)> {
+    socket
+        .async_io(Interest::READABLE, || recv_once(socket, buf))
+        .await
+}
+
+fn recv_once(socket: &UdpSocket, buf: &mut [u8]) -> io::Result<(usize, SocketAddr, Option<Ecn>)> {
+    let mut iov = [IoSliceMut::new(buf)];
+    let mut cmsg = nix::cmsg_space!(i32);
+    let msg = recvmsg::<SockaddrStorage>(
+        socket.as_raw_fd(),
+        &mut iov,
+        Some(&mut cmsg),
+        MsgFlags::empty(),
+    )?;
+
+    let ecn = msg.cmsgs()?.find_map(|cmsg| match cmsg {
+        ControlMessageOwned::Ipv4Tos(tos) => Some(Ecn::from_tos(tos)),
+        ControlMessageOwned::Ipv6TClass(class) => Some(Ecn::from_tos(class as u8)),
+        _ => None,
+    });
+    let peer = msg.address.as_ref().and_then(socket_addr).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "datagram without an IP source")
+    })?;
+    Ok((msg.bytes, peer, ecn))
+}
+
+fn socket_addr(addr: &SockaddrStorage) -> Option<SocketAddr> {
+    if let Some(v4) = addr.as_sockaddr_in() {
+        return Some(SocketAddrV4::from(*v4).into());
+    }
+    addr.as_sockaddr_in6()
+        .map(|v6| SocketAddrV6::from(*v6).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socket2::{Domain, Type};
+
+    #[tokio::test]
+    async fn test_traffic_class_is_reported() {
+        let server = Socket::new(Domain::IPV6, Type::DGRAM, None).unwrap();
+        server.set_only_v6(true).unwrap();
+        enable(&server, true, true).unwrap();
+        server
+            .bind(&"[::1]:0".parse::<SocketAddr>().unwrap().into())
+            .unwrap();
+        server.set_nonblocking(true).unwrap();
+        let server = UdpSocket::from_std(server.into()).unwrap();
+
+        let client = Socket::new(Domain::IPV6, Type::DGRAM, None).unwrap();
+        client.set_tclass_v6(0b11).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let client = UdpSocket::from_std(client.into()).unwrap();
+        client.connect(server.local_addr().unwrap()).await.unwrap();
+        client.send(b"ce").await.unwrap();
+
+        let mut buf = [0; 16];
+        let (n, peer, ecn) = recv_from(&server, &mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ce");
+        assert_eq!(peer, client.local_addr().unwrap());
+        assert_eq!(ecn, Some(Ecn::Ce));
+    }
+
+    #[tokio::test]
+    async fn test_no_codepoint_without_enable() {
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client
+            .send_to(b"plain", server.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let mut buf = [0; 16];
+        let (n, _, ecn) = recv_from(&server, &mut buf).await.unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(ecn, None);
+    }
+}
@@ -14,12 +14,240 @@ pub enum Error {
     #[error("IO operation failed: {0}")]
     Io(#[from] std::io::Error),
 
+    #[error("Network interface '{0}' disappeared")]
+    InterfaceGone(String),
+
     #[error("No valid socket address found for binding")]
     NoAddrAvailable,
 
+    #[error("Network interface '{0}' has no IP address to bind")]
+    InterfaceHasNoAddr(String),
+
+    #[error("Failed to bind any of the {0} candidate addresses")]
+    AllBindsFailed(usize),
+
+    #[error("Address {0} is already in use")]
+    AddrInUse(std::net::SocketAddr),
+
+    #[error("Failed to join multicast group {group} on interface '{iface}': {source}")]
+    MulticastJoinFailed {
+        group: std::net::IpAddr,
+        iface: String,
+        source: std::io::Error,
+    },
+
+    #[error("Invalid CIDR notation: {0}")]
+    InvalidCidr(String),
+
+    #[error("Pre-bound socket does not match the service protocol (expected a {0})")]
+    PreBoundMismatch(&'static str),
+
+    #[error("No service named '{0}' is registered")]
+    UnknownService(String),
+
+    #[error("Service '{0}' is not served by a handler of this type")]
+    HandlerMismatch(String),
+
+    #[error("Frame of {0} bytes exceeds the {1} byte limit")]
+    FrameTooLarge(usize, usize),
+
+    #[error("Service '{0}' sent no heartbeat for {1:?}")]
+    Unresponsive(String, std::time::Duration),
+
     #[error("Service '{0}' failed to start or crashed")]
     ServiceFailure(String),
 }
 
+/// Coarse category of an [`Error`](enum@Error), to decide whether to retry or give up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The setup is wrong (unknown interface, bad CIDR, mismatched socket...).
+    /// Retrying will not help.
+    Config,
+    /// A network resource is missing or busy. It may come back on its own.
+    Network,
+    /// An I/O operation failed for a reason that is unlikely to go away.
+    Io,
+    /// A peer sent data that violates the protocol.
+    Protocol,
+    /// A supervised service crashed or hung.
+    Service,
+}
+
+impl ErrorKind {
+    /// Returns whether errors of this kind may go away when retried.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::Network | Self::Service)
+    }
+}
+
+impl Error {
+    /// Returns the category of the error.
+    ///
+    /// I/O errors are [`ErrorKind::Network`] when their kind is typically transient
+    /// (timeouts, refused or reset connections, unreachable networks...) and
+    /// [`ErrorKind::Io`] otherwise.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::InterfaceNotFound(_)
+            | Self::InvalidInterfaceName(_)
+            | Self::InvalidCidr(_)
+            | Self::PreBoundMismatch(_)
+            | Self::UnknownService(_)
+            | Self::HandlerMismatch(_) => ErrorKind::Config,
+            Self::InterfaceGone(_)
+            | Self::NoAddrAvailable
+            | Self::InterfaceHasNoAddr(_)
+            | Self::AllBindsFailed(_)
+            | Self::AddrInUse(_)
+            | Self::MulticastJoinFailed { .. } => ErrorKind::Network,
+            Self::Io(e) => io_kind(e),
+            Self::FrameTooLarge(..) => ErrorKind::Protocol,
+            Self::Unresponsive(..) | Self::ServiceFailure(_) => ErrorKind::Service,
+        }
+    }
+
+    /// Shorthand for `self.kind().is_retryable()`.
+    pub fn is_retryable(&self) -> bool {
+        self.kind().is_retryable()
+    }
+
+    /// Returns whether retrying is pointless, the opposite of [`is_retryable`](Self::is_retryable).
+    pub fn is_fatal(&self) -> bool {
+        !self.is_retryable()
+    }
+}
+
+/// Unwraps I/O errors and wraps the others, so that Maestro calls can be used
+/// with `?` in functions returning [`std::io::Result`].
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        match e {
+            Error::Io(e) => e,
+            Error::AddrInUse(_) => Self::new(std::io::ErrorKind::AddrInUse, e),
+            Error::InterfaceNotFound(_) | Error::UnknownService(_) => {
+                Self::new(std::io::ErrorKind::NotFound, e)
+            }
+            Error::InvalidInterfaceName(_) | Error::InvalidCidr(_) => {
+                Self::new(std::io::ErrorKind::InvalidInput, e)
+            }
+            Error::FrameTooLarge(..) => Self::new(std::io::ErrorKind::InvalidData, e),
+            Error::Unresponsive(..) => Self::new(std::io::ErrorKind::TimedOut, e),
+            e => Self::other(e),
+        }
+    }
+}
+
+// Error reporting crates (`anyhow`, `eyre`, boxed errors) require these bounds.
+const _: fn() = || {
+    fn assert_error<T: std::error::Error + Send + Sync + 'static>() {}
+    assert_error::<Error>();
+};
+
+fn io_kind(e: &std::io::Error) -> ErrorKind {
+    use std::io::ErrorKind as Io;
+
+    match e.kind() {
+        Io::TimedOut
+        | Io::Interrupted
+        | Io::WouldBlock
+        | Io::ConnectionRefused
+        | Io::ConnectionReset
+        | Io::ConnectionAborted
+        | Io::NotConnected
+        | Io::BrokenPipe
+        | Io::UnexpectedEof
+        | Io::AddrInUse
+        | Io::AddrNotAvailable
+        | Io::NetworkDown
+        | Io::NetworkUnreachable
+        | Io::HostUnreachable => ErrorKind::Network,
+        _ => ErrorKind::Io,
+    }
+}
+
 /// Helper alias for `Result<T, maestro_rs::Error>`
 pub type Result<T> = std::result::Result<T, Error>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{io, time::Duration};
+
+    #[test]
+    fn test_error_kinds() {
+        let cases = [
+            (Error::InterfaceNotFound("eth9".into()), ErrorKind::Config),
+            (Error::InvalidInterfaceName("".into()), ErrorKind::Config),
+            (Error::InvalidCidr("10.0.0.0/33".into()), ErrorKind::Config),
+            (Error::PreBoundMismatch("UDP socket"), ErrorKind::Config),
+            (Error::UnknownService("dns".into()), ErrorKind::Config),
+            (Error::HandlerMismatch("dns".into()), ErrorKind::Config),
+            (Error::InterfaceGone("eth0".into()), ErrorKind::Network),
+            (Error::NoAddrAvailable, ErrorKind::Network),
+            (Error::InterfaceHasNoAddr("eth0".into()), ErrorKind::Network),
+            (Error::AllBindsFailed(2), ErrorKind::Network),
+            (
+                Error::AddrInUse("127.0.0.1:53".parse().unwrap()),
+                ErrorKind::Network,
+            ),
+            (
+                Error::MulticastJoinFailed {
+                    group: "239.1.2.3".parse().unwrap(),
+                    iface: "eth0".into(),
+                    source: io::ErrorKind::AddrNotAvailable.into(),
+                },
+                ErrorKind::Network,
+            ),
+            (
+                Error::Io(io::ErrorKind::TimedOut.into()),
+                ErrorKind::Network,
+            ),
+            (
+                Error::Io(io::ErrorKind::ConnectionReset.into()),
+                ErrorKind::Network,
+            ),
+            (
+                Error::Io(io::ErrorKind::PermissionDenied.into()),
+                ErrorKind::Io,
+            ),
+            (Error::FrameTooLarge(32, 16), ErrorKind::Protocol),
+            (
+                Error::Unresponsive("dns".into(), Duration::from_secs(1)),
+                ErrorKind::Service,
+            ),
+            (Error::ServiceFailure("dns".into()), ErrorKind::Service),
+        ];
+        for (error, kind) in cases {
+            assert_eq!(error.kind(), kind, "{error}");
+        }
+    }
+
+    #[test]
+    fn test_retryable_kinds() {
+        assert!(Error::Io(io::ErrorKind::TimedOut.into()).is_retryable());
+        assert!(Error::ServiceFailure("dns".into()).is_retryable());
+        assert!(!Error::InterfaceNotFound("eth9".into()).is_retryable());
+        assert!(!Error::FrameTooLarge(32, 16).is_retryable());
+        assert!(!Error::Io(io::ErrorKind::PermissionDenied.into()).is_retryable());
+    }
+
+    #[test]
+    fn test_error_converts_for_applications() {
+        fn boxed() -> std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            Err(Error::InterfaceNotFound("eth9".into()))?
+        }
+        let e = boxed().unwrap_err();
+        assert!(e.downcast_ref::<Error>().is_some_and(Error::is_fatal));
+
+        fn io() -> io::Result<()> {
+            Err(Error::FrameTooLarge(32, 16))?
+        }
+        assert_eq!(io().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let e = io::Error::from(Error::Io(io::ErrorKind::TimedOut.into()));
+        assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+        assert!(e.get_ref().is_none());
+    }
+}
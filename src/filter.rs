@@ -0,0 +1,508 @@
+#[cfg(feature = "tracing")]
+use tracing::debug;
+
+use async_trait::async_trait;
+use std::{
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    BindMode, ConnectionId, Error, IpFamily, PacketInfo, Result, ServiceContext,
+    SocketFailurePolicy, TcpConcurrency, TcpHandler, UdpHandler,
+};
+
+/// An IPv4 or IPv6 network in CIDR notation (e.g. `10.0.0.0/8`, `fd00::/8`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl Cidr {
+    /// Creates a network from an address and prefix length.
+    pub fn new(addr: IpAddr, prefix: u8) -> Result<Self> {
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        if prefix > max {
+            return Err(Error::InvalidCidr(format!("{addr}/{prefix}")));
+        }
+        Ok(Self { addr, prefix })
+    }
+
+    /// Returns `true` if `ip` belongs to this network.
+    ///
+    /// IPv4-mapped IPv6 addresses (as seen on dual-stack sockets) are matched
+    /// against IPv4 networks.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = Error;
+
+    /// Parses `addr/prefix`. A bare address is treated as a single host.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidCidr(s.to_string());
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+
+        let addr = IpAddr::from_str(addr).map_err(|_| invalid())?;
+        let prefix = match prefix {
+            Some(p) => p.parse::<u8>().map_err(|_| invalid())?,
+            None if addr.is_ipv4() => 32,
+            None => 128,
+        };
+
+        Self::new(addr, prefix).map_err(|_| invalid())
+    }
+}
+
+/// Wraps a [`TcpHandler`] or [`UdpHandler`] with CIDR-based allow/deny lists.
+///
+/// Connections and packets from rejected peers are dropped before reaching the
+/// inner handler; rejected connections are first handed to its
+/// [`on_reject`](TcpHandler::on_reject). Deny rules take precedence over allow rules, and an empty
+/// allow list admits every address that is not denied.
+///
+/// # Example
+/// ```rust,ignore
+/// let filtered = IpFilter::new(MyTcpService)
+///     .allow("10.0.0.0/8".parse()?)
+///     .deny("10.0.13.0/24".parse()?);
+/// supervisor.add(filtered);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IpFilter<H> {
+    inner: H,
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl<H> IpFilter<H> {
+    /// Wraps `inner` with empty allow/deny lists (everything is accepted).
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            allow: Vec::new(),
+            deny: Vec::new(),
+        }
+    }
+
+    /// Adds a network to the allow list.
+    pub fn allow(mut self, cidr: Cidr) -> Self {
+        self.allow.push(cidr);
+        self
+    }
+
+    /// Adds a network to the deny list.
+    pub fn deny(mut self, cidr: Cidr) -> Self {
+        self.deny.push(cidr);
+        self
+    }
+
+    /// Returns `true` if traffic from `ip` should reach the inner handler.
+    pub fn is_allowed(&self, ip: &IpAddr) -> bool {
+        if self.deny.iter().any(|c| c.contains(ip)) {
+            return false;
+        }
+        self.allow.is_empty() || self.allow.iter().any(|c| c.contains(ip))
+    }
+
+    /// Returns a reference to the wrapped handler.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<H: TcpHandler> TcpHandler for IpFilter<H> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn port(&self) -> u16 {
+        self.inner.port()
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        self.inner.bind_mode()
+    }
+
+    fn v6_only(&self) -> bool {
+        self.inner.v6_only()
+    }
+
+    fn preferred_family(&self) -> Option<IpFamily> {
+        self.inner.preferred_family()
+    }
+
+    fn freebind(&self) -> bool {
+        self.inner.freebind()
+    }
+
+    fn transparent(&self) -> bool {
+        self.inner.transparent()
+    }
+
+    fn network_namespace(&self) -> Option<&str> {
+        self.inner.network_namespace()
+    }
+
+    fn strict_interface(&self) -> bool {
+        self.inner.strict_interface()
+    }
+
+    fn configure_socket(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        self.inner.configure_socket(socket)
+    }
+
+    fn log_rate_limit(&self) -> Option<Duration> {
+        self.inner.log_rate_limit()
+    }
+
+    fn accept_shards(&self) -> usize {
+        self.inner.accept_shards()
+    }
+
+    fn accept_batch(&self) -> usize {
+        self.inner.accept_batch()
+    }
+
+    fn max_connections(&self) -> Option<usize> {
+        self.inner.max_connections()
+    }
+
+    fn concurrency(&self) -> TcpConcurrency {
+        self.inner.concurrency()
+    }
+
+    fn max_in_flight(&self) -> Option<usize> {
+        self.inner.max_in_flight()
+    }
+
+    fn accept_deadline(&self) -> Option<Duration> {
+        self.inner.accept_deadline()
+    }
+
+    fn exit_on_accept_deadline(&self) -> bool {
+        self.inner.exit_on_accept_deadline()
+    }
+
+    fn liveness_timeout(&self) -> Option<Duration> {
+        self.inner.liveness_timeout()
+    }
+
+    fn accept_stall_timeout(&self) -> Option<Duration> {
+        self.inner.accept_stall_timeout()
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.inner.connection_id()
+    }
+
+    async fn on_connection(
+        &self,
+        stream: TcpStream,
+        peer: &SocketAddr,
+        local: &SocketAddr,
+        token: CancellationToken,
+    ) {
+        if !self.is_allowed(&peer.ip()) {
+            #[cfg(feature = "tracing")]
+            debug!("[{}] Rejected TCP connection from {}", self.name(), peer);
+            self.inner.on_reject(stream, peer).await;
+            return;
+        }
+        self.inner.on_connection(stream, peer, local, token).await;
+    }
+
+    async fn on_reject(&self, stream: TcpStream, peer: &SocketAddr) {
+        self.inner.on_reject(stream, peer).await;
+    }
+
+    async fn on_started(&self, ctx: &ServiceContext) {
+        self.inner.on_started(ctx).await;
+    }
+
+    async fn on_shutdown(&self) {
+        self.inner.on_shutdown().await;
+    }
+}
+
+#[async_trait]
+impl<H: UdpHandler> UdpHandler for IpFilter<H> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn port(&self) -> u16 {
+        self.inner.port()
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        self.inner.bind_mode()
+    }
+
+    fn v6_only(&self) -> bool {
+        self.inner.v6_only()
+    }
+
+    fn preferred_family(&self) -> Option<IpFamily> {
+        self.inner.preferred_family()
+    }
+
+    fn freebind(&self) -> bool {
+        self.inner.freebind()
+    }
+
+    fn transparent(&self) -> bool {
+        self.inner.transparent()
+    }
+
+    fn network_namespace(&self) -> Option<&str> {
+        self.inner.network_namespace()
+    }
+
+    fn strict_interface(&self) -> bool {
+        self.inner.strict_interface()
+    }
+
+    fn configure_socket(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        self.inner.configure_socket(socket)
+    }
+
+    fn log_rate_limit(&self) -> Option<Duration> {
+        self.inner.log_rate_limit()
+    }
+
+    fn multicast_addrs(&self) -> &[IpAddr] {
+        self.inner.multicast_addrs()
+    }
+
+    fn multicast_join_required(&self) -> bool {
+        self.inner.multicast_join_required()
+    }
+
+    fn require_multicast(&self) -> bool {
+        self.inner.require_multicast()
+    }
+
+    fn broadcast(&self) -> bool {
+        self.inner.broadcast()
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        self.inner.max_datagram_size()
+    }
+
+    fn max_in_flight(&self) -> Option<usize> {
+        self.inner.max_in_flight()
+    }
+
+    fn pin_shards(&self) -> bool {
+        self.inner.pin_shards()
+    }
+
+    fn buffer_from_mtu(&self) -> bool {
+        self.inner.buffer_from_mtu()
+    }
+
+    fn recv_ecn(&self) -> bool {
+        self.inner.recv_ecn()
+    }
+
+    fn concurrent_packets(&self) -> usize {
+        self.inner.concurrent_packets()
+    }
+
+    fn on_socket_failure(&self) -> SocketFailurePolicy {
+        self.inner.on_socket_failure()
+    }
+
+    fn liveness_timeout(&self) -> Option<Duration> {
+        self.inner.liveness_timeout()
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.inner.connection_id()
+    }
+
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        info: &PacketInfo,
+    ) {
+        if !self.is_allowed(&peer.ip()) {
+            #[cfg(feature = "tracing")]
+            debug!("[{}] Dropped UDP packet from {}", self.name(), peer);
+            return;
+        }
+        self.inner.on_packet(data, socket, peer, info).await;
+    }
+
+    async fn on_started(&self, ctx: &ServiceContext) {
+        self.inner.on_started(ctx).await;
+    }
+
+    async fn on_shutdown(&self) {
+        self.inner.on_shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr_parsing() {
+        assert!("10.0.0.0/8".parse::<Cidr>().is_ok());
+        assert!("fd00::/8".parse::<Cidr>().is_ok());
+        assert!("192.168.1.1".parse::<Cidr>().is_ok());
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("fd00::/129".parse::<Cidr>().is_err());
+        assert!("not-an-ip/8".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_allow_list() {
+        let filter = IpFilter::new(())
+            .allow("10.0.0.0/8".parse().unwrap())
+            .allow("2001:db8::/32".parse().unwrap());
+
+        assert!(filter.is_allowed(&ip("10.1.2.3")));
+        assert!(filter.is_allowed(&ip("::ffff:10.1.2.3")));
+        assert!(!filter.is_allowed(&ip("192.168.0.1")));
+        assert!(filter.is_allowed(&ip("2001:db8::1")));
+        assert!(!filter.is_allowed(&ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn test_deny_list() {
+        let filter = IpFilter::new(())
+            .deny("192.168.0.0/16".parse().unwrap())
+            .deny("fe80::/10".parse().unwrap());
+
+        assert!(!filter.is_allowed(&ip("192.168.4.2")));
+        assert!(filter.is_allowed(&ip("172.16.0.1")));
+        assert!(!filter.is_allowed(&ip("fe80::1")));
+        assert!(filter.is_allowed(&ip("::1")));
+    }
+
+    #[test]
+    fn test_deny_overrides_allow() {
+        let filter = IpFilter::new(())
+            .allow("10.0.0.0/8".parse().unwrap())
+            .deny("10.0.13.0/24".parse().unwrap());
+
+        assert!(filter.is_allowed(&ip("10.0.12.1")));
+        assert!(!filter.is_allowed(&ip("10.0.13.1")));
+    }
+
+    struct CountingUdp(AtomicUsize);
+    #[async_trait]
+    impl UdpHandler for CountingUdp {
+        fn name(&self) -> &'static str {
+            "CountingUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    struct PoliteTcp;
+    #[async_trait]
+    impl TcpHandler for PoliteTcp {
+        fn name(&self) -> &'static str {
+            "PoliteTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let _ = s.write_all(b"welcome").await;
+        }
+        async fn on_reject(&self, mut s: TcpStream, _p: &SocketAddr) {
+            let _ = s.write_all(b"NAK").await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_connection_gets_reply() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local = listener.local_addr().unwrap();
+        let filter = IpFilter::new(PoliteTcp).deny("127.0.0.0/8".parse().unwrap());
+
+        let mut client = TcpStream::connect(local).await.unwrap();
+        let (stream, peer) = listener.accept().await.unwrap();
+        filter
+            .on_connection(stream, &peer, &local, CancellationToken::new())
+            .await;
+
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).await.unwrap();
+        assert_eq!(reply, b"NAK");
+    }
+
+    #[tokio::test]
+    async fn test_filter_intercepts_packets() {
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let filter =
+            IpFilter::new(CountingUdp(AtomicUsize::new(0))).allow("127.0.0.0/8".parse().unwrap());
+
+        let allowed: SocketAddr = "127.0.0.1:4000".parse().unwrap();
+        let denied: SocketAddr = "[2001:db8::1]:4000".parse().unwrap();
+        filter
+            .on_packet(
+                b"ok",
+                socket.clone(),
+                &allowed,
+                &PacketInfo::new(2, allowed),
+            )
+            .await;
+        filter
+            .on_packet(b"no", socket, &denied, &PacketInfo::new(2, allowed))
+            .await;
+
+        assert_eq!(filter.inner().0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,248 @@
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{Error, Result};
+
+/// Reads one length-prefixed frame: a big-endian `u32` length followed by the payload.
+///
+/// Frames announcing more than `max_len` bytes are rejected before anything is
+/// allocated, so a peer cannot exhaust memory with a forged length.
+///
+/// # Errors
+/// Returns [`Error::FrameTooLarge`] for oversized frames, and an I/O error of kind
+/// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) if the stream ends mid-frame.
+pub async fn read_frame<R>(reader: &mut R, max_len: usize) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let len = reader.read_u32().await? as usize;
+    if len > max_len {
+        return Err(Error::FrameTooLarge(len, max_len));
+    }
+
+    let mut payload = vec![0u8; len];
+    reader.read_exact(&mut payload).await?;
+    Ok(payload)
+}
+
+/// Writes `payload` as one length-prefixed frame readable by [`read_frame`], then
+/// flushes the writer.
+///
+/// Short writes are retried until the whole frame is out, so this is safe on
+/// sockets that accept only part of a buffer at a time.
+///
+/// # Errors
+/// Returns [`Error::FrameTooLarge`] if the payload does not fit in a `u32` length,
+/// and an I/O error of kind [`WriteZero`](std::io::ErrorKind::WriteZero) if the
+/// writer stops accepting bytes mid-frame.
+pub async fn write_frame<W>(writer: &mut W, payload: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    writer.write_all(&frame_len(payload)?.to_be_bytes()).await?;
+    writer.write_all(payload).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Writes several frames at once, then flushes the writer.
+///
+/// The frames are coalesced into a single buffer first, so a batch of small
+/// frames costs one write instead of two per frame. Nothing is written if any
+/// payload is too large.
+///
+/// # Errors
+/// Same as [`write_frame`].
+pub async fn write_frames<W, I>(writer: &mut W, payloads: I) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut buf = Vec::new();
+    for payload in payloads {
+        let payload = payload.as_ref();
+        buf.extend_from_slice(&frame_len(payload)?.to_be_bytes());
+        buf.extend_from_slice(payload);
+    }
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads one DNS-over-TCP message: a big-endian `u16` length followed by the
+/// message (RFC 1035, section 4.2.2).
+///
+/// # Errors
+/// Returns an I/O error of kind [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof)
+/// if the stream ends mid-message.
+pub async fn read_dns_message<R>(reader: &mut R) -> Result<Vec<u8>>
+where
+    R: AsyncRead + Unpin,
+{
+    let len = reader.read_u16().await? as usize;
+    let mut message = vec![0u8; len];
+    reader.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+/// Writes `message` with the DNS-over-TCP length prefix, then flushes the writer.
+///
+/// The prefix and message go out in a single write.
+///
+/// # Errors
+/// Returns [`Error::FrameTooLarge`] if the message is longer than 65535 bytes.
+pub async fn write_dns_message<W>(writer: &mut W, message: &[u8]) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let len = u16::try_from(message.len())
+        .map_err(|_| Error::FrameTooLarge(message.len(), u16::MAX as usize))?;
+    let mut buf = Vec::with_capacity(2 + message.len());
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(message);
+    writer.write_all(&buf).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+fn frame_len(payload: &[u8]) -> Result<u32> {
+    u32::try_from(payload.len()).map_err(|_| Error::FrameTooLarge(payload.len(), u32::MAX as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::ErrorKind,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    /// Accepts at most `chunk` bytes per call and is not ready every other call.
+    #[derive(Default)]
+    struct ThrottledWriter {
+        chunk: usize,
+        written: Vec<u8>,
+        writes: usize,
+        pending: bool,
+        flushed: bool,
+    }
+
+    impl AsyncWrite for ThrottledWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let n = buf.len().min(self.chunk);
+            self.written.extend_from_slice(&buf[..n]);
+            self.writes += 1;
+            self.flushed = false;
+            Poll::Ready(Ok(n))
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+        ) -> Poll<std::io::Result<()>> {
+            self.flushed = true;
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, b"hello").await.unwrap();
+        assert_eq!(buf, b"\0\0\0\x05hello");
+
+        let frame = read_frame(&mut buf.as_slice(), 16).await.unwrap();
+        assert_eq!(frame, b"hello");
+    }
+
+    #[tokio::test]
+    async fn test_oversized_frame_is_rejected() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, &[0u8; 32]).await.unwrap();
+
+        let res = read_frame(&mut buf.as_slice(), 16).await;
+        assert!(matches!(res, Err(Error::FrameTooLarge(32, 16))));
+    }
+
+    #[tokio::test]
+    async fn test_truncated_frame() {
+        let res = read_frame(&mut &b"\0\0\0\x05hel"[..], 16).await;
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof));
+
+        let res = read_frame(&mut &b"\0\0"[..], 16).await;
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof));
+    }
+
+    #[tokio::test]
+    async fn test_partial_writes() {
+        let mut writer = ThrottledWriter {
+            chunk: 3,
+            ..Default::default()
+        };
+        write_frame(&mut writer, b"hello").await.unwrap();
+        assert_eq!(writer.written, b"\0\0\0\x05hello");
+        assert!(writer.flushed);
+
+        let mut writer = ThrottledWriter {
+            chunk: 3,
+            ..Default::default()
+        };
+        write_frames(&mut writer, [&b"ab"[..], b"", b"cde"])
+            .await
+            .unwrap();
+        assert_eq!(writer.written, b"\0\0\0\x02ab\0\0\0\0\0\0\0\x03cde");
+        assert_eq!(writer.writes, 6);
+        assert!(writer.flushed);
+
+        let mut reader = writer.written.as_slice();
+        assert_eq!(read_frame(&mut reader, 16).await.unwrap(), b"ab");
+        assert_eq!(read_frame(&mut reader, 16).await.unwrap(), b"");
+        assert_eq!(read_frame(&mut reader, 16).await.unwrap(), b"cde");
+    }
+
+    #[tokio::test]
+    async fn test_stalled_writer_is_an_error() {
+        let mut writer = ThrottledWriter::default();
+        let res = write_frame(&mut writer, b"hello").await;
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == ErrorKind::WriteZero));
+    }
+
+    #[tokio::test]
+    async fn test_dns_message_roundtrip() {
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00";
+        let mut buf = Vec::new();
+        write_dns_message(&mut buf, query).await.unwrap();
+        assert_eq!(&buf[..2], [0, 12]);
+
+        let message = read_dns_message(&mut buf.as_slice()).await.unwrap();
+        assert_eq!(message, query);
+    }
+
+    #[tokio::test]
+    async fn test_dns_message_limits() {
+        let mut buf = Vec::new();
+        let res = write_dns_message(&mut buf, &[0u8; 65536]).await;
+        assert!(matches!(res, Err(Error::FrameTooLarge(65536, 65535))));
+        assert!(buf.is_empty());
+        write_dns_message(&mut buf, &[0u8; 65535]).await.unwrap();
+
+        let res = read_dns_message(&mut &b"\0\x05abc"[..]).await;
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof));
+        let res = read_dns_message(&mut &b"\0"[..]).await;
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof));
+    }
+}
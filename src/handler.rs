@@ -1,19 +1,69 @@
 use async_trait::async_trait;
 use std::{
+    collections::HashMap,
     net::{IpAddr, SocketAddr},
     sync::Arc,
+    time::{Duration, Instant},
 };
 use tokio::net::{TcpStream, UdpSocket};
+use tokio_util::sync::CancellationToken;
 
 use crate::{
-    NetworkInterface, RestartPolicy,
+    BindMode, ConnectionId, IpFamily, Isolation, NetworkInterface, RestartPolicy, ServiceContext,
+    SocketFailurePolicy,
     network::{run_tcp, run_udp},
+    state::{ServiceState, Transport},
     supervisor::{SupervisedTask, Task},
 };
 
+/// How a TCP service spreads accepting and bounds connection handling, returned
+/// by [`TcpHandler::concurrency`].
+///
+/// The connection limit is global: all shards draw from one pool of
+/// `max_connections` slots, so the service never handles more connections at once,
+/// however many shards there are. A shard takes a slot once a connection is queued
+/// on it but *before* accepting; while the pool is empty, every shard stops
+/// accepting and new connections wait in the kernel backlog of the shard they were
+/// hashed to. Shards without queued connections hold no slot, so the whole pool
+/// serves whichever shards are busy.
+///
+/// Outside Unix, a shard takes its slot before waiting for a connection, so each
+/// idle shard keeps one: under load, the service then handles up to
+/// `max_connections - (accept_shards - 1)` connections at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TcpConcurrency {
+    /// Listeners accepting in parallel. See [`TcpHandler::accept_shards`].
+    pub accept_shards: usize,
+    /// Connections handled at once across all shards, or `None` for no limit.
+    /// See [`TcpHandler::max_connections`].
+    pub max_connections: Option<usize>,
+}
+
+impl Default for TcpConcurrency {
+    fn default() -> Self {
+        Self {
+            accept_shards: 1,
+            max_connections: None,
+        }
+    }
+}
+
+impl TcpConcurrency {
+    /// Creates a configuration with `accept_shards` listeners and a global cap of
+    /// `max_connections`.
+    pub fn new(accept_shards: usize, max_connections: Option<usize>) -> Self {
+        Self {
+            accept_shards,
+            max_connections,
+        }
+    }
+}
+
 /// Marker type for TCP service registration.
+#[derive(Debug, Clone, Copy)]
 pub struct Tcp;
 /// Marker type for UDP service registration.
+#[derive(Debug, Clone, Copy)]
 pub struct Udp;
 
 /// Defines the behavior of a TCP service.
@@ -30,12 +80,236 @@ pub trait TcpHandler: Send + Sync + 'static {
         crate::BindMode::PreferInterface
     }
 
+    /// Returns whether IPv6 sockets only accept IPv6 traffic. Defaults to `true`.
+    ///
+    /// When `false`, an IPv6 socket (e.g. bound to `::`) is dual-stack and also
+    /// accepts IPv4 peers as IPv4-mapped addresses (`::ffff:a.b.c.d`). Support varies
+    /// by platform: Linux and macOS allow it, Windows and FreeBSD default to
+    /// IPv6-only but allow clearing it, and OpenBSD rejects dual-stack sockets.
+    fn v6_only(&self) -> bool {
+        true
+    }
+
+    /// Returns the address family tried first when binding. Defaults to `None`,
+    /// which keeps the order of the bind mode (IPv4 first for interface addresses).
+    ///
+    /// Set it to [`IpFamily::V6`] for IPv6-preferred deployments.
+    fn preferred_family(&self) -> Option<IpFamily> {
+        None
+    }
+
+    /// Returns whether sockets may bind addresses not (yet) assigned to this host,
+    /// using `IP_FREEBIND`. Defaults to `false`.
+    ///
+    /// Useful for floating VIPs that are moved here during failover. Linux only;
+    /// ignored elsewhere.
+    fn freebind(&self) -> bool {
+        false
+    }
+
+    /// Returns whether sockets are transparent proxies, using `IP_TRANSPARENT`.
+    /// Defaults to `false`.
+    ///
+    /// Requires the `CAP_NET_ADMIN` capability, otherwise binding fails. Linux and
+    /// IPv4 only; ignored elsewhere.
+    fn transparent(&self) -> bool {
+        false
+    }
+
+    /// Returns the named network namespace (as created by `ip netns add`) the
+    /// sockets are bound in. Defaults to `None`, the namespace of the process.
+    ///
+    /// The service keeps serving from its own namespace; only its sockets live in
+    /// the other one. Pair it with [`BindMode::Specific`](crate::BindMode::Specific)
+    /// or [`BindMode::BindAll`](crate::BindMode::BindAll), as interface addresses
+    /// are looked up in the namespace of the process. Requires `CAP_SYS_ADMIN` and
+    /// the `netns` feature on Linux; binding fails otherwise.
+    fn network_namespace(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns whether [`BindMode::PreferInterface`](crate::BindMode::PreferInterface)
+    /// must stay on the interface. Defaults to `true`.
+    ///
+    /// When the interface has no IP address, a strict service fails to bind with
+    /// [`Error::InterfaceHasNoAddr`](crate::Error::InterfaceHasNoAddr) and is
+    /// retried following its restart policy. Return `false` to fall back to
+    /// `0.0.0.0` instead, which listens on *all* interfaces.
+    fn strict_interface(&self) -> bool {
+        true
+    }
+
+    /// Applies custom options to a socket right before it is bound. Defaults to a no-op.
+    ///
+    /// This is an escape hatch for options Maestro has no dedicated setting for
+    /// (TTL, DSCP, keepalive, linger...). It runs after Maestro's own options, so it
+    /// may override them. Returning an error aborts the bind.
+    ///
+    /// Kernel timeouts (`SO_RCVTIMEO`/`SO_SNDTIMEO`, via
+    /// [`set_read_timeout`](socket2::Socket::set_read_timeout) and
+    /// [`set_write_timeout`](socket2::Socket::set_write_timeout)) can be set here
+    /// too. They only bound blocking calls, and Maestro makes the socket
+    /// nonblocking once bound, so they matter mostly as a safeguard for code that
+    /// turns it back into a blocking socket.
+    fn configure_socket(&self, _socket: &socket2::Socket) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns the interval used to coalesce repeated error logs. Defaults to `None`.
+    ///
+    /// When set, a flood of identical errors (e.g. failed accepts under overload)
+    /// logs one line per interval followed by a summary of how many were suppressed,
+    /// instead of one line per occurrence.
+    fn log_rate_limit(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns how many listeners accept connections in parallel. Defaults to `1`.
+    ///
+    /// Each listener is bound to the same address with `SO_REUSEPORT` and runs its
+    /// own accept loop, like the UDP shards, which removes the single accept loop
+    /// bottleneck at very high connection rates. Linux only; other platforms always
+    /// use a single listener.
+    fn accept_shards(&self) -> usize {
+        1
+    }
+
+    /// Returns how many connections are accepted per listener wake-up. Defaults to `1`.
+    ///
+    /// With a larger batch, the accept loop drains connections that are already
+    /// queued before waiting for readiness again, which saves executor wake-ups at
+    /// very high connection rates.
+    fn accept_batch(&self) -> usize {
+        1
+    }
+
+    /// Returns the maximum number of connections handled at once. Defaults to `None` (no limit).
+    ///
+    /// Once the limit is reached, the service stops accepting: new connections wait
+    /// in the kernel backlog until a running [`on_connection`](Self::on_connection)
+    /// returns, which pushes back on clients instead of queueing work in memory.
+    /// The limit is shared by all [`accept_shards`](Self::accept_shards).
+    fn max_connections(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the accept shards and connection limit together. Defaults to
+    /// [`accept_shards`](Self::accept_shards) and
+    /// [`max_connections`](Self::max_connections).
+    ///
+    /// Override either this or the two methods. See [`TcpConcurrency`] for how the
+    /// settings interact.
+    fn concurrency(&self) -> TcpConcurrency {
+        TcpConcurrency {
+            accept_shards: self.accept_shards(),
+            max_connections: self.max_connections(),
+        }
+    }
+
+    /// Returns how many connections may be handled at once before new ones are shed.
+    /// Defaults to `None` (no limit).
+    ///
+    /// Unlike [`max_connections`](Self::max_connections), which stops accepting,
+    /// this closes connections accepted past the mark right away, so that clients
+    /// fail fast instead of waiting in the backlog. The count is shared by all
+    /// listeners of the service; shed connections are reported in
+    /// [`ServiceSnapshot::shed`](crate::ServiceSnapshot::shed).
+    fn max_in_flight(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns how long the service accepts connections after it starts serving.
+    /// Defaults to `None` (for as long as it runs).
+    ///
+    /// Use it for ephemeral listeners such as a one-time bootstrap port. Once the
+    /// deadline passes, no new connection is accepted, while connections already
+    /// accepted carry on. This is a listener lifetime, unlike per-connection
+    /// timeouts. See [`exit_on_accept_deadline`](Self::exit_on_accept_deadline) for
+    /// what happens to the listeners.
+    fn accept_deadline(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns whether the service exits once its
+    /// [`accept_deadline`](Self::accept_deadline) passes. Defaults to `true`.
+    ///
+    /// Exiting closes the listeners, so that later clients are refused, and is not
+    /// treated as a failure by the supervisor. When `false`, the listeners stay
+    /// bound but idle until shutdown, and later clients wait in the backlog.
+    fn exit_on_accept_deadline(&self) -> bool {
+        true
+    }
+
+    /// Returns how long the accept loop may go without making progress before
+    /// the supervisor restarts the service. Defaults to `None` (no watchdog).
+    ///
+    /// The loop counts as alive while it waits for connections or is paused, but
+    /// not while it waits for a free [`max_connections`](Self::max_connections)
+    /// slot, so that connections stuck in their handler are detected too.
+    fn liveness_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns how long connections may wait in the accept queue of a listener
+    /// without its accept loop taking any, before the service is considered
+    /// wedged and restarted. Defaults to `None` (no watchdog).
+    ///
+    /// Unlike [`liveness_timeout`](Self::liveness_timeout), this tells an idle loop
+    /// from a stuck one by the queue depth reported by
+    /// [`SupervisorHandle::accept_queue_depth`](crate::SupervisorHandle::accept_queue_depth),
+    /// so it only fires on Linux. A paused listener, one waiting for a free
+    /// [`max_connections`](TcpConcurrency::max_connections) slot, or one past its
+    /// [`accept_deadline`](Self::accept_deadline) keeps its queue on purpose and
+    /// is not considered wedged.
+    fn accept_stall_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the correlation ID of a newly accepted connection. Defaults to
+    /// [`ConnectionId::next`].
+    ///
+    /// Called once per connection, before [`on_connection`](Self::on_connection).
+    fn connection_id(&self) -> ConnectionId {
+        ConnectionId::next()
+    }
+
     /// Handles a new incoming TCP connection.
     ///
     /// # Arguments
     /// * `stream` - The connected TCP stream.
     /// * `peer` - The address of the remote peer.
-    async fn on_connection(&self, stream: TcpStream, peer: &SocketAddr);
+    /// * `local` - The local address the connection arrived on; its family tells
+    ///   IPv4 and IPv6 clients apart on dual-stack services.
+    /// * `token` - Cancelled when this connection should wind down, e.g. when the
+    ///   service shuts down. Long-lived read loops should `select!` on
+    ///   [`cancelled`](CancellationToken::cancelled) and return once it fires.
+    async fn on_connection(
+        &self,
+        stream: TcpStream,
+        peer: &SocketAddr,
+        local: &SocketAddr,
+        token: CancellationToken,
+    );
+
+    /// Called instead of [`on_connection`](Self::on_connection) when a connection is
+    /// refused, e.g. by an [`IpFilter`](crate::IpFilter). Defaults to closing it.
+    ///
+    /// Use it to send a rejection message (an HTTP 429, a protocol NAK) before
+    /// closing; the connection is closed when `stream` is dropped.
+    async fn on_reject(&self, _stream: TcpStream, _peer: &SocketAddr) {}
+
+    /// Called each time an instance of the service has bound its sockets, before it
+    /// starts serving. Defaults to a no-op.
+    ///
+    /// Use [`ServiceContext::spawn`] for background work that must stop with the
+    /// instance. Keep it short: serving starts only once it returns.
+    async fn on_started(&self, _ctx: &ServiceContext) {}
+
+    /// Called once when the service is cancelled during shutdown. Defaults to a no-op.
+    ///
+    /// Use it to release external resources (e.g. deregister from discovery).
+    /// It is not called when the service exits on its own.
+    async fn on_shutdown(&self) {}
 }
 
 /// Defines the behavior of a UDP service.
@@ -51,37 +325,368 @@ pub trait UdpHandler: Send + Sync + 'static {
     fn bind_mode(&self) -> crate::BindMode {
         crate::BindMode::PreferInterface
     }
+
+    /// Returns whether IPv6 sockets only accept IPv6 traffic. Defaults to `true`.
+    ///
+    /// When `false`, an IPv6 socket (e.g. bound to `::`) is dual-stack and also
+    /// accepts IPv4 peers as IPv4-mapped addresses (`::ffff:a.b.c.d`). Support varies
+    /// by platform: Linux and macOS allow it, Windows and FreeBSD default to
+    /// IPv6-only but allow clearing it, and OpenBSD rejects dual-stack sockets.
+    fn v6_only(&self) -> bool {
+        true
+    }
+
+    /// Returns the address family tried first when binding. Defaults to `None`,
+    /// which keeps the order of the bind mode (IPv4 first for interface addresses).
+    ///
+    /// Set it to [`IpFamily::V6`] for IPv6-preferred deployments.
+    fn preferred_family(&self) -> Option<IpFamily> {
+        None
+    }
+
+    /// Returns whether sockets may bind addresses not (yet) assigned to this host,
+    /// using `IP_FREEBIND`. Defaults to `false`.
+    ///
+    /// Useful for floating VIPs that are moved here during failover. Linux only;
+    /// ignored elsewhere.
+    fn freebind(&self) -> bool {
+        false
+    }
+
+    /// Returns whether sockets are transparent proxies, using `IP_TRANSPARENT`.
+    /// Defaults to `false`.
+    ///
+    /// Requires the `CAP_NET_ADMIN` capability, otherwise binding fails. Linux and
+    /// IPv4 only; ignored elsewhere.
+    fn transparent(&self) -> bool {
+        false
+    }
+
+    /// Returns the named network namespace (as created by `ip netns add`) the
+    /// sockets are bound in. Defaults to `None`, the namespace of the process.
+    ///
+    /// The service keeps serving from its own namespace; only its sockets live in
+    /// the other one. Pair it with [`BindMode::Specific`](crate::BindMode::Specific)
+    /// or [`BindMode::BindAll`](crate::BindMode::BindAll), as interface addresses
+    /// are looked up in the namespace of the process. Requires `CAP_SYS_ADMIN` and
+    /// the `netns` feature on Linux; binding fails otherwise.
+    fn network_namespace(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns whether [`BindMode::PreferInterface`](crate::BindMode::PreferInterface)
+    /// must stay on the interface. Defaults to `true`.
+    ///
+    /// When the interface has no IP address, a strict service fails to bind with
+    /// [`Error::InterfaceHasNoAddr`](crate::Error::InterfaceHasNoAddr) and is
+    /// retried following its restart policy. Return `false` to fall back to
+    /// `0.0.0.0` instead, which listens on *all* interfaces.
+    fn strict_interface(&self) -> bool {
+        true
+    }
+
+    /// Applies custom options to a socket right before it is bound. Defaults to a no-op.
+    ///
+    /// This is an escape hatch for options Maestro has no dedicated setting for
+    /// (TTL, DSCP, keepalive, linger...). It runs after Maestro's own options, so it
+    /// may override them. Returning an error aborts the bind.
+    ///
+    /// Kernel timeouts (`SO_RCVTIMEO`/`SO_SNDTIMEO`, via
+    /// [`set_read_timeout`](socket2::Socket::set_read_timeout) and
+    /// [`set_write_timeout`](socket2::Socket::set_write_timeout)) can be set here
+    /// too. They only bound blocking calls, and Maestro makes the socket
+    /// nonblocking once bound, so they matter mostly as a safeguard for code that
+    /// turns it back into a blocking socket.
+    fn configure_socket(&self, _socket: &socket2::Socket) -> std::io::Result<()> {
+        Ok(())
+    }
+
     /// Returns a list of multicast addresses to join. Defaults to empty.
     fn multicast_addrs(&self) -> &[IpAddr] {
         &[]
     }
 
+    /// Returns whether joining the [`multicast_addrs`](Self::multicast_addrs) groups
+    /// must succeed. Defaults to `true`.
+    ///
+    /// Failed joins are retried briefly. When required, a join that still fails
+    /// aborts the bind and the service fails with
+    /// [`Error::MulticastJoinFailed`](crate::Error::MulticastJoinFailed), so the
+    /// supervisor restarts or abandons it like any other failure. Otherwise it is
+    /// logged and the service runs without the group.
+    fn multicast_join_required(&self) -> bool {
+        true
+    }
+
+    /// Returns whether the service must not run without all of its
+    /// [`multicast_addrs`](Self::multicast_addrs) groups. Defaults to `false`.
+    ///
+    /// Meant for services that only consume a multicast feed: when set, a join
+    /// that still fails after its retries fails the service with
+    /// [`Error::MulticastJoinFailed`](crate::Error::MulticastJoinFailed), even if
+    /// [`multicast_join_required`](Self::multicast_join_required) returns `false`.
+    fn require_multicast(&self) -> bool {
+        false
+    }
+
+    /// Returns whether IPv4 sockets may send broadcast datagrams. Defaults to `false`.
+    ///
+    /// `SO_BROADCAST` is also enabled when [`multicast_addrs`](Self::multicast_addrs)
+    /// is not empty. Earlier releases enabled it on every IPv4 socket, which let
+    /// unicast services send broadcasts by accident.
+    fn broadcast(&self) -> bool {
+        false
+    }
+
+    /// Returns the largest datagram size accepted by the service. Defaults to `None` (no limit).
+    ///
+    /// Larger datagrams are dropped with a warning before reaching [`UdpHandler::on_packet`].
+    fn max_datagram_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns how many [`on_packet`](Self::on_packet) calls may run at once per
+    /// socket. Defaults to `1`.
+    ///
+    /// With `1`, each datagram is handled before the next one is received. Higher
+    /// values copy each datagram into its own buffer and handle it in a separate
+    /// task, so that a slow packet does not hold up the ones behind it. Once the
+    /// limit is reached, receiving waits for a running call to return, so a flood
+    /// never has more than this many handler tasks per socket.
+    fn concurrent_packets(&self) -> usize {
+        1
+    }
+
+    /// Returns how many datagrams may be handled at once before new ones are shed.
+    /// Defaults to `None` (no limit).
+    ///
+    /// Datagrams received past the mark are dropped without reaching
+    /// [`on_packet`](Self::on_packet). The count is shared by all sockets of the
+    /// service; dropped datagrams are reported in
+    /// [`ServiceSnapshot::shed`](crate::ServiceSnapshot::shed).
+    fn max_in_flight(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns whether each socket shard is served by its own thread, pinned to a
+    /// CPU core. Defaults to `false`.
+    ///
+    /// Shards are spread over the cores the process may run on, so that
+    /// `SO_REUSEPORT` sharding lines up with cores and receive buffers live on
+    /// the shard's NUMA node. Requires the `core-affinity` feature; ignored with a
+    /// warning otherwise.
+    fn pin_shards(&self) -> bool {
+        false
+    }
+
+    /// Whether to size each shard's receive buffer from the interface MTU instead
+    /// of the 64 KiB maximum datagram size. Defaults to `false`.
+    ///
+    /// This cuts memory per shard on links with a small MTU, at the cost of
+    /// truncating datagrams the kernel reassembled from fragments (they reach
+    /// [`on_packet`](Self::on_packet) with [`PacketInfo::truncated`] set). Ignored
+    /// when the MTU is unknown.
+    fn buffer_from_mtu(&self) -> bool {
+        false
+    }
+
+    /// Whether to report the ECN codepoint of each datagram in [`PacketInfo::ecn`].
+    /// Defaults to `false`.
+    ///
+    /// Enables `IP_RECVTOS` (and `IPV6_RECVTCLASS` on IPv6 sockets) and receives
+    /// with `recvmsg`. Requires the `ecn` feature on Linux; binding fails otherwise.
+    fn recv_ecn(&self) -> bool {
+        false
+    }
+
+    /// Returns the interval used to coalesce repeated error logs. Defaults to `None`.
+    ///
+    /// When set, a flood of identical errors (e.g. failed accepts under overload)
+    /// logs one line per interval followed by a summary of how many were suppressed,
+    /// instead of one line per occurrence.
+    fn log_rate_limit(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns how long the receive loop may go without making progress before
+    /// the supervisor restarts the service. Defaults to `None` (no watchdog).
+    ///
+    /// The loop counts as alive while it waits for datagrams or is paused, but not
+    /// while a datagram is being handled inline or it waits for a free
+    /// [`concurrent_packets`](Self::concurrent_packets) slot, so that a stuck
+    /// [`on_packet`](Self::on_packet) is detected too.
+    fn liveness_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Returns the correlation ID of a received datagram, passed on as
+    /// [`PacketInfo::id`]. Defaults to [`ConnectionId::next`].
+    fn connection_id(&self) -> ConnectionId {
+        ConnectionId::next()
+    }
+
+    /// Returns what happens when a socket task stops. Defaults to
+    /// [`SocketFailurePolicy::Respawn`].
+    fn on_socket_failure(&self) -> SocketFailurePolicy {
+        SocketFailurePolicy::Respawn
+    }
+
     /// Handles an incoming UDP packet.
     ///
     /// # Arguments
     /// * `data` - The raw packet data.
     /// * `socket` - The shared socket (thread-safe, can be used to send replies).
     /// * `peer` - The address of the sender.
-    async fn on_packet(&self, data: &[u8], socket: Arc<UdpSocket>, peer: &SocketAddr);
+    /// * `info` - Details about the datagram and the socket that received it.
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        info: &PacketInfo,
+    );
+
+    /// Called each time an instance of the service has bound its sockets, before it
+    /// starts serving. Defaults to a no-op.
+    ///
+    /// Use [`ServiceContext::spawn`] for background work that must stop with the
+    /// instance. Keep it short: serving starts only once it returns.
+    async fn on_started(&self, _ctx: &ServiceContext) {}
+
+    /// Called once when the service is cancelled during shutdown. Defaults to a no-op.
+    ///
+    /// Use it to release external resources (e.g. deregister from discovery).
+    /// It is not called when the service exits on its own.
+    async fn on_shutdown(&self) {}
+}
+
+/// Details about a received datagram, passed to [`UdpHandler::on_packet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PacketInfo {
+    /// Number of bytes received.
+    pub len: usize,
+    /// Whether the datagram was larger than the receive buffer and got cut short.
+    pub truncated: bool,
+    /// Address of the receiving socket, which may be a wildcard address when
+    /// bound with [`BindMode::BindAll`].
+    pub local_addr: SocketAddr,
+    /// When the datagram was read from the socket.
+    pub received_at: Instant,
+    /// Correlation ID of the datagram, from [`UdpHandler::connection_id`].
+    pub id: ConnectionId,
+    /// ECN codepoint of the datagram, when [`UdpHandler::recv_ecn`] is enabled.
+    pub ecn: Option<Ecn>,
+}
+
+impl PacketInfo {
+    /// Describes a complete datagram of `len` bytes received now on `local_addr`,
+    /// with an ID from [`ConnectionId::next`].
+    ///
+    /// Useful to call [`UdpHandler::on_packet`] directly, e.g. in tests.
+    pub fn new(len: usize, local_addr: SocketAddr) -> Self {
+        Self {
+            len,
+            truncated: false,
+            local_addr,
+            received_at: Instant::now(),
+            id: ConnectionId::next(),
+            ecn: None,
+        }
+    }
+}
+
+/// Explicit Congestion Notification codepoint of a datagram (RFC 3168), reported
+/// in [`PacketInfo::ecn`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ecn {
+    /// The sender does not support ECN.
+    NotEct,
+    /// ECN-capable transport, codepoint `ECT(1)`.
+    Ect1,
+    /// ECN-capable transport, codepoint `ECT(0)`.
+    Ect0,
+    /// Congestion experienced, set by a router on the path.
+    Ce,
+}
+
+impl Ecn {
+    /// Reads the codepoint from the two low bits of an IPv4 TOS or IPv6 traffic
+    /// class byte.
+    pub fn from_tos(tos: u8) -> Self {
+        match tos & 0b11 {
+            0b00 => Self::NotEct,
+            0b01 => Self::Ect1,
+            0b10 => Self::Ect0,
+            _ => Self::Ce,
+        }
+    }
+}
+
+/// Per-registration settings that override a handler's own configuration.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceOptions {
+    /// Display name used in logs instead of the handler's name.
+    pub name: Option<String>,
+    /// Bind strategy used instead of the handler's [`bind_mode`](TcpHandler::bind_mode).
+    pub bind_mode: Option<BindMode>,
+    /// Startup phase; services of a phase start once every earlier phase has bound.
+    pub phase: u8,
+    /// Labels attached to the service for metrics and log grouping.
+    pub labels: HashMap<String, String>,
+    /// Whether the service waits for [`SupervisorHandle::activate`](crate::SupervisorHandle::activate)
+    /// before binding.
+    pub inactive: bool,
+    /// Runtime the service runs on; the supervisor's own by default.
+    pub isolation: Isolation,
 }
 
 /// A generic trait to convert user handlers into supervised tasks.
 pub trait Service<Kind> {
     /// Consumes the handler and produces a supervised task.
-    fn into_task(self, iface: Arc<NetworkInterface>, policy: RestartPolicy) -> Box<dyn Task>;
+    fn into_task(
+        self,
+        options: ServiceOptions,
+        iface: Arc<NetworkInterface>,
+        policy: RestartPolicy,
+    ) -> Box<dyn Task>;
 }
 
 impl<T> Service<Tcp> for T
 where
     T: TcpHandler,
 {
-    fn into_task(self, iface: Arc<NetworkInterface>, policy: RestartPolicy) -> Box<dyn Task> {
+    fn into_task(
+        self,
+        options: ServiceOptions,
+        iface: Arc<NetworkInterface>,
+        policy: RestartPolicy,
+    ) -> Box<dyn Task> {
         let handler = Arc::new(self);
-        Box::new(SupervisedTask::new(handler.name(), policy, move || {
-            let h = handler.clone();
+        let name = options.name.unwrap_or_else(|| handler.name().to_string());
+        let state = ServiceState::new(name, Transport::Tcp)
+            .with_phase(options.phase)
+            .with_labels(options.labels)
+            .with_active(!options.inactive)
+            .with_isolation(options.isolation)
+            .with_handler(handler.clone())
+            .with_liveness_timeout(handler.liveness_timeout());
+        let state = Arc::new(state);
+        let bind_mode = options.bind_mode;
+        let shutdown_handler = handler.clone();
+        let shutdown_state = state.clone();
+        let task = SupervisedTask::new(state.clone(), policy, move || {
+            let h = state.current_handler(&handler);
             let i = iface.clone();
-            Box::pin(async move { run_tcp(h, i).await })
-        }))
+            let st = state.clone();
+            let m = bind_mode.clone().unwrap_or_else(|| h.bind_mode());
+            Box::pin(async move { run_tcp(h, i, st, m).await })
+        })
+        .with_shutdown_hook(move || {
+            let h = shutdown_state.current_handler(&shutdown_handler);
+            Box::pin(async move { h.on_shutdown().await })
+        });
+        Box::new(task)
     }
 }
 
@@ -89,13 +694,266 @@ impl<T> Service<Udp> for T
 where
     T: UdpHandler,
 {
-    fn into_task(self, iface: Arc<NetworkInterface>, policy: RestartPolicy) -> Box<dyn Task> {
+    fn into_task(
+        self,
+        options: ServiceOptions,
+        iface: Arc<NetworkInterface>,
+        policy: RestartPolicy,
+    ) -> Box<dyn Task> {
         let handler = Arc::new(self);
-        Box::new(SupervisedTask::new(handler.name(), policy, move || {
-            let h = handler.clone();
+        let name = options.name.unwrap_or_else(|| handler.name().to_string());
+        let state = ServiceState::new(name, Transport::Udp)
+            .with_phase(options.phase)
+            .with_labels(options.labels)
+            .with_active(!options.inactive)
+            .with_isolation(options.isolation)
+            .with_handler(handler.clone())
+            .with_liveness_timeout(handler.liveness_timeout());
+        let state = Arc::new(state);
+        let bind_mode = options.bind_mode;
+        let shutdown_handler = handler.clone();
+        let shutdown_state = state.clone();
+        let task = SupervisedTask::new(state.clone(), policy, move || {
+            let h = state.current_handler(&handler);
             let i = iface.clone();
-            Box::pin(async move { run_udp(h, i).await })
-        }))
+            let st = state.clone();
+            let m = bind_mode.clone().unwrap_or_else(|| h.bind_mode());
+            Box::pin(async move { run_udp(h, i, st, m).await })
+        })
+        .with_shutdown_hook(move || {
+            let h = shutdown_state.current_handler(&shutdown_handler);
+            Box::pin(async move { h.on_shutdown().await })
+        });
+        Box::new(task)
+    }
+}
+
+/// Shares one handler between several services, e.g. the TCP and UDP sides of a
+/// protocol registered with [`Supervisor::add_dual`](crate::Supervisor::add_dual).
+#[async_trait]
+impl<H: TcpHandler> TcpHandler for Arc<H> {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn port(&self) -> u16 {
+        (**self).port()
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        (**self).bind_mode()
+    }
+
+    fn v6_only(&self) -> bool {
+        (**self).v6_only()
+    }
+
+    fn preferred_family(&self) -> Option<IpFamily> {
+        (**self).preferred_family()
+    }
+
+    fn freebind(&self) -> bool {
+        (**self).freebind()
+    }
+
+    fn transparent(&self) -> bool {
+        (**self).transparent()
+    }
+
+    fn network_namespace(&self) -> Option<&str> {
+        (**self).network_namespace()
+    }
+
+    fn strict_interface(&self) -> bool {
+        (**self).strict_interface()
+    }
+
+    fn configure_socket(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        (**self).configure_socket(socket)
+    }
+
+    fn log_rate_limit(&self) -> Option<Duration> {
+        (**self).log_rate_limit()
+    }
+
+    fn accept_shards(&self) -> usize {
+        (**self).accept_shards()
+    }
+
+    fn accept_batch(&self) -> usize {
+        (**self).accept_batch()
+    }
+
+    fn max_connections(&self) -> Option<usize> {
+        (**self).max_connections()
+    }
+
+    fn concurrency(&self) -> TcpConcurrency {
+        (**self).concurrency()
+    }
+
+    fn max_in_flight(&self) -> Option<usize> {
+        (**self).max_in_flight()
+    }
+
+    fn accept_deadline(&self) -> Option<Duration> {
+        (**self).accept_deadline()
+    }
+
+    fn exit_on_accept_deadline(&self) -> bool {
+        (**self).exit_on_accept_deadline()
+    }
+
+    fn liveness_timeout(&self) -> Option<Duration> {
+        (**self).liveness_timeout()
+    }
+
+    fn accept_stall_timeout(&self) -> Option<Duration> {
+        (**self).accept_stall_timeout()
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        (**self).connection_id()
+    }
+
+    async fn on_connection(
+        &self,
+        stream: TcpStream,
+        peer: &SocketAddr,
+        local: &SocketAddr,
+        token: CancellationToken,
+    ) {
+        (**self).on_connection(stream, peer, local, token).await;
+    }
+
+    async fn on_reject(&self, stream: TcpStream, peer: &SocketAddr) {
+        (**self).on_reject(stream, peer).await;
+    }
+
+    async fn on_started(&self, ctx: &ServiceContext) {
+        (**self).on_started(ctx).await;
+    }
+
+    async fn on_shutdown(&self) {
+        (**self).on_shutdown().await;
+    }
+}
+
+/// Shares one handler between several services, like the [`TcpHandler`] implementation.
+#[async_trait]
+impl<H: UdpHandler> UdpHandler for Arc<H> {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn port(&self) -> u16 {
+        (**self).port()
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        (**self).bind_mode()
+    }
+
+    fn v6_only(&self) -> bool {
+        (**self).v6_only()
+    }
+
+    fn preferred_family(&self) -> Option<IpFamily> {
+        (**self).preferred_family()
+    }
+
+    fn freebind(&self) -> bool {
+        (**self).freebind()
+    }
+
+    fn transparent(&self) -> bool {
+        (**self).transparent()
+    }
+
+    fn network_namespace(&self) -> Option<&str> {
+        (**self).network_namespace()
+    }
+
+    fn strict_interface(&self) -> bool {
+        (**self).strict_interface()
+    }
+
+    fn configure_socket(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        (**self).configure_socket(socket)
+    }
+
+    fn log_rate_limit(&self) -> Option<Duration> {
+        (**self).log_rate_limit()
+    }
+
+    fn multicast_addrs(&self) -> &[IpAddr] {
+        (**self).multicast_addrs()
+    }
+
+    fn multicast_join_required(&self) -> bool {
+        (**self).multicast_join_required()
+    }
+
+    fn require_multicast(&self) -> bool {
+        (**self).require_multicast()
+    }
+
+    fn broadcast(&self) -> bool {
+        (**self).broadcast()
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        (**self).max_datagram_size()
+    }
+
+    fn max_in_flight(&self) -> Option<usize> {
+        (**self).max_in_flight()
+    }
+
+    fn pin_shards(&self) -> bool {
+        (**self).pin_shards()
+    }
+
+    fn buffer_from_mtu(&self) -> bool {
+        (**self).buffer_from_mtu()
+    }
+
+    fn recv_ecn(&self) -> bool {
+        (**self).recv_ecn()
+    }
+
+    fn concurrent_packets(&self) -> usize {
+        (**self).concurrent_packets()
+    }
+
+    fn on_socket_failure(&self) -> SocketFailurePolicy {
+        (**self).on_socket_failure()
+    }
+
+    fn liveness_timeout(&self) -> Option<Duration> {
+        (**self).liveness_timeout()
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        (**self).connection_id()
+    }
+
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        info: &PacketInfo,
+    ) {
+        (**self).on_packet(data, socket, peer, info).await;
+    }
+
+    async fn on_started(&self, ctx: &ServiceContext) {
+        (**self).on_started(ctx).await;
+    }
+
+    async fn on_shutdown(&self) {
+        (**self).on_shutdown().await;
     }
 }
 
@@ -114,7 +972,14 @@ mod tests {
         fn port(&self) -> u16 {
             0
         }
-        async fn on_connection(&self, _s: TcpStream, _p: &SocketAddr) {}
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+        }
     }
 
     struct MockUdp;
@@ -126,20 +991,59 @@ mod tests {
         fn port(&self) -> u16 {
             0
         }
-        async fn on_packet(&self, _data: &[u8], _socket: Arc<UdpSocket>, _peer: &SocketAddr) {}
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+        }
     }
 
     #[test]
     fn test_tcp_into_task() {
         let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
         let service = MockTcp;
-        let _task = Service::<Tcp>::into_task(service, iface, RestartPolicy::default());
+        let task = Service::<Tcp>::into_task(
+            service,
+            ServiceOptions::default(),
+            iface,
+            RestartPolicy::default(),
+        );
+        assert_eq!(task.name(), "MockTcp");
     }
 
     #[test]
     fn test_udp_into_task() {
         let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
         let service = MockUdp;
-        let _task = Service::<Udp>::into_task(service, iface, RestartPolicy::default());
+        let task = Service::<Udp>::into_task(
+            service,
+            ServiceOptions::default(),
+            iface,
+            RestartPolicy::default(),
+        );
+        assert_eq!(task.name(), "MockUdp");
+    }
+
+    #[test]
+    fn test_into_task_name_override() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let options = ServiceOptions {
+            name: Some("tcp-internal".into()),
+            ..Default::default()
+        };
+        let task = Service::<Tcp>::into_task(MockTcp, options, iface, RestartPolicy::default());
+        assert_eq!(task.name(), "tcp-internal");
+    }
+
+    #[test]
+    fn test_ecn_from_tos() {
+        // DSCP bits (the upper six) do not affect the codepoint.
+        assert_eq!(Ecn::from_tos(0xb8), Ecn::NotEct);
+        assert_eq!(Ecn::from_tos(0xb9), Ecn::Ect1);
+        assert_eq!(Ecn::from_tos(0x02), Ecn::Ect0);
+        assert_eq!(Ecn::from_tos(0x03), Ecn::Ce);
     }
 }
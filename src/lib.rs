@@ -10,7 +10,7 @@
 //! # Example
 //!
 //! ```rust,no_run
-//! use maestro_rs::{NetworkInterface, Result, Supervisor, TcpHandler, UdpHandler, async_trait};
+//! use maestro_rs::{CancellationToken, NetworkInterface, Result, Supervisor, TcpHandler, UdpHandler, PacketInfo, async_trait};
 //! use std::{net::SocketAddr, str::FromStr, sync::Arc};
 //! use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpStream, UdpSocket}};
 //! use tracing::{error, info};
@@ -27,7 +27,7 @@
 //!         8080
 //!     }
 //!
-//!     async fn on_connection(&self, mut stream: TcpStream, peer: &SocketAddr) {
+//!     async fn on_connection(&self, mut stream: TcpStream, peer: &SocketAddr, _local: &SocketAddr, _token: CancellationToken) {
 //!         unimplemented!()
 //!     }
 //! }
@@ -44,7 +44,7 @@
 //!         5353
 //!     }
 //!
-//!     async fn on_packet(&self, data: &[u8], socket: Arc<UdpSocket>, peer: &SocketAddr) {
+//!     async fn on_packet(&self, data: &[u8], socket: Arc<UdpSocket>, peer: &SocketAddr, _info: &PacketInfo) {
 //!         unimplemented!()
 //!     }
 //! }
@@ -66,13 +66,47 @@
 //!     Ok(())
 //! }
 //! ```
+mod context;
+mod correlation;
+#[cfg(all(target_os = "linux", feature = "ecn"))]
+mod ecn;
 mod error;
+mod filter;
+pub mod framing;
 mod handler;
+#[cfg(feature = "tracing")]
+mod log_limit;
+#[cfg(all(target_os = "linux", feature = "netns"))]
+mod netns;
 mod network;
+mod responder;
+mod runtime;
+mod state;
 mod supervisor;
+mod timed;
+mod timeout;
+mod util;
 
 pub use async_trait::async_trait;
-pub use error::{Error, Result};
-pub use handler::{Tcp, TcpHandler, Udp, UdpHandler};
-pub use network::{BindMode, NetworkInterface};
-pub use supervisor::{RestartPolicy, Supervisor};
+pub use bytes;
+pub use context::{MulticastMembership, ServiceContext};
+pub use correlation::ConnectionId;
+pub use error::{Error, ErrorKind, Result};
+pub use filter::{Cidr, IpFilter};
+pub use handler::{Ecn, PacketInfo, Tcp, TcpConcurrency, TcpHandler, Udp, UdpHandler};
+pub use network::{
+    BindMode, InterfaceDiff, IpFamily, NetworkInterface, PreBoundSocket, SocketFailurePolicy,
+};
+pub use responder::{Responder, StaticResponder};
+pub use runtime::{TcpRuntime, UdpRuntime};
+pub use socket2;
+pub use state::WorkerHealth;
+pub use supervisor::{
+    ConnectionBudget, ConnectionPermit, Isolation, RestartPolicy, Schedule, ServiceSnapshot,
+    ShutdownReport, SupervisionEvent, Supervisor, SupervisorHandle,
+};
+pub use timed::{LatencyHistogram, LatencySnapshot, Timed};
+pub use timeout::TimeoutStream;
+pub use tokio::time::MissedTickBehavior;
+pub use tokio_util::sync::CancellationToken;
+pub use util::{retry, write_all_vectored};
@@ -0,0 +1,195 @@
+use std::{collections::HashMap, hash::Hash, time::Duration};
+
+use tokio::time::Instant;
+
+/// Logs how many events of a key were suppressed, and over how long.
+type Report<K> = Box<dyn Fn(&K, u64, Duration) + Send>;
+
+/// Coalesces repeated log events so that a flood of identical errors produces
+/// one line per interval instead of one line per occurrence.
+///
+/// Events are grouped by key (e.g. an [`std::io::ErrorKind`]). The first event of
+/// a key is logged, further events within the interval are only counted, and the
+/// suppressed count is reported once the interval is over: with the next event of
+/// the key, on the next [`flush`](Self::flush), or when the limiter is dropped,
+/// so the tail of a flood that stops is not lost.
+pub(crate) struct LogLimiter<K> {
+    interval: Option<Duration>,
+    windows: HashMap<K, Window>,
+    report: Report<K>,
+}
+
+struct Window {
+    started: Instant,
+    suppressed: u64,
+}
+
+/// Outcome of [`LogLimiter::check`].
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Verdict {
+    /// The event should be logged.
+    Log,
+    /// The event should be dropped.
+    Suppress,
+}
+
+impl<K: Hash + Eq> LogLimiter<K> {
+    /// Creates a limiter that passes suppressed counts to `report`. With `None`,
+    /// every event is logged.
+    pub(crate) fn new<R>(interval: Option<Duration>, report: R) -> Self
+    where
+        R: Fn(&K, u64, Duration) + Send + 'static,
+    {
+        Self {
+            interval,
+            windows: HashMap::new(),
+            report: Box::new(report),
+        }
+    }
+
+    /// Records an event and decides whether it should be logged.
+    pub(crate) fn check(&mut self, key: K, now: Instant) -> Verdict {
+        let Some(interval) = self.interval else {
+            return Verdict::Log;
+        };
+
+        let fresh = Window {
+            started: now,
+            suppressed: 0,
+        };
+        match self.windows.get_mut(&key) {
+            Some(window) if now.duration_since(window.started) < interval => {
+                window.suppressed += 1;
+                Verdict::Suppress
+            }
+            Some(window) => {
+                let old = std::mem::replace(window, fresh);
+                if old.suppressed > 0 {
+                    (self.report)(&key, old.suppressed, now.duration_since(old.started));
+                }
+                Verdict::Log
+            }
+            None => {
+                self.windows.insert(key, fresh);
+                Verdict::Log
+            }
+        }
+    }
+
+    /// Reports and forgets the windows whose interval is over.
+    ///
+    /// Meant to be called when the operation succeeds again, to close the windows
+    /// of a flood that stopped.
+    pub(crate) fn flush(&mut self, now: Instant) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let report = &self.report;
+        self.windows.retain(|key, window| {
+            let elapsed = now.duration_since(window.started);
+            if elapsed < interval {
+                return true;
+            }
+            if window.suppressed > 0 {
+                report(key, window.suppressed, elapsed);
+            }
+            false
+        });
+    }
+}
+
+impl<K> Drop for LogLimiter<K> {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        for (key, window) in &self.windows {
+            if window.suppressed > 0 {
+                (self.report)(key, window.suppressed, now.duration_since(window.started));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::ErrorKind,
+        sync::{Arc, Mutex},
+    };
+
+    /// Returns a limiter and the suppressed counts it reports.
+    fn recording<K: Hash + Eq>(
+        interval: Option<Duration>,
+    ) -> (LogLimiter<K>, Arc<Mutex<Vec<u64>>>) {
+        let reported = Arc::new(Mutex::new(Vec::new()));
+        let sink = reported.clone();
+        let limiter = LogLimiter::new(interval, move |_: &K, suppressed, _| {
+            sink.lock().unwrap().push(suppressed);
+        });
+        (limiter, reported)
+    }
+
+    #[test]
+    fn test_flood_is_bounded() {
+        let (mut limiter, reported) = recording(Some(Duration::from_secs(5)));
+        let start = Instant::now();
+        let mut logged = 0u64;
+
+        // One million accept errors spread over 20 seconds.
+        for i in 0..1_000_000u64 {
+            let now = start + Duration::from_micros(i * 20);
+            if limiter.check(ErrorKind::Other, now) == Verdict::Log {
+                logged += 1;
+            }
+        }
+        drop(limiter);
+
+        // One line per 5s window, and every event is accounted for once the
+        // limiter reports what it still held.
+        assert_eq!(logged, 4);
+        let reported = reported.lock().unwrap();
+        assert_eq!(reported.len(), 4);
+        assert_eq!(logged + reported.iter().sum::<u64>(), 1_000_000);
+    }
+
+    #[test]
+    fn test_flush_reports_a_flood_that_stopped() {
+        let (mut limiter, reported) = recording(Some(Duration::from_secs(5)));
+        let start = Instant::now();
+        for _ in 0..3 {
+            limiter.check(ErrorKind::Other, start);
+        }
+
+        limiter.flush(start + Duration::from_secs(4));
+        assert!(reported.lock().unwrap().is_empty());
+        limiter.flush(start + Duration::from_secs(5));
+        assert_eq!(*reported.lock().unwrap(), [2]);
+
+        // The window is closed: the next event is logged, and nothing is left.
+        let later = start + Duration::from_secs(6);
+        assert_eq!(limiter.check(ErrorKind::Other, later), Verdict::Log);
+        drop(limiter);
+        assert_eq!(*reported.lock().unwrap(), [2]);
+    }
+
+    #[test]
+    fn test_keys_are_limited_independently() {
+        let (mut limiter, _) = recording(Some(Duration::from_secs(5)));
+        let now = Instant::now();
+
+        assert_eq!(limiter.check(ErrorKind::Other, now), Verdict::Log);
+        assert_eq!(limiter.check(ErrorKind::Other, now), Verdict::Suppress);
+        assert_eq!(limiter.check(ErrorKind::ConnectionReset, now), Verdict::Log);
+    }
+
+    #[test]
+    fn test_disabled_logs_everything() {
+        let (mut limiter, reported) = recording(None);
+        let now = Instant::now();
+        for _ in 0..10 {
+            assert_eq!(limiter.check((), now), Verdict::Log);
+        }
+        drop(limiter);
+        assert!(reported.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use std::{fs::File, io, path::Path, thread};
+
+use nix::sched::{CloneFlags, setns};
+use tokio::runtime::Handle;
+
+use crate::{Error, Result};
+
+/// Directory where `ip netns` keeps its named network namespaces.
+const NETNS_DIR: &str = "/var/run/netns";
+
+/// Runs `bind` inside the named network namespace.
+///
+/// Only the namespace of the calling thread can be changed, so `bind` runs on a
+/// short-lived thread that enters the namespace and ends with it: the runtime's
+/// threads never leave their own namespace. Sockets keep the namespace they were
+/// created in, and `bind` can register them with the runtime as usual.
+pub fn bind_in<T, F>(name: &str, bind: F) -> Result<T>
+where
+    T: Send,
+    F: FnOnce() -> Result<T> + Send,
+{
+    let namespace = File::open(Path::new(NETNS_DIR).join(name)).map_err(|e| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!("network namespace `{name}`: {e}"),
+        ))
+    })?;
+    let runtime = Handle::current();
+
+    thread::scope(|scope| {
+        scope
+            .spawn(move || {
+                setns(&namespace, CloneFlags::CLONE_NEWNET).map_err(io::Error::from)?;
+                let _runtime = runtime.enter();
+                bind()
+            })
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::SocketAddr;
+
+    #[tokio::test]
+    async fn test_unknown_namespace_is_an_error() {
+        let res = bind_in("maestro-no-such-netns", || Ok(()));
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == io::ErrorKind::NotFound));
+    }
+
+    /// Needs root and a namespace set up with `ip netns add maestro-test` and
+    /// `ip -n maestro-test link set lo up`.
+    #[tokio::test]
+    #[ignore = "requires root and the `maestro-test` network namespace"]
+    async fn test_bind_in_namespace() {
+        let listener = bind_in("maestro-test", || {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+            listener.set_nonblocking(true)?;
+            Ok(tokio::net::TcpListener::from_std(listener)?)
+        })
+        .unwrap();
+        let addr: SocketAddr = listener.local_addr().unwrap();
+
+        // The loopback of the namespace is not the host's.
+        assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+    }
+}
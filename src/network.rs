@@ -1,25 +1,90 @@
 #[cfg(feature = "tracing")]
 use tracing::{error, info, warn};
 
-use getifaddrs::{Address, getifaddrs, if_nametoindex};
+#[cfg(feature = "tracing")]
+use crate::log_limit::{LogLimiter, Verdict};
+
+use getifaddrs::{Address, Interface, InterfaceFlags, getifaddrs, if_nametoindex};
 use socket2::{Domain, Protocol, Socket, Type};
 use std::{
+    collections::HashMap,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     str::FromStr,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    task::Poll,
+    time::{Duration, Instant},
 };
+#[cfg(unix)]
+use tokio::io::unix::AsyncFd;
 use tokio::{
-    net::{TcpListener, UdpSocket},
+    net::{TcpListener, TcpStream, UdpSocket, lookup_host},
+    sync::{OwnedSemaphorePermit, Semaphore},
     task::JoinSet,
+    time::{interval, sleep, timeout},
 };
 
 use crate::{
-    Error, Result,
-    handler::{TcpHandler, UdpHandler},
+    Error, RestartPolicy, Result, ServiceContext,
+    context::MulticastMembership,
+    correlation::in_connection,
+    handler::{Ecn, PacketInfo, TcpHandler, UdpHandler},
+    state::{ServiceState, WorkerHealth},
+    util::retry,
+};
+
+/// Environment variable overriding the number of UDP sockets bound per address.
+const UDP_SHARDS_ENV: &str = "MAESTRO_UDP_SHARDS";
+
+/// Upper bound on the detected UDP shard count when no override is provided.
+const MAX_UDP_SHARDS: usize = 16;
+
+/// Pause before a failed UDP socket task is respawned, to avoid a hot failure loop.
+const SHARD_RESPAWN_DELAY: Duration = Duration::from_millis(100);
+
+/// How often a vanished interface is checked for while waiting for it to reappear.
+const INTERFACE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Extra bind attempts made when a TCP address is still held (e.g. by a socket of the
+/// previous instance), before the failure is handed to the restart policy.
+const ADDR_IN_USE_RETRIES: u32 = 3;
+
+/// Pause between bind attempts on an address that is in use.
+const ADDR_IN_USE_DELAY: Duration = Duration::from_millis(100);
+
+/// Size requested for the receive and send buffers of UDP sockets.
+const UDP_BUFFER_SIZE: usize = 7 * 1024 * 1024;
+
+/// Largest UDP payload, and the size of the per-shard receive buffer by default.
+const MAX_RECV_BUFFER: usize = 65535;
+
+/// Smallest receive buffer sized from an MTU, the minimum datagram every IPv4
+/// host must accept.
+const MIN_RECV_BUFFER: usize = 576;
+
+/// Log target of the bind plan summary, so it can be filtered on its own.
+#[cfg(feature = "tracing")]
+const BIND_PLAN_TARGET: &str = "maestro_rs::bind";
+
+/// Backoff applied to multicast group joins that fail.
+const MULTICAST_JOIN_POLICY: RestartPolicy = RestartPolicy {
+    max_attempts: Some(4),
+    base_delay: Duration::from_millis(50),
+    min_healthy_duration: None,
+    min_restart_interval: Duration::ZERO,
 };
 
 /// Strategies for binding sockets to network interfaces.
-#[derive(Debug, Clone, Copy)]
+///
+/// With the `serde` feature, it can be read from configuration files in snake
+/// case: `"prefer_interface"`, `"bind_all"`, `{ specific = "10.0.0.1" }` or
+/// `{ hostname = "example.com" }` in TOML. [`BindMode::PreBound`] holds a live
+/// socket and cannot be (de)serialized.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum BindMode {
     /// Bind to all IP addresses associated with the selected [`NetworkInterface`].
     /// This is the default strategy.
@@ -27,7 +92,221 @@ pub enum BindMode {
     /// Bind to `0.0.0.0` (IPv4) and `::` (IPv6), listening on all interfaces.
     BindAll,
     /// Bind to a specific, manually provided IP address.
+    ///
+    /// For discovery protocols this may be a broadcast address, so that only
+    /// broadcast datagrams are received. `SO_BROADCAST` is enabled on its own
+    /// for `255.255.255.255`; for a directed broadcast address such as
+    /// `192.168.1.255`, also return `true` from [`UdpHandler::broadcast`].
     Specific(IpAddr),
+    /// Resolve a hostname at bind time and bind to the resulting addresses.
+    ///
+    /// Every returned A/AAAA record is attempted in order. A failed lookup is
+    /// reported as a bind error ([`Error::Io`]), and a lookup returning no
+    /// records as [`Error::NoAddrAvailable`].
+    Hostname(String),
+    /// Serve on a socket that was bound outside of Maestro.
+    ///
+    /// See [`PreBoundSocket`] for ownership semantics.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    PreBound(PreBoundSocket),
+}
+
+/// A socket bound by the caller and handed to Maestro instead of being bound in-process.
+///
+/// This lets privileged ports be bound before dropping privileges, or sockets be
+/// received from an orchestrator. Maestro keeps the original socket open for as
+/// long as the service is registered and serves on duplicated handles
+/// ([`try_clone`](std::net::TcpListener::try_clone)), so the socket stays bound
+/// across restarts. It is closed once the supervisor drops the service.
+#[derive(Debug, Clone)]
+pub enum PreBoundSocket {
+    /// A listening TCP socket.
+    Tcp(Arc<std::net::TcpListener>),
+    /// A bound UDP socket.
+    Udp(Arc<std::net::UdpSocket>),
+}
+
+impl PreBoundSocket {
+    /// Returns the local address the socket is bound to.
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        match self {
+            Self::Tcp(l) => l.local_addr(),
+            Self::Udp(s) => s.local_addr(),
+        }
+    }
+}
+
+impl From<std::net::TcpListener> for PreBoundSocket {
+    fn from(listener: std::net::TcpListener) -> Self {
+        Self::Tcp(Arc::new(listener))
+    }
+}
+
+impl From<std::net::UdpSocket> for PreBoundSocket {
+    fn from(socket: std::net::UdpSocket) -> Self {
+        Self::Udp(Arc::new(socket))
+    }
+}
+
+/// An IP address family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpFamily {
+    /// IPv4.
+    V4,
+    /// IPv6.
+    V6,
+}
+
+impl IpFamily {
+    fn of(addr: &SocketAddr) -> Self {
+        if addr.is_ipv4() { Self::V4 } else { Self::V6 }
+    }
+}
+
+/// What a UDP service does when one of its socket tasks stops, because receiving
+/// failed or [`UdpHandler::on_packet`] panicked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SocketFailurePolicy {
+    /// Keep serving on the remaining sockets, at reduced capacity. Losing the last
+    /// socket fails the service, as with [`FailService`](Self::FailService).
+    Ignore,
+    /// Restart the task on the same socket after a short delay.
+    #[default]
+    Respawn,
+    /// Fail the whole service so that the supervisor restarts it per its [`RestartPolicy`](crate::RestartPolicy).
+    FailService,
+}
+
+/// Callback applying user-defined options to a socket before it is bound.
+type ConfigureSocket<'a> = Box<dyn Fn(&Socket) -> std::io::Result<()> + Send + Sync + 'a>;
+
+/// Socket options applied by the bind functions, gathered from the handler.
+struct SocketConfig<'a> {
+    v6_only: bool,
+    freebind: bool,
+    transparent: bool,
+    broadcast: bool,
+    recv_ecn: bool,
+    netns: Option<&'a str>,
+    configure: ConfigureSocket<'a>,
+    /// Enables `SO_REUSEPORT`; replaced in tests to simulate kernels without it.
+    reuse_port: fn(&Socket) -> std::io::Result<()>,
+    /// Creates sockets; replaced in tests to simulate hosts with IPv6 disabled.
+    new_socket: fn(Domain, Type, Option<Protocol>) -> std::io::Result<Socket>,
+}
+
+impl<'a> SocketConfig<'a> {
+    fn tcp<H: TcpHandler>(handler: &'a H) -> Self {
+        Self {
+            v6_only: handler.v6_only(),
+            freebind: handler.freebind(),
+            transparent: handler.transparent(),
+            broadcast: false,
+            recv_ecn: false,
+            netns: handler.network_namespace(),
+            configure: Box::new(|socket| handler.configure_socket(socket)),
+            reuse_port: set_reuse_port,
+            new_socket: Socket::new,
+        }
+    }
+
+    fn udp<H: UdpHandler>(handler: &'a H) -> Self {
+        Self {
+            v6_only: handler.v6_only(),
+            freebind: handler.freebind(),
+            transparent: handler.transparent(),
+            // Some stacks need broadcast enabled to deliver multicast.
+            broadcast: handler.broadcast() || !handler.multicast_addrs().is_empty(),
+            recv_ecn: handler.recv_ecn(),
+            netns: handler.network_namespace(),
+            configure: Box::new(|socket| handler.configure_socket(socket)),
+            reuse_port: set_reuse_port,
+            new_socket: Socket::new,
+        }
+    }
+
+    /// Names the options set on every socket, for the bind plan log.
+    #[cfg(feature = "tracing")]
+    fn options(&self) -> Vec<&'static str> {
+        let mut options = vec!["reuse_addr"];
+        let flags = [
+            (cfg!(target_os = "linux"), "reuse_port"),
+            (self.v6_only, "v6_only"),
+            (self.freebind, "freebind"),
+            (self.transparent, "transparent"),
+            (self.broadcast, "broadcast"),
+            (self.recv_ecn, "recv_ecn"),
+        ];
+        options.extend(
+            flags
+                .into_iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, name)| name),
+        );
+        options
+    }
+}
+
+/// What a service tried to bind, summarized in one log line once it is bound.
+#[cfg(feature = "tracing")]
+struct BindPlan {
+    mode: String,
+    candidates: Vec<SocketAddr>,
+    options: Vec<&'static str>,
+}
+
+#[cfg(feature = "tracing")]
+impl BindPlan {
+    fn new(mode: &BindMode) -> Self {
+        Self {
+            mode: format!("{mode:?}"),
+            candidates: Vec::new(),
+            options: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, candidates: &[SocketAddr], options: Vec<&'static str>) {
+        self.candidates = candidates.to_vec();
+        self.options = options;
+    }
+
+    fn log(&self, transport: &str, name: &str, chosen: &[SocketAddr]) {
+        info!(
+            target: BIND_PLAN_TARGET,
+            mode = %self.mode,
+            candidates = ?self.candidates,
+            chosen = ?chosen,
+            options = ?self.options,
+            "{} service `{}` bind plan",
+            transport,
+            name
+        );
+    }
+}
+
+/// Applies the Linux-only options allowing a socket to bind non-local addresses.
+fn set_nonlocal_options(
+    socket: &Socket,
+    addr: &SocketAddr,
+    config: &SocketConfig<'_>,
+) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        if config.freebind {
+            match addr {
+                SocketAddr::V4(_) => socket.set_freebind_v4(true)?,
+                SocketAddr::V6(_) => socket.set_freebind_v6(true)?,
+            }
+        }
+        if config.transparent && addr.is_ipv4() {
+            socket.set_ip_transparent_v4(true)?;
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = (socket, addr, config);
+
+    Ok(())
 }
 
 /// Represents a local network interface and its associated addresses.
@@ -43,6 +322,17 @@ pub struct NetworkInterface {
     pub inet6: Vec<Ipv6Addr>,
     /// Optional hardware (MAC) address.
     pub mac: Option<[u8; 6]>,
+    /// Whether [`NetworkInterface::mac`] was generated locally because the
+    /// system did not report a hardware address for this interface.
+    pub mac_is_generated: bool,
+    /// Whether the interface was administratively up when it was resolved.
+    pub is_up: bool,
+    /// Whether this is a loopback interface.
+    pub is_loopback: bool,
+    /// Whether the interface supports multicast.
+    pub supports_multicast: bool,
+    /// Maximum transmission unit of the link, in bytes, if the system reports it.
+    pub mtu: Option<u32>,
 }
 
 impl NetworkInterface {
@@ -59,7 +349,205 @@ impl NetworkInterface {
 
     /// Assigns a specific MAC address to this interface.
     fn _set_mac(&mut self, mac: [u8; 6]) {
-        self.mac = Some(mac)
+        self.mac = Some(mac);
+        self.mac_is_generated = false;
+    }
+
+    /// Fills in a generated MAC address if none was discovered, flagging it as such.
+    fn ensure_mac(&mut self) {
+        if self.mac.is_none() {
+            self.mac = Some(self.generate_mac());
+            self.mac_is_generated = true;
+        }
+    }
+
+    /// Returns the MAC address only if it was reported by the system.
+    ///
+    /// Use this instead of [`NetworkInterface::mac`] when a generated address
+    /// would be incorrect (e.g. tooling that must act on the real hardware address).
+    pub fn hardware_mac(&self) -> Option<[u8; 6]> {
+        if self.mac_is_generated {
+            None
+        } else {
+            self.mac
+        }
+    }
+
+    /// Lists the interfaces of the system that have at least one IP address.
+    ///
+    /// Interfaces are returned in the order the system reports them.
+    pub fn list() -> Result<Vec<NetworkInterface>> {
+        let entries: Vec<Interface> = getifaddrs()?.into_iter().collect();
+        let mut names: Vec<&str> = Vec::new();
+        for entry in &entries {
+            if !names.contains(&entry.name.as_str()) {
+                names.push(&entry.name);
+            }
+        }
+
+        let mut interfaces = Vec::new();
+        for name in names {
+            // The interface may have gone away since the addresses were read.
+            let Ok(index) = if_nametoindex(name) else {
+                continue;
+            };
+            if let Some(iface) = Self::from_entries(name, index, &entries) {
+                interfaces.push(iface);
+            }
+        }
+        Ok(interfaces)
+    }
+
+    /// Builds the interface called `name` from `getifaddrs` entries, or returns
+    /// `None` if it has no IP address.
+    fn from_entries(name: &str, index: u32, entries: &[Interface]) -> Option<Self> {
+        let mut information = Self {
+            name: name.to_string(),
+            index,
+            inet: vec![],
+            inet6: vec![],
+            mac: None,
+            mac_is_generated: false,
+            is_up: false,
+            is_loopback: false,
+            supports_multicast: false,
+            mtu: None,
+        };
+
+        for iface in entries.iter().filter(|iface| iface.name == name) {
+            match &iface.address {
+                Address::V4(v4) => information.inet.push(v4.address),
+                Address::V6(v6) => information.inet6.push(v6.address),
+                Address::Mac(mac) => information.mac = Some(*mac),
+            }
+            information.is_up = iface.flags.contains(InterfaceFlags::UP);
+            information.is_loopback = iface.flags.contains(InterfaceFlags::LOOPBACK);
+            information.supports_multicast = iface.flags.contains(InterfaceFlags::MULTICAST);
+        }
+
+        if information.inet.is_empty() && information.inet6.is_empty() {
+            return None;
+        }
+
+        information.ensure_mac();
+        information.mtu = read_mtu(name);
+
+        Some(information)
+    }
+
+    /// Returns all addresses of the interface, IPv4 first.
+    fn addrs(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        let v4 = self.inet.iter().map(|ip| IpAddr::V4(*ip));
+        v4.chain(self.inet6.iter().map(|ip| IpAddr::V6(*ip)))
+    }
+
+    /// Returns a multi-line, human-readable summary of the interface, suited for
+    /// display in CLI tools.
+    ///
+    /// ```text
+    /// eth0 (index 2)
+    ///   flags: up, multicast
+    ///   mtu 1500
+    ///   inet 192.168.1.10
+    ///   inet6 fe80::1
+    ///   mac 02:42:ac:11:00:02 (generated)
+    /// ```
+    pub fn describe(&self) -> String {
+        let mut flags = vec![if self.is_up { "up" } else { "down" }];
+        if self.is_loopback {
+            flags.push("loopback");
+        }
+        if self.supports_multicast {
+            flags.push("multicast");
+        }
+
+        let mut out = format!(
+            "{} (index {})\n  flags: {}",
+            self.name,
+            self.index,
+            flags.join(", ")
+        );
+        if let Some(mtu) = self.mtu {
+            out.push_str(&format!("\n  mtu {mtu}"));
+        }
+        for ip in &self.inet {
+            out.push_str(&format!("\n  inet {ip}"));
+        }
+        for ip in &self.inet6 {
+            out.push_str(&format!("\n  inet6 {ip}"));
+        }
+        match self.mac {
+            Some(mac) => {
+                let origin = if self.mac_is_generated {
+                    "generated"
+                } else {
+                    "hardware"
+                };
+                out.push_str(&format!("\n  mac {} ({origin})", format_mac(&mac)));
+            }
+            None => out.push_str("\n  mac none"),
+        }
+        out
+    }
+
+    /// Compares this snapshot with a newer one and returns the address changes.
+    pub fn diff(&self, newer: &NetworkInterface) -> InterfaceDiff {
+        let old: Vec<IpAddr> = self.addrs().collect();
+        let new: Vec<IpAddr> = newer.addrs().collect();
+        InterfaceDiff {
+            added: new.iter().filter(|ip| !old.contains(ip)).copied().collect(),
+            removed: old.iter().filter(|ip| !new.contains(ip)).copied().collect(),
+        }
+    }
+}
+
+/// Reads the MTU of the interface called `name` from sysfs.
+#[cfg(target_os = "linux")]
+fn read_mtu(name: &str) -> Option<u32> {
+    std::fs::read_to_string(format!("/sys/class/net/{name}/mtu"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_mtu(_name: &str) -> Option<u32> {
+    None
+}
+
+/// Formats a MAC address as colon-separated lowercase hex.
+fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+/// Two snapshots are equal when they have the same name, index and addresses,
+/// regardless of address order. The MAC address is ignored since it may have been
+/// generated.
+impl PartialEq for NetworkInterface {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.index == other.index && self.diff(other).is_empty()
+    }
+}
+
+impl Eq for NetworkInterface {}
+
+/// Addresses that changed between two [`NetworkInterface`] snapshots.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InterfaceDiff {
+    /// Addresses present in the new snapshot only.
+    pub added: Vec<IpAddr>,
+    /// Addresses present in the old snapshot only.
+    pub removed: Vec<IpAddr>,
+}
+
+impl InterfaceDiff {
+    /// Returns whether no address was added or removed.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
     }
 }
 
@@ -83,125 +571,826 @@ impl FromStr for NetworkInterface {
         }
 
         let index = if_nametoindex(name)?;
-        let mut information = Self {
-            name: name.to_string(),
-            index,
-            inet: vec![],
-            inet6: vec![],
-            mac: None,
-        };
+        let entries: Vec<Interface> = getifaddrs()?.into_iter().collect();
+        Self::from_entries(name, index, &entries)
+            .ok_or_else(|| Error::InterfaceNotFound(name.to_string()))
+    }
+}
+
+/// Internal loop for running a TCP service.
+pub async fn run_tcp<H: TcpHandler>(
+    handler: Arc<H>,
+    iface: Arc<NetworkInterface>,
+    state: Arc<ServiceState>,
+    mode: BindMode,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let name = state.name().clone();
+    #[cfg(feature = "tracing")]
+    let mut plan = BindPlan::new(&mode);
+    let concurrency = handler.concurrency();
 
-        for iface in getifaddrs()? {
-            if iface.name == name {
-                match iface.address {
-                    Address::V4(v4) => information.inet.push(v4.address),
-                    Address::V6(v6) => information.inet6.push(v6.address),
-                    Address::Mac(mac) => information.mac = Some(mac),
+    let listeners = match mode {
+        BindMode::PreBound(PreBoundSocket::Tcp(listener)) => vec![adopt_tcp_listener(&listener)?],
+        BindMode::PreBound(PreBoundSocket::Udp(_)) => {
+            return Err(Error::PreBoundMismatch("TCP listener"));
+        }
+        mode => {
+            let mut addrs =
+                resolve_addrs(mode, handler.port(), &iface, handler.strict_interface()).await?;
+            order_by_family(&mut addrs, handler.preferred_family());
+            let config = SocketConfig::tcp(&*handler);
+            #[cfg(feature = "tracing")]
+            plan.record(&addrs, config.options());
+            let mut retries = 0;
+            loop {
+                let shards = concurrency.accept_shards;
+                match bind_in_netns(&config, || bind_tcp_listeners(&addrs, &config, shards)) {
+                    Err(Error::AddrInUse(addr)) if retries < ADDR_IN_USE_RETRIES => {
+                        retries += 1;
+                        #[cfg(feature = "tracing")]
+                        warn!(
+                            "TCP service `{}` found {} already in use, retrying bind ({}/{})",
+                            name, addr, retries, ADDR_IN_USE_RETRIES
+                        );
+                        #[cfg(not(feature = "tracing"))]
+                        let _ = addr;
+                        sleep(ADDR_IN_USE_DELAY).await;
+                    }
+                    res => break res?,
                 }
             }
         }
+    };
 
-        if information.inet.is_empty() && information.inet6.is_empty() {
-            return Err(Error::InterfaceNotFound(name.to_string()));
+    let mut addrs: Vec<SocketAddr> = listeners
+        .iter()
+        .filter_map(|l| l.local_addr().ok())
+        .collect();
+    addrs.dedup();
+    #[cfg(feature = "tracing")]
+    plan.log("TCP", &name, &addrs);
+    let _bound = state.track_bound(addrs.clone());
+    let ctx = ServiceContext::new(state.name().as_ref());
+    handler.on_started(&ctx).await;
+
+    #[cfg(feature = "tracing")]
+    info!(
+        "TCP service `{}` started. Listening on {} with {} accept loops (Interface: {})",
+        name,
+        display_local_addr(listeners[0].local_addr()),
+        listeners.len(),
+        iface.name
+    );
+
+    let limit = concurrency
+        .max_connections
+        .map(|max| Arc::new(Semaphore::new(max)));
+    // One watchdog for all shards: they share the address, so the queue depth
+    // read for it is the total of every shard, and is compared to their total progress.
+    let progress = Arc::new(AcceptProgress::default());
+    let watchdog = {
+        let (progress, state) = (progress.clone(), state.clone());
+        let stall_timeout = handler.accept_stall_timeout();
+        async move {
+            match stall_timeout {
+                Some(timeout) => {
+                    watch_accepts(addrs, progress, state, timeout, accept_queue_depth).await
+                }
+                None => std::future::pending().await,
+            }
+        }
+    };
+    let mut set = JoinSet::new();
+    for listener in listeners {
+        let limit = limit.clone();
+        set.spawn(serve_listener(
+            listener,
+            handler.clone(),
+            state.clone(),
+            limit,
+            progress.clone(),
+        ));
+    }
+
+    tokio::select! {
+        _ = async { while set.join_next().await.is_some() {} } => Ok(()),
+        timeout = watchdog => {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "TCP service `{}` left connections waiting for {:?} without accepting any",
+                name, timeout
+            );
+            Err(Error::Unresponsive(state.name().to_string(), timeout))
         }
+    }
+}
 
-        if information.mac.is_none() {
-            information.mac = Some(information.generate_mac())
+/// Progress of the accept loops of a service, checked by [`watch_accepts`].
+#[derive(Debug, Default)]
+struct AcceptProgress {
+    /// Accept calls that returned, successfully or not.
+    accepted: AtomicU64,
+    /// Accept loops leaving connections in the queue on purpose: they wait for a
+    /// free connection slot, or are past their accept deadline.
+    holding: AtomicUsize,
+}
+
+/// Resolves once connections have waited in the accept queues of `addrs` for
+/// `timeout` while the loops accepted none, and returns `timeout`.
+///
+/// `depth` reads a queue; the watchdog never fires where it is unknown.
+async fn watch_accepts(
+    addrs: Vec<SocketAddr>,
+    progress: Arc<AcceptProgress>,
+    state: Arc<ServiceState>,
+    timeout: Duration,
+    depth: fn(&SocketAddr) -> Option<u32>,
+) -> Duration {
+    let mut ticks = interval((timeout / 4).max(Duration::from_millis(1)));
+    // When connections were first seen waiting, and the accept count back then.
+    let mut waiting: Option<(tokio::time::Instant, u64)> = None;
+    loop {
+        ticks.tick().await;
+        let accepted = progress.accepted.load(Ordering::Relaxed);
+        let pending = !state.is_paused()
+            && progress.holding.load(Ordering::Relaxed) == 0
+            && addrs.iter().filter_map(depth).sum::<u32>() > 0;
+        match waiting {
+            Some((since, before)) if pending && accepted == before => {
+                if since.elapsed() >= timeout {
+                    return timeout;
+                }
+            }
+            _ => waiting = pending.then(|| (tokio::time::Instant::now(), accepted)),
         }
+    }
+}
 
-        Ok(information)
+/// Formats a listener address for logs, falling back to `<unknown>`.
+///
+/// A bound listener that cannot report its address is still usable, so this is
+/// not worth failing the service over.
+#[cfg(feature = "tracing")]
+fn display_local_addr(addr: std::io::Result<SocketAddr>) -> String {
+    match addr {
+        Ok(addr) => addr.to_string(),
+        Err(_) => "<unknown>".to_string(),
     }
 }
 
-/// Internal loop for running a TCP service.
-pub async fn run_tcp<H: TcpHandler>(handler: Arc<H>, iface: Arc<NetworkInterface>) -> Result<()> {
-    let addrs = resolve_addrs(handler.bind_mode(), handler.port(), &iface);
-    let listener = bind_tcp_listener(&addrs)?;
+/// Runs the accept loop of one listener until the handler's accept deadline, if any.
+///
+/// Past the deadline, the listener is closed or, if the handler asks for it, kept
+/// bound without accepting until the service is cancelled.
+async fn serve_listener<H: TcpHandler>(
+    listener: TcpListener,
+    handler: Arc<H>,
+    state: Arc<ServiceState>,
+    limit: Option<Arc<Semaphore>>,
+    progress: Arc<AcceptProgress>,
+) {
+    let Some(deadline) = handler.accept_deadline() else {
+        return accept_loop(&listener, &handler, &state, limit, &progress).await;
+    };
+    let accepting = accept_loop(&listener, &handler, &state, limit, &progress);
+    let _ = timeout(deadline, accepting).await;
+    progress.holding.fetch_add(1, Ordering::Relaxed);
 
     #[cfg(feature = "tracing")]
     info!(
-        "TCP service `{}` started. Listening on {:?} (Interface: {})",
-        handler.name(),
-        listener.local_addr().map_err(Error::Io)?,
-        iface.name
+        "TCP service `{}` reached its accept deadline ({:?}). No longer accepting on {:?}",
+        state.name(),
+        deadline,
+        listener.local_addr()
     );
+    if !handler.exit_on_accept_deadline() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Accepts connections on one listener and hands each of them to the handler.
+///
+/// With a connection limit, a permit is acquired *before* accepting, so that a
+/// saturated service leaves new connections in the kernel backlog. On Unix it is
+/// acquired only once a connection is queued on this listener, so that idle
+/// shards do not hold permits.
+async fn accept_loop<H: TcpHandler>(
+    listener: &TcpListener,
+    handler: &Arc<H>,
+    state: &Arc<ServiceState>,
+    limit: Option<Arc<Semaphore>>,
+    progress: &AcceptProgress,
+) {
+    #[cfg(feature = "tracing")]
+    let name = state.name().clone();
+    #[cfg(feature = "tracing")]
+    let mut limiter = LogLimiter::new(handler.log_rate_limit(), {
+        let name = name.clone();
+        move |kind: &std::io::ErrorKind, suppressed, elapsed: Duration| {
+            error!(
+                "TCP service `{}` suppressed {} `{}` accept errors in last {:.1}s",
+                name,
+                suppressed,
+                kind,
+                elapsed.as_secs_f32()
+            );
+        }
+    });
+    let batch = handler.accept_batch().max(1);
+    #[cfg(unix)]
+    let queue = limit.as_ref().and_then(|_| watch_queue(listener));
 
     loop {
-        match listener.accept().await {
+        state.idle(state.wait_resumed()).await;
+        // With a limit, wait for a connection of this shard before taking a slot,
+        // so that an idle shard holds none that the others could use.
+        #[cfg(unix)]
+        let mut ready = None;
+        #[cfg(unix)]
+        if let Some(queue) = &queue {
+            tokio::select! {
+                res = state.idle(queue.readable()) => ready = res.ok(),
+                _ = state.wait_paused() => continue,
+            }
+        }
+        let permit = match &limit {
+            Some(limit) => {
+                progress.holding.fetch_add(1, Ordering::Relaxed);
+                let permit = limit.clone().acquire_owned().await.ok();
+                progress.holding.fetch_sub(1, Ordering::Relaxed);
+                permit
+            }
+            None => None,
+        };
+        #[cfg(unix)]
+        let queued = match ready {
+            Some(mut ready) => match ready.try_io(|queue| queue.get_ref().accept()) {
+                Ok(res) => Some(res.and_then(adopt_accepted)),
+                // Another task took the connection, or it was reset meanwhile.
+                Err(_would_block) => continue,
+            },
+            None => None,
+        };
+        #[cfg(not(unix))]
+        let queued = None;
+        let accepted = match queued {
+            Some(res) => res,
+            None => tokio::select! {
+                res = state.idle(listener.accept()) => res,
+                _ = state.wait_paused() => continue,
+            },
+        };
+        progress.accepted.fetch_add(1, Ordering::Relaxed);
+        match accepted {
             Ok((stream, peer)) => {
-                let h = handler.clone();
-                tokio::spawn(async move {
-                    h.on_connection(stream, &peer).await;
-                });
+                #[cfg(feature = "tracing")]
+                limiter.flush(tokio::time::Instant::now());
+                spawn_connection(handler, state, stream, peer, permit);
+                accept_queued(listener, handler, state, limit.as_ref(), batch - 1).await;
+            }
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                if limiter.check(e.kind(), tokio::time::Instant::now()) == Verdict::Log {
+                    error!("TCP accept failed for `{}`: {:?}", name, e);
+                }
+
+                #[cfg(not(feature = "tracing"))]
+                let _ = e;
+            }
+        }
+    }
+}
+
+/// Returns a second handle on `listener`, to wait for queued connections without
+/// accepting them.
+#[cfg(unix)]
+fn watch_queue(listener: &TcpListener) -> Option<AsyncFd<Socket>> {
+    let socket = socket2::SockRef::from(listener).try_clone().ok()?;
+    AsyncFd::new(socket).ok()
+}
+
+/// Turns a connection accepted through [`watch_queue`] into a Tokio stream.
+#[cfg(unix)]
+fn adopt_accepted(
+    (socket, peer): (Socket, socket2::SockAddr),
+) -> std::io::Result<(TcpStream, SocketAddr)> {
+    let peer = peer
+        .as_socket()
+        .ok_or_else(|| std::io::Error::other("accepted a connection from a non-IP peer"))?;
+    socket.set_nonblocking(true)?;
+    Ok((TcpStream::from_std(socket.into())?, peer))
+}
+
+/// Accepts up to `max` connections that are already queued, without waiting for
+/// the listener to become ready again. Returns how many were accepted.
+///
+/// Stops early when the queue is empty, the connection limit is reached, or
+/// accepting fails (the error is reported by the next regular accept).
+async fn accept_queued<H: TcpHandler>(
+    listener: &TcpListener,
+    handler: &Arc<H>,
+    state: &Arc<ServiceState>,
+    limit: Option<&Arc<Semaphore>>,
+    max: usize,
+) -> usize {
+    for accepted in 0..max {
+        let permit = match limit {
+            Some(limit) => match limit.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => return accepted,
+            },
+            None => None,
+        };
+        let ready = std::future::poll_fn(|cx| Poll::Ready(listener.poll_accept(cx))).await;
+        match ready {
+            Poll::Ready(Ok((stream, peer))) => {
+                spawn_connection(handler, state, stream, peer, permit)
+            }
+            _ => return accepted,
+        }
+    }
+    max
+}
+
+/// Runs the handler on a new connection, holding `permit` until it returns.
+///
+/// The connection is closed right away if the service is shedding load, or if
+/// the [`ConnectionBudget`](crate::ConnectionBudget) of the supervisor is spent.
+fn spawn_connection<H: TcpHandler>(
+    handler: &Arc<H>,
+    state: &Arc<ServiceState>,
+    stream: TcpStream,
+    peer: SocketAddr,
+    permit: Option<OwnedSemaphorePermit>,
+) {
+    let Some(in_flight) = state.enter(handler.max_in_flight()) else {
+        return;
+    };
+    let budget = match state.connection_budget() {
+        Some(budget) => match budget.try_acquire() {
+            Some(permit) => Some(permit),
+            None => return,
+        },
+        None => None,
+    };
+    let h = state.current_handler(handler);
+    let token = state.connection_token();
+    let id = h.connection_id();
+    spawn_handler(state, &peer, async move {
+        match stream.local_addr() {
+            Ok(local) => {
+                in_connection(id, &peer, h.on_connection(stream, &peer, &local, token)).await
             }
             Err(e) => {
                 #[cfg(feature = "tracing")]
-                error!("TCP accept failed for `{}`: {:?}", handler.name(), e);
+                warn!("Dropping connection from {}: no local address: {}", peer, e);
 
                 #[cfg(not(feature = "tracing"))]
                 let _ = e;
             }
         }
+        drop((permit, budget, in_flight));
+    });
+}
+
+/// Spawns the task handling one connection or datagram from `peer`.
+///
+/// The task ends early if the connections of the service are aborted.
+///
+/// With the `task-names` feature and `--cfg tokio_unstable`, the task is named
+/// after the service and the peer so that it can be told apart in `tokio-console`.
+fn spawn_handler<F>(state: &ServiceState, peer: &SocketAddr, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let aborted = state.connections_aborted();
+    let future = async move {
+        tokio::select! {
+            _ = future => {}
+            _ = aborted => {}
+        }
+    };
+    #[cfg(all(feature = "task-names", tokio_unstable))]
+    tokio::task::Builder::new()
+        .name(&task_name(state.name(), peer))
+        .spawn(future)
+        .expect("spawning on the current runtime cannot fail");
+
+    #[cfg(not(all(feature = "task-names", tokio_unstable)))]
+    {
+        let _ = (state, peer);
+        tokio::spawn(future);
     }
 }
 
+/// Names a handler task, e.g. `dns 10.0.0.7:5353`.
+#[cfg(all(feature = "task-names", tokio_unstable))]
+fn task_name(service: &str, peer: &SocketAddr) -> String {
+    format!("{service} {peer}")
+}
+
 /// Internal loop for running a UDP service.
-pub async fn run_udp<H: UdpHandler>(handler: Arc<H>, iface: Arc<NetworkInterface>) -> Result<()> {
-    let addrs = resolve_addrs(handler.bind_mode(), handler.port(), &iface);
-    let sockets = bind_udp_sockets(&addrs, &iface, handler.multicast_addrs())?;
+pub async fn run_udp<H: UdpHandler>(
+    handler: Arc<H>,
+    iface: Arc<NetworkInterface>,
+    state: Arc<ServiceState>,
+    mode: BindMode,
+) -> Result<()> {
+    #[cfg(feature = "tracing")]
+    let name = state.name().clone();
+    #[cfg(feature = "tracing")]
+    let mut plan = BindPlan::new(&mode);
 
-    if sockets.is_empty() {
-        return Err(Error::NoAddrAvailable);
+    let sockets = match mode {
+        BindMode::PreBound(PreBoundSocket::Udp(socket)) => adopt_udp_socket(&socket)?,
+        BindMode::PreBound(PreBoundSocket::Tcp(_)) => {
+            return Err(Error::PreBoundMismatch("UDP socket"));
+        }
+        mode => {
+            let mut addrs =
+                resolve_addrs(mode, handler.port(), &iface, handler.strict_interface()).await?;
+            order_by_family(&mut addrs, handler.preferred_family());
+            let config = SocketConfig::udp(&*handler);
+            #[cfg(feature = "tracing")]
+            plan.record(&addrs, {
+                let mut options = config.options();
+                options.push("7MiB buffers");
+                options
+            });
+            let shards = udp_shard_count();
+            bind_in_netns(&config, || bind_udp_sockets(&addrs, &config, shards))?
+        }
+    };
+
+    let required = handler.multicast_join_required() || handler.require_multicast();
+    for socket in &sockets {
+        for group in handler.multicast_addrs() {
+            join_multicast(group, &iface.name, required, || {
+                join_group(socket, group, &iface)
+            })
+            .await?;
+        }
     }
 
+    let mut addrs: Vec<SocketAddr> = sockets.iter().filter_map(|s| s.local_addr().ok()).collect();
+    addrs.dedup();
+    #[cfg(feature = "tracing")]
+    plan.log("UDP", &name, &addrs);
+    let _bound = state.track_bound(addrs);
+    let sockets: Vec<Arc<UdpSocket>> = sockets.into_iter().map(Arc::new).collect();
+    let membership = MulticastMembership::new(
+        sockets.clone(),
+        iface.clone(),
+        handler.multicast_addrs().to_vec(),
+    );
+    let ctx = ServiceContext::new(state.name().as_ref()).with_multicast(membership);
+    handler.on_started(&ctx).await;
+
     #[cfg(feature = "tracing")]
     info!(
         "UDP service `{}` started. Sharded across {} sockets on interface `{}`",
-        handler.name(),
+        name,
         sockets.len(),
         iface.name
     );
 
+    let policy = handler.on_socket_failure();
+    let cores = shard_cores(handler.pin_shards());
+    let buffer_size = recv_buffer_size(handler.buffer_from_mtu(), iface.mtu);
     let mut set = JoinSet::new();
+    let mut shards = HashMap::new();
+    for (i, socket) in sockets.into_iter().enumerate() {
+        let shard = Shard {
+            socket,
+            core: cores.as_ref().map(|cores| cores[i % cores.len()]),
+            buffer_size,
+        };
+        let id = spawn_shard(&mut set, &shard, &handler, &state);
+        shards.insert(id, shard);
+    }
 
-    for socket in sockets {
-        let h = handler.clone();
-        let s = Arc::new(socket);
+    while let Some(res) = set.join_next_with_id().await {
+        let id = res.as_ref().map_or_else(|e| e.id(), |(id, ())| *id);
+        let Some(shard) = shards.remove(&id) else {
+            continue;
+        };
 
-        set.spawn(async move {
-            let mut buf = vec![0u8; 65535];
-            loop {
-                match s.recv_from(&mut buf).await {
-                    Ok((n, peer)) => {
-                        h.on_packet(&buf[..n], s.clone(), &peer).await;
-                    }
-                    Err(e) => {
-                        #[cfg(feature = "tracing")]
-                        error!("UDP recv critical failure in `{}`: {:?}", h.name(), e);
+        #[cfg(feature = "tracing")]
+        if let Err(e) = &res {
+            error!("UDP socket task of `{}` panicked: {}", name, e);
+        }
 
-                        #[cfg(not(feature = "tracing"))]
-                        let _ = e;
+        match policy {
+            SocketFailurePolicy::Ignore => {
+                if shards.is_empty() {
+                    #[cfg(feature = "tracing")]
+                    error!("UDP service `{}` lost its last socket", name);
+                    return Err(Error::ServiceFailure(state.name().to_string()));
+                }
+                state.set_health(WorkerHealth::Degraded);
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "UDP service `{}` lost a socket, {} remaining",
+                    name,
+                    shards.len()
+                );
+            }
+            SocketFailurePolicy::Respawn => {
+                #[cfg(feature = "tracing")]
+                warn!("UDP service `{}` respawning a failed socket task", name);
+                state.set_health(WorkerHealth::Degraded);
+                sleep(SHARD_RESPAWN_DELAY).await;
+                let id = spawn_shard(&mut set, &shard, &handler, &state);
+                shards.insert(id, shard);
+                state.set_health(WorkerHealth::Healthy);
+            }
+            SocketFailurePolicy::FailService => {
+                return Err(Error::ServiceFailure(state.name().to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// One socket of a UDP service and the CPU core its task is pinned to, if any.
+struct Shard {
+    socket: Arc<UdpSocket>,
+    core: Option<usize>,
+    buffer_size: usize,
+}
+
+/// Returns the size of the receive buffer of each UDP socket.
+///
+/// With `from_mtu` and a known MTU, the buffer holds exactly one link-sized
+/// packet: the IP and UDP headers it does not need to store leave headroom, so
+/// only fragmented datagrams can fill it. Otherwise it holds any datagram.
+fn recv_buffer_size(from_mtu: bool, mtu: Option<u32>) -> usize {
+    match mtu {
+        Some(mtu) if from_mtu => (mtu as usize).clamp(MIN_RECV_BUFFER, MAX_RECV_BUFFER),
+        _ => MAX_RECV_BUFFER,
+    }
+}
+
+/// Returns the CPU cores shards are pinned to, or `None` if they are not pinned.
+fn shard_cores(pin: bool) -> Option<Vec<usize>> {
+    if !pin {
+        return None;
+    }
+    #[cfg(feature = "core-affinity")]
+    let cores: Option<Vec<usize>> = core_affinity::get_core_ids()
+        .map(|ids| ids.into_iter().map(|core| core.id).collect())
+        .filter(|ids: &Vec<usize>| !ids.is_empty());
+    #[cfg(not(feature = "core-affinity"))]
+    let cores = None;
+
+    #[cfg(feature = "tracing")]
+    if cores.is_none() {
+        warn!("UDP shards cannot be pinned: CPU cores unavailable or `core-affinity` disabled");
+    }
+    cores
+}
+
+/// Starts the receive loop of `shard`, on its pinned thread if it has a core.
+fn spawn_shard<H: UdpHandler>(
+    set: &mut JoinSet<()>,
+    shard: &Shard,
+    handler: &Arc<H>,
+    state: &Arc<ServiceState>,
+) -> tokio::task::Id {
+    let (socket, size) = (shard.socket.clone(), shard.buffer_size);
+    let (handler, state) = (handler.clone(), state.clone());
+    match shard.core {
+        #[cfg(feature = "core-affinity")]
+        Some(core) => set.spawn(pinned_recv_loop(socket, handler, state, size, core)),
+        _ => set.spawn(recv_loop(socket, handler, state, size)),
+    }
+    .id()
+}
+
+/// Runs [`recv_loop`] on a dedicated thread pinned to `core`, with its own
+/// single-threaded runtime. Receive buffers are allocated on that thread, hence
+/// on the memory node of the core.
+///
+/// The thread stops when the returned future completes or is dropped.
+#[cfg(feature = "core-affinity")]
+async fn pinned_recv_loop<H: UdpHandler>(
+    socket: Arc<UdpSocket>,
+    handler: Arc<H>,
+    state: Arc<ServiceState>,
+    buffer_size: usize,
+    core: usize,
+) {
+    let token = tokio_util::sync::CancellationToken::new();
+    let _stop = token.clone().drop_guard();
+    let (done_tx, done_rx) = tokio::sync::oneshot::channel::<()>();
+    let thread = std::thread::Builder::new()
+        .name(format!("{}-core{}", state.name(), core))
+        .spawn(move || {
+            let _done = done_tx;
+            if !core_affinity::set_for_current(core_affinity::CoreId { id: core }) {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "UDP shard of `{}` could not be pinned to core {}",
+                    state.name(),
+                    core
+                );
+            }
+            let runtime = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(runtime) => runtime,
+                Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    error!("UDP shard of `{}` has no runtime: {}", state.name(), e);
+                    #[cfg(not(feature = "tracing"))]
+                    let _ = e;
+                    return;
+                }
+            };
+            runtime.block_on(async move {
+                tokio::select! {
+                    _ = recv_loop(socket, handler, state, buffer_size) => {}
+                    _ = token.cancelled() => {}
+                }
+            });
+        });
+    if thread.is_ok() {
+        // Resolves once the thread is done, as the sender is dropped with it.
+        let _ = done_rx.await;
+    }
+}
+
+/// Receives datagrams on one socket and hands each of them to the handler.
+///
+/// Returns when receiving fails; the caller decides what happens next based on
+/// the handler's [`SocketFailurePolicy`].
+async fn recv_loop<H: UdpHandler>(
+    socket: Arc<UdpSocket>,
+    handler: Arc<H>,
+    state: Arc<ServiceState>,
+    buffer_size: usize,
+) {
+    #[cfg(feature = "tracing")]
+    let name = state.name().clone();
+    let local = match socket.local_addr() {
+        Ok(local) => local,
+        Err(e) => {
+            #[cfg(feature = "tracing")]
+            error!("UDP socket of `{}` has no local address: {:?}", name, e);
+
+            #[cfg(not(feature = "tracing"))]
+            let _ = e;
+            return;
+        }
+    };
+    // Filled rather than zeroed, so that every page is touched now by the shard's
+    // thread instead of being mapped lazily on first receive.
+    let mut buf = vec![u8::MAX; buffer_size];
+    let max_size = handler.max_datagram_size();
+    #[cfg(feature = "tracing")]
+    let mut limiter = LogLimiter::new(handler.log_rate_limit(), {
+        let name = name.clone();
+        move |_: &(), suppressed, elapsed: Duration| {
+            warn!(
+                "UDP service `{}` suppressed {} oversized datagram warnings in last {:.1}s",
+                name,
+                suppressed,
+                elapsed.as_secs_f32()
+            );
+        }
+    });
+    let concurrency = handler.concurrent_packets();
+    let limit = (concurrency > 1).then(|| Arc::new(Semaphore::new(concurrency)));
+    let max_in_flight = handler.max_in_flight();
+    let recv_ecn = handler.recv_ecn();
 
-                        break;
+    loop {
+        state.idle(state.wait_resumed()).await;
+        let permit = match &limit {
+            Some(limit) => limit.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+        let received = tokio::select! {
+            res = state.idle(recv_datagram(&socket, &mut buf, recv_ecn)) => res,
+            _ = state.wait_paused() => continue,
+        };
+        match received {
+            Ok((n, peer, ecn)) => {
+                if let Some(max) = max_size
+                    && n > max
+                {
+                    state.record_oversized();
+                    #[cfg(feature = "tracing")]
+                    if limiter.check((), tokio::time::Instant::now()) == Verdict::Log {
+                        warn!(
+                            "UDP service `{}` dropped {} byte datagram from {} (limit {})",
+                            name, n, peer, max
+                        );
                     }
+                    continue;
+                }
+                #[cfg(feature = "tracing")]
+                limiter.flush(tokio::time::Instant::now());
+                let Some(in_flight) = state.enter(max_in_flight) else {
+                    continue;
+                };
+                let h = state.current_handler(&handler);
+                let info = PacketInfo {
+                    len: n,
+                    // No datagram fills the buffer exactly (see `recv_buffer_size`),
+                    // so a full buffer means the kernel cut it short.
+                    truncated: n == buf.len(),
+                    local_addr: local,
+                    received_at: Instant::now(),
+                    id: h.connection_id(),
+                    ecn,
+                };
+                if let Some(permit) = permit {
+                    let socket = socket.clone();
+                    let data = buf[..n].to_vec();
+                    spawn_handler(&state, &peer, async move {
+                        let packet = h.on_packet(&data, socket, &peer, &info);
+                        in_connection(info.id, &peer, packet).await;
+                        drop((permit, in_flight));
+                    });
+                } else {
+                    let packet = h.on_packet(&buf[..n], socket.clone(), &peer, &info);
+                    in_connection(info.id, &peer, packet).await;
+                    drop(in_flight);
                 }
             }
-        });
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                error!("UDP recv critical failure in `{}`: {:?}", name, e);
+
+                #[cfg(not(feature = "tracing"))]
+                let _ = e;
+
+                return;
+            }
+        }
     }
+}
 
-    while set.join_next().await.is_some() {}
-    Ok(())
+/// Receives a datagram into `buf`, with its ECN codepoint when `ecn` is set.
+async fn recv_datagram(
+    socket: &UdpSocket,
+    buf: &mut [u8],
+    ecn: bool,
+) -> std::io::Result<(usize, SocketAddr, Option<Ecn>)> {
+    #[cfg(all(target_os = "linux", feature = "ecn"))]
+    if ecn {
+        return crate::ecn::recv_from(socket, buf).await;
+    }
+    #[cfg(not(all(target_os = "linux", feature = "ecn")))]
+    let _ = ecn;
+    let (n, peer) = socket.recv_from(buf).await?;
+    Ok((n, peer, None))
+}
+
+/// Returns whether an interface with this name currently exists.
+fn interface_present(name: &str) -> bool {
+    if_nametoindex(name).is_ok()
+}
+
+/// Waits until the named interface exists again.
+pub async fn wait_for_interface(name: &str) {
+    while !interface_present(name) {
+        sleep(INTERFACE_POLL_INTERVAL).await;
+    }
 }
 
 // Socket Helpers
-fn resolve_addrs(mode: BindMode, port: u16, iface: &NetworkInterface) -> Vec<SocketAddr> {
-    match mode {
+/// Lists the addresses a service binds with `mode`.
+///
+/// With `strict`, an interface without addresses is an error rather than a reason
+/// to bind the wildcard address.
+async fn resolve_addrs(
+    mode: BindMode,
+    port: u16,
+    iface: &NetworkInterface,
+    strict: bool,
+) -> Result<Vec<SocketAddr>> {
+    let addrs = match mode {
         BindMode::Specific(ip) => vec![SocketAddr::new(ip, port)],
         BindMode::BindAll => vec![
             SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
             SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port),
         ],
+        BindMode::Hostname(host) => {
+            let addrs: Vec<SocketAddr> = lookup_host((host.as_str(), port)).await?.collect();
+            if addrs.is_empty() {
+                return Err(Error::NoAddrAvailable);
+            }
+            addrs
+        }
+        BindMode::PreBound(socket) => vec![socket.local_addr()?],
         BindMode::PreferInterface => {
+            if !interface_present(&iface.name) {
+                return Err(Error::InterfaceGone(iface.name.clone()));
+            }
             let mut addrs = Vec::new();
             for ip in &iface.inet {
                 addrs.push(SocketAddr::new(IpAddr::V4(*ip), port));
@@ -210,103 +1399,485 @@ fn resolve_addrs(mode: BindMode, port: u16, iface: &NetworkInterface) -> Vec<Soc
                 addrs.push(SocketAddr::new(IpAddr::V6(*ip), port));
             }
             if addrs.is_empty() {
+                if strict {
+                    return Err(Error::InterfaceHasNoAddr(iface.name.clone()));
+                }
+                let wildcard = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port);
                 #[cfg(feature = "tracing")]
                 warn!(
-                    "Interface `{}` has no IPs configured. Falling back to wildcard 0.0.0.0:{}",
-                    iface.name, port
+                    target: BIND_PLAN_TARGET,
+                    interface = %iface.name,
+                    fallback = %wildcard,
+                    all_interfaces = true,
+                    "Interface `{}` has no IPs configured. Listening on ALL interfaces at {} instead",
+                    iface.name,
+                    wildcard
                 );
-                addrs.push(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port));
+                addrs.push(wildcard);
             }
             addrs
         }
+    };
+    Ok(addrs)
+}
+
+/// Moves the addresses of the preferred family first, keeping the order within each family.
+///
+/// TCP services listen on the first address that binds, so this decides which
+/// family they end up on.
+fn order_by_family(addrs: &mut [SocketAddr], preferred: Option<IpFamily>) {
+    if let Some(family) = preferred {
+        addrs.sort_by_key(|addr| IpFamily::of(addr) != family);
     }
 }
 
-fn bind_tcp_listener(addrs: &[SocketAddr]) -> Result<TcpListener> {
-    for addr in addrs {
-        let domain = if addr.is_ipv4() {
-            Domain::IPV4
-        } else {
-            Domain::IPV6
+fn bind_tcp_listeners(
+    addrs: &[SocketAddr],
+    config: &SocketConfig<'_>,
+    shards: usize,
+) -> Result<Vec<TcpListener>> {
+    // Without SO_REUSEPORT, extra listeners could not share the port.
+    let shards = if cfg!(target_os = "linux") {
+        shards.max(1)
+    } else {
+        1
+    };
+
+    let mut in_use = None;
+    for &addr in addrs {
+        let first = match bind_tcp_socket(addr, config) {
+            Ok(Some(socket)) => socket,
+            Ok(None) => continue,
+            Err(Error::AddrInUse(addr)) => {
+                in_use.get_or_insert(addr);
+                continue;
+            }
+            Err(e) => return Err(e),
         };
-        let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))?;
+        // Later shards join the port picked by the first one, even if it was ephemeral.
+        let addr = first.local_addr()?.as_socket().unwrap_or(addr);
+        let shards = if shares_port(&first) { shards } else { 1 };
+        let mut listeners = vec![first];
+        for _ in 1..shards {
+            listeners.extend(bind_tcp_socket(addr, config)?);
+        }
+        return listeners
+            .into_iter()
+            .map(|socket| Ok(TcpListener::from_std(socket.into())?))
+            .collect();
+    }
 
-        socket.set_reuse_address(true)?;
-        #[cfg(target_os = "linux")]
-        socket.set_reuse_port(true)?;
+    Err(match in_use {
+        Some(addr) => Error::AddrInUse(addr),
+        None if addrs.is_empty() => Error::NoAddrAvailable,
+        None => Error::AllBindsFailed(addrs.len()),
+    })
+}
 
-        if addr.is_ipv6() {
-            socket.set_only_v6(true)?;
-        }
+/// Creates a listening socket on `addr`, or returns `None` if the address cannot be bound.
+///
+/// An address held by another socket is reported as [`Error::AddrInUse`] so callers
+/// can retry it.
+fn bind_tcp_socket(addr: SocketAddr, config: &SocketConfig<'_>) -> Result<Option<Socket>> {
+    let domain = if addr.is_ipv4() {
+        Domain::IPV4
+    } else {
+        Domain::IPV6
+    };
+    let socket = (config.new_socket)(domain, Type::STREAM, Some(Protocol::TCP))?;
 
-        if socket.bind(&((*addr).into())).is_ok() {
-            socket.listen(1024)?;
-            socket.set_nonblocking(true)?;
-            return Ok(TcpListener::from_std(socket.into())?);
-        }
+    socket.set_reuse_address(true)?;
+    try_reuse_port(&socket, &addr, config);
+
+    if addr.is_ipv6() {
+        socket.set_only_v6(config.v6_only)?;
+    }
+
+    set_nonlocal_options(&socket, &addr, config)?;
+    (config.configure)(&socket)?;
+
+    match socket.bind(&addr.into()) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AddrInUse => return Err(Error::AddrInUse(addr)),
+        Err(_) => return Ok(None),
+    }
+    socket.listen(1024)?;
+    socket.set_nonblocking(true)?;
+    Ok(Some(socket))
+}
+
+fn adopt_tcp_listener(listener: &std::net::TcpListener) -> Result<TcpListener> {
+    let listener = listener.try_clone()?;
+    listener.set_nonblocking(true)?;
+    Ok(TcpListener::from_std(listener)?)
+}
+
+fn adopt_udp_socket(socket: &std::net::UdpSocket) -> Result<Vec<UdpSocket>> {
+    let socket = socket.try_clone()?;
+    socket.set_nonblocking(true)?;
+    Ok(vec![UdpSocket::from_std(socket)?])
+}
+
+/// Runs `bind` in the network namespace of `config`, if it names one.
+fn bind_in_netns<T, F>(config: &SocketConfig<'_>, bind: F) -> Result<T>
+where
+    T: Send,
+    F: FnOnce() -> Result<T> + Send,
+{
+    match config.netns {
+        None => bind(),
+        #[cfg(all(target_os = "linux", feature = "netns"))]
+        Some(name) => crate::netns::bind_in(name, bind),
+        #[cfg(not(all(target_os = "linux", feature = "netns")))]
+        Some(name) => Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!(
+                "cannot bind in network namespace `{name}`: requires the `netns` feature on Linux"
+            ),
+        ))),
+    }
+}
+
+/// Asks the kernel to pass the ECN codepoint of each datagram received on `socket`.
+fn set_recv_ecn(socket: &Socket, addr: &SocketAddr, config: &SocketConfig<'_>) -> Result<()> {
+    #[cfg(all(target_os = "linux", feature = "ecn"))]
+    {
+        crate::ecn::enable(socket, addr.is_ipv6(), config.v6_only)?;
+        Ok(())
     }
+    #[cfg(not(all(target_os = "linux", feature = "ecn")))]
+    {
+        let _ = (socket, config);
+        Err(Error::Io(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            format!("cannot report ECN on {addr}: requires the `ecn` feature on Linux"),
+        )))
+    }
+}
+
+/// Enables `SO_REUSEPORT` so that shards can share a port. Only Linux spreads
+/// traffic across such sockets, so it is left off elsewhere.
+fn set_reuse_port(socket: &Socket) -> std::io::Result<()> {
+    #[cfg(target_os = "linux")]
+    socket.set_reuse_port(true)?;
+    #[cfg(not(target_os = "linux"))]
+    let _ = socket;
+    Ok(())
+}
+
+/// Tries to enable `SO_REUSEPORT`. A kernel refusing it is not fatal: the socket
+/// is bound anyway and serves alone, see [`shares_port`].
+fn try_reuse_port(socket: &Socket, addr: &SocketAddr, config: &SocketConfig<'_>) {
+    if let Err(e) = (config.reuse_port)(socket) {
+        #[cfg(feature = "tracing")]
+        warn!(
+            "SO_REUSEPORT unavailable for {}, binding a single socket: {}",
+            addr, e
+        );
+
+        #[cfg(not(feature = "tracing"))]
+        let _ = (addr, e);
+    }
+}
 
-    Err(Error::NoAddrAvailable)
+/// Returns whether more sockets can join the port of `socket`, i.e. whether it
+/// has `SO_REUSEPORT`.
+fn shares_port(socket: &Socket) -> bool {
+    #[cfg(target_os = "linux")]
+    return socket.reuse_port().unwrap_or(false);
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = socket;
+        false
+    }
 }
 
+/// Binds `shards` sockets (at least one) on each of `addrs`, skipping the
+/// addresses that cannot be bound. Without `SO_REUSEPORT`, a single socket is
+/// bound per address.
+///
+/// An address whose socket cannot even be created, e.g. an IPv6 address on a
+/// host with IPv6 disabled, is skipped too, so the other families still serve.
 fn bind_udp_sockets(
     addrs: &[SocketAddr],
-    iface: &NetworkInterface,
-    mcast: &[IpAddr],
+    config: &SocketConfig<'_>,
+    shards: usize,
 ) -> Result<Vec<UdpSocket>> {
+    if addrs.is_empty() {
+        return Err(Error::NoAddrAvailable);
+    }
+    let shards = shards.max(1);
     let mut sockets = Vec::new();
-    let num_cores = num_cpus::get();
 
-    for addr in addrs {
-        for _ in 0..num_cores {
+    for &addr in addrs {
+        // With an ephemeral port, the first shard picks the port and the others
+        // join it, instead of each shard landing on its own random port.
+        let mut addr = addr;
+        for _ in 0..shards {
             let domain = if addr.is_ipv4() {
                 Domain::IPV4
             } else {
                 Domain::IPV6
             };
-            let socket = Socket::new(domain, Type::DGRAM, Some(Protocol::UDP))?;
+            let socket = match (config.new_socket)(domain, Type::DGRAM, Some(Protocol::UDP)) {
+                Ok(socket) => socket,
+                Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    warn!(
+                        "Cannot create a UDP socket for {}, skipping it: {}",
+                        addr, e
+                    );
+
+                    #[cfg(not(feature = "tracing"))]
+                    let _ = e;
+                    break;
+                }
+            };
 
             socket.set_reuse_address(true)?;
-            #[cfg(target_os = "linux")]
-            socket.set_reuse_port(true)?;
+            try_reuse_port(&socket, &addr, config);
 
-            let _ = socket.set_recv_buffer_size(7 * 1024 * 1024);
-            let _ = socket.set_send_buffer_size(7 * 1024 * 1024);
+            let _ = socket.set_recv_buffer_size(UDP_BUFFER_SIZE);
+            let _ = socket.set_send_buffer_size(UDP_BUFFER_SIZE);
 
             if addr.is_ipv6() {
-                socket.set_only_v6(true)?;
-            } else {
+                socket.set_only_v6(config.v6_only)?;
+            } else if config.broadcast || addr.ip() == IpAddr::V4(Ipv4Addr::BROADCAST) {
                 socket.set_broadcast(true)?;
             }
+            if config.recv_ecn {
+                set_recv_ecn(&socket, &addr, config)?;
+            }
 
-            if socket.bind(&((*addr).into())).is_ok() {
-                socket.set_nonblocking(true)?;
-                let udp = UdpSocket::from_std(socket.into())?;
+            set_nonlocal_options(&socket, &addr, config)?;
+            (config.configure)(&socket)?;
 
-                for group in mcast {
-                    join_multicast(&udp, group, iface);
-                }
-                sockets.push(udp);
+            if let Err(e) = socket.bind(&addr.into()) {
+                #[cfg(feature = "tracing")]
+                warn!("Cannot bind UDP socket to {}, skipping it: {}", addr, e);
+
+                #[cfg(not(feature = "tracing"))]
+                let _ = e;
+                break;
+            }
+            if addr.port() == 0
+                && let Some(local) = socket.local_addr()?.as_socket()
+            {
+                addr.set_port(local.port());
+            }
+            let shared = shares_port(&socket);
+            socket.set_nonblocking(true)?;
+            sockets.push(UdpSocket::from_std(socket.into())?);
+            if !shared {
+                break;
             }
         }
     }
 
+    if sockets.is_empty() {
+        return Err(Error::AllBindsFailed(addrs.len()));
+    }
     Ok(sockets)
 }
 
-fn join_multicast(socket: &UdpSocket, group: &IpAddr, iface: &NetworkInterface) {
-    let _ = match group {
+/// Determines how many UDP sockets to bind per address.
+///
+/// Honors [`UDP_SHARDS_ENV`] when set, otherwise uses the cgroup-aware
+/// available parallelism (falling back to [`num_cpus::get`]) capped at a sane maximum.
+fn udp_shard_count() -> usize {
+    let detected = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or_else(|_| num_cpus::get());
+    shard_count(std::env::var(UDP_SHARDS_ENV).ok().as_deref(), detected)
+}
+
+fn shard_count(env_override: Option<&str>, detected: usize) -> usize {
+    if let Some(value) = env_override {
+        match value.trim().parse::<usize>() {
+            Ok(n) if n > 0 => return n,
+            _ => {
+                #[cfg(feature = "tracing")]
+                warn!(
+                    "Ignoring invalid {} value `{}`. Falling back to detected core count.",
+                    UDP_SHARDS_ENV, value
+                );
+            }
+        }
+    }
+    detected.clamp(1, MAX_UDP_SHARDS)
+}
+
+/// Returns the number of connections waiting in the accept queue of the TCP
+/// listener bound to `addr`, or `None` if it cannot be determined.
+///
+/// With [`accept_shards`](crate::TcpHandler::accept_shards), this is the total
+/// over every listener sharing the address.
+///
+/// Only supported on Linux, where it is read from `/proc/net/tcp{,6}`.
+pub fn accept_queue_depth(addr: &SocketAddr) -> Option<u32> {
+    #[cfg(target_os = "linux")]
+    {
+        let table = if addr.is_ipv4() {
+            "/proc/net/tcp"
+        } else {
+            "/proc/net/tcp6"
+        };
+        parse_listen_queue(&std::fs::read_to_string(table).ok()?, addr)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = addr;
+        None
+    }
+}
+
+/// Finds the listening sockets for `addr` in a `/proc/net/tcp{,6}` table and
+/// returns the sum of their `rx_queue` column, which holds the accept queue
+/// length. Returns `None` if no socket listens on `addr`.
+#[cfg(target_os = "linux")]
+fn parse_listen_queue(table: &str, addr: &SocketAddr) -> Option<u32> {
+    const TCP_LISTEN: &str = "0A";
+
+    let local = match addr.ip() {
+        IpAddr::V4(ip) => format!("{:08X}", u32::from_ne_bytes(ip.octets())),
+        IpAddr::V6(ip) => ip
+            .octets()
+            .chunks_exact(4)
+            .map(|w| format!("{:08X}", u32::from_ne_bytes([w[0], w[1], w[2], w[3]])))
+            .collect(),
+    };
+    let local = format!("{}:{:04X}", local, addr.port());
+
+    table
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.get(1) != Some(&local.as_str()) || fields.get(3) != Some(&TCP_LISTEN) {
+                return None;
+            }
+            let (_, rx_queue) = fields.get(4)?.split_once(':')?;
+            u32::from_str_radix(rx_queue, 16).ok()
+        })
+        .reduce(u32::saturating_add)
+}
+
+/// Joins a multicast group, retrying with backoff while the join fails.
+///
+/// A join can fail transiently while the interface is still coming up. Once the
+/// attempts are exhausted, the error is returned if the join is `required` and
+/// logged otherwise.
+async fn join_multicast<F>(group: &IpAddr, iface: &str, required: bool, mut join: F) -> Result<()>
+where
+    F: FnMut() -> std::io::Result<()>,
+{
+    let res = retry(MULTICAST_JOIN_POLICY, || std::future::ready(join())).await;
+    match res {
+        Ok(()) => Ok(()),
+        Err(e) if required => Err(multicast_join_failed(group, iface, e)),
+        Err(e) => {
+            #[cfg(feature = "tracing")]
+            warn!("Skipping multicast group {} on `{}`: {}", group, iface, e);
+
+            #[cfg(not(feature = "tracing"))]
+            let _ = (group, iface, e);
+            Ok(())
+        }
+    }
+}
+
+/// Builds the [`Error::MulticastJoinFailed`] for `group` on `iface`.
+pub(crate) fn multicast_join_failed(group: &IpAddr, iface: &str, source: std::io::Error) -> Error {
+    Error::MulticastJoinFailed {
+        group: *group,
+        iface: iface.to_string(),
+        source,
+    }
+}
+
+/// Joins `group` on `iface`.
+///
+/// IPv6 groups are scoped to the interface: outgoing multicast leaves through it
+/// (`IPV6_MULTICAST_IF`) and, on Linux, the socket only receives the groups it
+/// joined itself (`IPV6_MULTICAST_ALL` off) rather than every group joined on
+/// the host, so memberships of other services on other interfaces do not leak in.
+pub fn join_group(
+    socket: &UdpSocket,
+    group: &IpAddr,
+    iface: &NetworkInterface,
+) -> std::io::Result<()> {
+    match group {
         IpAddr::V4(g) => {
             let i = iface.inet.first().cloned().unwrap_or(Ipv4Addr::UNSPECIFIED);
             socket.join_multicast_v4(*g, i)
         }
-        IpAddr::V6(g) => socket.join_multicast_v6(g, iface.index),
-    };
+        IpAddr::V6(g) => {
+            scope_multicast_v6(&socket2::SockRef::from(socket), iface.index)?;
+            socket.join_multicast_v6(g, iface.index)
+        }
+    }
+}
+
+/// Restricts IPv6 multicast on `socket` to the interface with index `index`.
+fn scope_multicast_v6(socket: &socket2::SockRef<'_>, index: u32) -> std::io::Result<()> {
+    socket.set_multicast_if_v6(index)?;
+    #[cfg(target_os = "linux")]
+    socket.set_multicast_all_v6(false)?;
+    Ok(())
+}
+
+/// Leaves `group` on `iface`, the reverse of [`join_group`].
+///
+/// `remaining` holds the groups the socket stays in. Once none of them is IPv6,
+/// the interface scoping applied by [`join_group`] is lifted again.
+pub(crate) fn leave_group(
+    socket: &UdpSocket,
+    group: &IpAddr,
+    iface: &NetworkInterface,
+    remaining: &[IpAddr],
+) -> std::io::Result<()> {
+    match group {
+        IpAddr::V4(g) => {
+            let i = iface.inet.first().cloned().unwrap_or(Ipv4Addr::UNSPECIFIED);
+            socket.leave_multicast_v4(*g, i)
+        }
+        IpAddr::V6(g) => {
+            socket.leave_multicast_v6(g, iface.index)?;
+            if remaining.iter().any(IpAddr::is_ipv6) {
+                return Ok(());
+            }
+            unscope_multicast_v6(&socket2::SockRef::from(socket))
+        }
+    }
+}
+
+/// Undoes [`scope_multicast_v6`], returning `socket` to the system defaults.
+fn unscope_multicast_v6(socket: &socket2::SockRef<'_>) -> std::io::Result<()> {
+    socket.set_multicast_if_v6(0)?;
+    #[cfg(target_os = "linux")]
+    socket.set_multicast_all_v6(true)?;
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ConnectionId;
+    use crate::state::Transport;
+    use tokio_util::sync::CancellationToken;
+
+    /// Waits for `state` to bind and returns its first address.
+    async fn bound_addr(state: &ServiceState) -> SocketAddr {
+        tokio::time::timeout(Duration::from_secs(2), state.wait_settled())
+            .await
+            .unwrap();
+        state
+            .bound_addrs()
+            .first()
+            .copied()
+            .expect("service did not bind")
+    }
 
     #[test]
     fn test_interface_resolution() {
@@ -316,9 +1887,1867 @@ mod tests {
     }
 
     #[test]
-    fn test_resolve_addrs() {
-        let iface = NetworkInterface::from_str("lo").unwrap();
-        let addrs = resolve_addrs(BindMode::PreferInterface, 8080, &iface);
-        assert!(!addrs.is_empty());
+    fn test_interface_list_flags_loopback() {
+        let ifaces = NetworkInterface::list().unwrap();
+        let lo = ifaces.iter().find(|iface| iface.name == "lo").unwrap();
+        assert!(lo.is_loopback);
+        assert!(lo.is_up);
+        #[cfg(target_os = "linux")]
+        assert!(lo.mtu.is_some_and(|mtu| mtu > 0));
+    }
+
+    #[test]
+    fn test_recv_buffer_size() {
+        assert_eq!(recv_buffer_size(true, Some(1500)), 1500);
+        assert_eq!(recv_buffer_size(true, Some(65536)), MAX_RECV_BUFFER);
+        assert_eq!(recv_buffer_size(true, Some(68)), MIN_RECV_BUFFER);
+        assert_eq!(recv_buffer_size(true, None), MAX_RECV_BUFFER);
+        assert_eq!(recv_buffer_size(false, Some(1500)), MAX_RECV_BUFFER);
+    }
+
+    #[tokio::test]
+    async fn test_rebind_on_missing_interface() {
+        let mut iface = NetworkInterface::from_str("lo").unwrap();
+        iface.name = "maestro-gone0".into();
+
+        let res = resolve_addrs(BindMode::PreferInterface, 0, &iface, true).await;
+        assert!(matches!(res, Err(Error::InterfaceGone(name)) if name == "maestro-gone0"));
+        assert!(
+            resolve_addrs(BindMode::BindAll, 0, &iface, true)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_missing_mac_is_generated() {
+        let mut iface = NetworkInterface {
+            name: "test0".into(),
+            index: 0,
+            inet: vec![Ipv4Addr::LOCALHOST],
+            inet6: vec![],
+            mac: None,
+            mac_is_generated: false,
+            is_up: true,
+            is_loopback: false,
+            supports_multicast: false,
+            mtu: None,
+        };
+        iface.ensure_mac();
+        assert!(iface.mac.is_some());
+        assert!(iface.mac_is_generated);
+        assert_eq!(iface.hardware_mac(), None);
+    }
+
+    #[test]
+    fn test_interface_snapshot_diff() {
+        let old = NetworkInterface {
+            name: "test0".into(),
+            index: 2,
+            inet: vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)],
+            inet6: vec![Ipv6Addr::LOCALHOST],
+            mac: Some([2, 0, 0, 0, 0, 1]),
+            mac_is_generated: true,
+            is_up: true,
+            is_loopback: false,
+            supports_multicast: false,
+            mtu: None,
+        };
+        let mut same = old.clone();
+        same.inet.reverse();
+        same.mac = Some([2, 0, 0, 0, 0, 2]);
+        assert_eq!(old, same);
+        assert!(old.diff(&same).is_empty());
+
+        let mut grown = old.clone();
+        grown.inet.push(Ipv4Addr::new(10, 0, 0, 3));
+        assert_ne!(old, grown);
+        assert_eq!(
+            old.diff(&grown),
+            InterfaceDiff {
+                added: vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3))],
+                removed: vec![],
+            }
+        );
+        assert_eq!(
+            grown.diff(&old).removed,
+            [IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3))]
+        );
+    }
+
+    #[test]
+    fn test_describe_lists_addresses() {
+        let iface = NetworkInterface {
+            name: "test0".into(),
+            index: 3,
+            inet: vec![Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)],
+            inet6: vec![Ipv6Addr::LOCALHOST],
+            mac: Some([2, 0, 0, 0, 0, 0xab]),
+            mac_is_generated: true,
+            is_up: true,
+            is_loopback: false,
+            supports_multicast: true,
+            mtu: None,
+        };
+
+        let description = iface.describe();
+        assert!(description.starts_with("test0 (index 3)"));
+        assert!(description.contains("flags: up, multicast"));
+        for ip in ["10.0.0.1", "10.0.0.2", "::1"] {
+            assert!(description.contains(ip), "{description}");
+        }
+        assert!(description.contains("mac 02:00:00:00:00:ab (generated)"));
+    }
+
+    struct CountingUdp(std::sync::atomic::AtomicUsize);
+    #[async_trait::async_trait]
+    impl UdpHandler for CountingUdp {
+        fn name(&self) -> &'static str {
+            "CountingUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn max_datagram_size(&self) -> Option<usize> {
+            Some(8)
+        }
+        async fn on_packet(
+            &self,
+            data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            assert!(data.len() <= 8);
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_oversized_datagram_dropped() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let handler = Arc::new(CountingUdp(Default::default()));
+        let state = Arc::new(ServiceState::new("CountingUdp", Transport::Udp));
+
+        let task = tokio::spawn(run_udp(
+            handler.clone(),
+            iface,
+            state.clone(),
+            BindMode::PreBound(server.into()),
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(&[0u8; 64], addr).await.unwrap();
+        client.send_to(b"small", addr).await.unwrap();
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        task.abort();
+
+        assert_eq!(handler.0.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert_eq!(state.oversized(), 1);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_unknown_local_addr_is_logged() {
+        let addr = "127.0.0.1:8080".parse().unwrap();
+        assert_eq!(display_local_addr(Ok(addr)), "127.0.0.1:8080");
+        let err = std::io::ErrorKind::NotConnected.into();
+        assert_eq!(display_local_addr(Err(err)), "<unknown>");
+    }
+
+    #[cfg(all(feature = "task-names", tokio_unstable))]
+    #[tokio::test]
+    async fn test_handler_tasks_are_named() {
+        let peer = "10.0.0.7:5353".parse().unwrap();
+        assert_eq!(task_name("dns", &peer), "dns 10.0.0.7:5353");
+
+        let state = ServiceState::new("dns", Transport::Udp);
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        spawn_handler(&state, &peer, async move {
+            let _ = tx.send(());
+        });
+        rx.await.unwrap();
+    }
+
+    struct UnicastGroupUdp(Vec<IpAddr>);
+    #[async_trait::async_trait]
+    impl UdpHandler for UnicastGroupUdp {
+        fn name(&self) -> &'static str {
+            "UnicastGroupUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn multicast_addrs(&self) -> &[IpAddr] {
+            &self.0
+        }
+        fn multicast_join_required(&self) -> bool {
+            false
+        }
+        fn require_multicast(&self) -> bool {
+            true
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_required_multicast_join_fails_service() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let handler = Arc::new(UnicastGroupUdp(vec![IpAddr::V4(Ipv4Addr::new(
+            10, 1, 2, 3,
+        ))]));
+
+        let res = run_udp(
+            handler,
+            iface,
+            Arc::new(ServiceState::new("UnicastGroupUdp", Transport::Udp)),
+            BindMode::PreBound(server.into()),
+        )
+        .await;
+        assert!(matches!(res, Err(Error::MulticastJoinFailed { .. })));
+    }
+
+    /// Handles up to 4 datagrams at once, recording the peak overlap. A `slow`
+    /// datagram takes 5s, any other 20ms; each is sent on once handled.
+    struct SlowUdp {
+        tx: tokio::sync::mpsc::UnboundedSender<Vec<u8>>,
+        active: std::sync::atomic::AtomicUsize,
+        peak: std::sync::atomic::AtomicUsize,
+    }
+    impl SlowUdp {
+        fn new(tx: tokio::sync::mpsc::UnboundedSender<Vec<u8>>) -> Self {
+            Self {
+                tx,
+                active: Default::default(),
+                peak: Default::default(),
+            }
+        }
+    }
+    #[async_trait::async_trait]
+    impl UdpHandler for SlowUdp {
+        fn name(&self) -> &'static str {
+            "SlowUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn concurrent_packets(&self) -> usize {
+            4
+        }
+        async fn on_packet(
+            &self,
+            data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            use std::sync::atomic::Ordering::SeqCst;
+            let active = self.active.fetch_add(1, SeqCst) + 1;
+            self.peak.fetch_max(active, SeqCst);
+            let delay = if data == b"slow" {
+                Duration::from_secs(5)
+            } else {
+                Duration::from_millis(20)
+            };
+            tokio::time::sleep(delay).await;
+            self.active.fetch_sub(1, SeqCst);
+            let _ = self.tx.send(data.to_vec());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_slow_packet_does_not_block_next() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        let task = tokio::spawn(run_udp(
+            Arc::new(SlowUdp::new(tx)),
+            iface,
+            Arc::new(ServiceState::new("SlowUdp", Transport::Udp)),
+            BindMode::PreBound(server.into()),
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"slow", addr).await.unwrap();
+        client.send_to(b"fast", addr).await.unwrap();
+        let handled = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .expect("datagram blocked behind a slow handler");
+        assert_eq!(handled.as_deref(), Some(&b"fast"[..]));
+        task.abort();
+    }
+
+    #[tokio::test]
+    async fn test_udp_flood_spawns_bounded_tasks() {
+        use std::sync::atomic::Ordering::SeqCst;
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handler = Arc::new(SlowUdp::new(tx));
+
+        let task = tokio::spawn(run_udp(
+            handler.clone(),
+            iface,
+            Arc::new(ServiceState::new("SlowUdp", Transport::Udp)),
+            BindMode::PreBound(server.into()),
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for _ in 0..200 {
+            client.send_to(b"flood", addr).await.unwrap();
+        }
+        tokio::time::timeout(Duration::from_secs(5), async {
+            for _ in 0..20 {
+                rx.recv().await.unwrap();
+            }
+        })
+        .await
+        .unwrap();
+        task.abort();
+
+        assert_eq!(handler.peak.load(SeqCst), 4);
+    }
+
+    struct InfoUdp {
+        tx: tokio::sync::mpsc::UnboundedSender<(usize, PacketInfo)>,
+        buffer_from_mtu: bool,
+        recv_ecn: bool,
+    }
+    impl InfoUdp {
+        fn new(tx: tokio::sync::mpsc::UnboundedSender<(usize, PacketInfo)>) -> Self {
+            Self {
+                tx,
+                buffer_from_mtu: false,
+                recv_ecn: false,
+            }
+        }
+    }
+    #[async_trait::async_trait]
+    impl UdpHandler for InfoUdp {
+        fn name(&self) -> &'static str {
+            "InfoUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn buffer_from_mtu(&self) -> bool {
+            self.buffer_from_mtu
+        }
+        fn recv_ecn(&self) -> bool {
+            self.recv_ecn
+        }
+        async fn on_packet(
+            &self,
+            data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            info: &PacketInfo,
+        ) {
+            let _ = self.tx.send((data.len(), *info));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_packet_info_describes_datagram() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("InfoUdp", Transport::Udp));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::spawn(run_udp(
+            Arc::new(InfoUdp::new(tx)),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"hello", addr).await.unwrap();
+        let (len, info) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.len, len);
+        assert_eq!(info.len, 5);
+        assert!(!info.truncated);
+        assert_eq!(info.local_addr, addr);
+        assert_eq!(info.ecn, None);
+        task.abort();
+    }
+
+    #[cfg(all(target_os = "linux", feature = "ecn"))]
+    #[tokio::test]
+    async fn test_ecn_codepoint_reported() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("InfoUdp", Transport::Udp));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handler = InfoUdp {
+            recv_ecn: true,
+            ..InfoUdp::new(tx)
+        };
+        let task = tokio::spawn(run_udp(
+            Arc::new(handler),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let client = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket2::SockRef::from(&client).set_tos_v4(0b10).unwrap();
+        client.send_to(b"ect0", addr).unwrap();
+        let (_, info) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(info.ecn, Some(Ecn::Ect0));
+        task.abort();
+    }
+
+    #[cfg(not(all(target_os = "linux", feature = "ecn")))]
+    #[tokio::test]
+    async fn test_ecn_without_support_fails_binding() {
+        let handler = CountingUdp(Default::default());
+        let mut config = SocketConfig::udp(&handler);
+        config.recv_ecn = true;
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+
+        let res = bind_udp_sockets(&addrs, &config, 1);
+        assert!(matches!(res, Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::Unsupported));
+    }
+
+    #[tokio::test]
+    async fn test_buffer_sized_from_mtu() {
+        let mut iface = NetworkInterface::from_str("lo").unwrap();
+        iface.mtu = Some(1500);
+        let state = Arc::new(ServiceState::new("InfoUdp", Transport::Udp));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let handler = InfoUdp {
+            buffer_from_mtu: true,
+            ..InfoUdp::new(tx)
+        };
+        let task = tokio::spawn(run_udp(
+            Arc::new(handler),
+            Arc::new(iface),
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for size in [1000, 2000] {
+            client.send_to(&vec![0; size], addr).await.unwrap();
+        }
+        for expected in [(1000, false), (1500, true)] {
+            let (_, info) = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!((info.len, info.truncated), expected);
+        }
+        task.abort();
+    }
+
+    struct BroadcastUdp(tokio::sync::mpsc::UnboundedSender<Vec<u8>>);
+    #[async_trait::async_trait]
+    impl UdpHandler for BroadcastUdp {
+        fn name(&self) -> &'static str {
+            "BroadcastUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn broadcast(&self) -> bool {
+            true
+        }
+        async fn on_packet(
+            &self,
+            data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            let _ = self.0.send(data.to_vec());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_bind_to_broadcast_address() {
+        // The loopback network's directed broadcast address is always routable.
+        let broadcast = IpAddr::V4(Ipv4Addr::new(127, 255, 255, 255));
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("BroadcastUdp", Transport::Udp));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::spawn(run_udp(
+            Arc::new(BroadcastUdp(tx)),
+            iface,
+            state.clone(),
+            BindMode::Specific(broadcast),
+        ));
+        let addr = bound_addr(&state).await;
+        assert_eq!(addr.ip(), broadcast);
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.set_broadcast(true).unwrap();
+        client.send_to(b"discover", addr).await.unwrap();
+        let data = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(data, b"discover");
+        task.abort();
+
+        // The limited broadcast address gets SO_BROADCAST without asking for it.
+        let limited = SocketAddr::new(IpAddr::V4(Ipv4Addr::BROADCAST), 0);
+        let sockets = bind_udp_sockets(
+            &[limited],
+            &SocketConfig::udp(&CountingUdp(Default::default())),
+            1,
+        )
+        .unwrap();
+        assert!(socket2::SockRef::from(&sockets[0]).broadcast().unwrap());
+    }
+
+    /// Collects the logs of the current thread while the returned guard lives.
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct Capture(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    #[cfg(feature = "tracing")]
+    impl Capture {
+        fn start() -> (Self, tracing::subscriber::DefaultGuard) {
+            let capture = Capture::default();
+            let subscriber = tracing_subscriber::fmt()
+                .with_writer({
+                    let capture = capture.clone();
+                    move || capture.clone()
+                })
+                .with_ansi(false)
+                .finish();
+            (capture, tracing::subscriber::set_default(subscriber))
+        }
+
+        fn logs(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl std::io::Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test]
+    async fn test_bind_plan_logged() {
+        let (capture, _guard) = Capture::start();
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("FamilyTcp", Transport::Tcp));
+        let task = tokio::spawn(run_tcp(
+            Arc::new(FamilyTcp),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+        task.abort();
+
+        let logs = capture.logs();
+        let plan = logs
+            .lines()
+            .find(|line| line.contains("bind plan"))
+            .expect("no bind plan logged");
+        assert!(plan.contains(BIND_PLAN_TARGET), "{plan}");
+        assert!(plan.contains("mode=Specific(127.0.0.1)"), "{plan}");
+        assert!(plan.contains(&addr.to_string()), "{plan}");
+    }
+
+    #[tokio::test]
+    async fn test_multicast_join_retried() {
+        let group = IpAddr::V4(Ipv4Addr::new(239, 1, 2, 3));
+        let mut calls = 0;
+        let res = join_multicast(&group, "lo", true, || {
+            calls += 1;
+            if calls == 1 {
+                Err(std::io::ErrorKind::AddrNotAvailable.into())
+            } else {
+                Ok(())
+            }
+        })
+        .await;
+        assert!(res.is_ok());
+        assert_eq!(calls, 2);
+
+        let failing = || Err(std::io::ErrorKind::AddrNotAvailable.into());
+        assert!(join_multicast(&group, "lo", true, failing).await.is_err());
+        assert!(join_multicast(&group, "lo", false, failing).await.is_ok());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_v6_multicast_scoped_to_interface() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let socket = UdpSocket::bind("[::1]:0").await.unwrap();
+        let group = IpAddr::V6("ff02::1:3".parse().unwrap());
+
+        // The join itself may be refused on a loopback without multicast support;
+        // the scoping options are applied before it either way.
+        let joined = join_group(&socket, &group, &iface).is_ok();
+
+        let sock = socket2::SockRef::from(&socket);
+        assert_eq!(sock.multicast_if_v6().unwrap(), iface.index);
+        assert!(!sock.multicast_all_v6().unwrap());
+
+        if joined {
+            leave_group(&socket, &group, &iface, &[]).unwrap();
+            assert_eq!(sock.multicast_if_v6().unwrap(), 0);
+            assert!(sock.multicast_all_v6().unwrap());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bind_mode_serde_round_trip() {
+        let cases = [
+            (BindMode::PreferInterface, r#""prefer_interface""#),
+            (BindMode::BindAll, r#""bind_all""#),
+            (
+                BindMode::Specific("10.0.0.1".parse().unwrap()),
+                r#"{"specific":"10.0.0.1"}"#,
+            ),
+            (
+                BindMode::Specific("::1".parse().unwrap()),
+                r#"{"specific":"::1"}"#,
+            ),
+            (
+                BindMode::Hostname("localhost".into()),
+                r#"{"hostname":"localhost"}"#,
+            ),
+        ];
+        for (mode, json) in cases {
+            assert_eq!(serde_json::to_string(&mode).unwrap(), json);
+            let back: BindMode = serde_json::from_str(json).unwrap();
+            assert_eq!(format!("{back:?}"), format!("{mode:?}"));
+        }
+
+        let socket = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(serde_json::to_string(&BindMode::PreBound(socket.into())).is_err());
+        assert!(serde_json::from_str::<BindMode>(r#"{"specific":"not-an-ip"}"#).is_err());
+    }
+
+    #[cfg(all(target_os = "linux", feature = "core-affinity"))]
+    struct PinnedUdp(std::sync::Mutex<Vec<Vec<usize>>>);
+    #[cfg(all(target_os = "linux", feature = "core-affinity"))]
+    #[async_trait::async_trait]
+    impl UdpHandler for PinnedUdp {
+        fn name(&self) -> &'static str {
+            "PinnedUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            // On Linux, this is the affinity of the calling thread.
+            let cores = core_affinity::get_core_ids().unwrap_or_default();
+            let cores = cores.into_iter().map(|core| core.id).collect();
+            self.0.lock().unwrap().push(cores);
+        }
+    }
+
+    #[cfg(all(target_os = "linux", feature = "core-affinity"))]
+    #[tokio::test]
+    async fn test_shards_pinned_to_distinct_cores() {
+        let cores = shard_cores(true).unwrap();
+        if cores.len() < 2 {
+            return;
+        }
+        let handler = Arc::new(PinnedUdp(Default::default()));
+        let state = Arc::new(ServiceState::new("PinnedUdp", Transport::Udp));
+        let mut set = JoinSet::new();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        for core in &cores[..2] {
+            let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+            let addr = socket.local_addr().unwrap();
+            let shard = Shard {
+                socket,
+                core: Some(*core),
+                buffer_size: MAX_RECV_BUFFER,
+            };
+            spawn_shard(&mut set, &shard, &handler, &state);
+            client.send_to(b"ping", addr).await.unwrap();
+        }
+
+        for _ in 0..100 {
+            if handler.0.lock().unwrap().len() == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut seen = handler.0.lock().unwrap().clone();
+        seen.sort();
+        assert_eq!(seen, [[cores[0]], [cores[1]]]);
+        set.abort_all();
+    }
+
+    #[tokio::test]
+    async fn test_reuse_port_failure_disables_sharding() {
+        let handler = CountingUdp(Default::default());
+        let mut config = SocketConfig::udp(&handler);
+        config.reuse_port = |_| Err(std::io::ErrorKind::Unsupported.into());
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+
+        let sockets = bind_udp_sockets(&addrs, &config, 4).unwrap();
+        assert_eq!(sockets.len(), 1);
+
+        let listeners = bind_tcp_listeners(&addrs, &config, 4).unwrap();
+        assert_eq!(listeners.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_netns_fails_before_binding() {
+        let handler = CountingUdp(Default::default());
+        let mut config = SocketConfig::udp(&handler);
+        config.netns = Some("maestro-no-such-netns");
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+
+        let res = bind_in_netns(&config, || bind_udp_sockets(&addrs, &config, 1));
+        assert!(matches!(res, Err(Error::Io(_))));
+    }
+
+    #[tokio::test]
+    async fn test_multicast_join_error_names_group() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let unicast = IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3));
+
+        let res = join_multicast(&unicast, &iface.name, true, || {
+            join_group(&socket, &unicast, &iface)
+        })
+        .await;
+        match res {
+            Err(Error::MulticastJoinFailed { group, iface, .. }) => {
+                assert_eq!(group, unicast);
+                assert_eq!(iface, "lo");
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_port_zero_shards_share_port() {
+        let handler = CountingUdp(Default::default());
+        let config = SocketConfig::udp(&handler);
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        let sockets = bind_udp_sockets(&addrs, &config, 4).unwrap();
+
+        assert_eq!(sockets.len(), 4);
+        let port = sockets[0].local_addr().unwrap().port();
+        assert_ne!(port, 0);
+        for socket in &sockets {
+            assert_eq!(socket.local_addr().unwrap().port(), port);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_udp_bind_errors_are_distinct() {
+        let handler = CountingUdp(Default::default());
+        let config = SocketConfig::udp(&handler);
+
+        let none = bind_udp_sockets(&[], &config, 0).unwrap_err();
+        assert!(matches!(none, Error::NoAddrAvailable));
+
+        // TEST-NET-1 is never assigned locally, so binding it fails without freebind.
+        let addrs = ["192.0.2.1:0".parse().unwrap()];
+        let failed = bind_udp_sockets(&addrs, &config, 0).unwrap_err();
+        assert!(matches!(failed, Error::AllBindsFailed(1)));
+        assert_ne!(none.to_string(), failed.to_string());
+
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        assert_eq!(bind_udp_sockets(&addrs, &config, 0).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_udp_bind_skips_unbindable_address() {
+        let handler = CountingUdp(Default::default());
+        let config = SocketConfig::udp(&handler);
+        // The unbindable address comes first, so it must not stop the loop.
+        let addrs = [
+            "192.0.2.1:0".parse().unwrap(),
+            "127.0.0.1:0".parse().unwrap(),
+        ];
+        let sockets = bind_udp_sockets(&addrs, &config, 1).unwrap();
+        assert_eq!(sockets.len(), 1);
+        let addr = sockets[0].local_addr().unwrap();
+        assert_eq!(addr.ip(), IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"ping", addr).await.unwrap();
+        let mut buf = [0; 4];
+        let (n, _) = tokio::time::timeout(Duration::from_secs(1), sockets[0].recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_unicast_socket_has_no_broadcast() {
+        let handler = CountingUdp(Default::default());
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        let sockets = bind_udp_sockets(&addrs, &SocketConfig::udp(&handler), 1).unwrap();
+        assert!(!sockets[0].broadcast().unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    struct FreebindUdp;
+    #[cfg(target_os = "linux")]
+    #[async_trait::async_trait]
+    impl UdpHandler for FreebindUdp {
+        fn name(&self) -> &'static str {
+            "FreebindUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn freebind(&self) -> bool {
+            true
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_freebind_binds_nonlocal_address() {
+        let config = SocketConfig::udp(&FreebindUdp);
+        // TEST-NET-1, never configured locally.
+        let addrs = ["192.0.2.1:0".parse().unwrap()];
+        let sockets = bind_udp_sockets(&addrs, &config, 1).unwrap();
+
+        assert_eq!(sockets.len(), 1);
+        assert_eq!(sockets[0].local_addr().unwrap().ip(), addrs[0].ip());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_tcp_accept_shards() {
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        let listeners = bind_tcp_listeners(&addrs, &SocketConfig::tcp(&TtlTcp), 4).unwrap();
+        assert_eq!(listeners.len(), 4);
+        let addr = listeners[0].local_addr().unwrap();
+        assert_ne!(addr.port(), 0);
+
+        // The kernel spreads connections by hash, so enough clients reach every shard.
+        let mut clients = Vec::new();
+        for _ in 0..64 {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        for listener in &listeners {
+            assert_eq!(listener.local_addr().unwrap(), addr);
+            tokio::time::timeout(Duration::from_secs(1), listener.accept())
+                .await
+                .expect("shard accepted no connection")
+                .unwrap();
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    struct HeldTcp {
+        active: std::sync::atomic::AtomicUsize,
+        release: Arc<tokio::sync::Notify>,
+    }
+    #[cfg(target_os = "linux")]
+    #[async_trait::async_trait]
+    impl TcpHandler for HeldTcp {
+        fn name(&self) -> &'static str {
+            "HeldTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn concurrency(&self) -> crate::TcpConcurrency {
+            crate::TcpConcurrency::new(2, Some(10))
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            use std::sync::atomic::Ordering::SeqCst;
+            use tokio::io::AsyncWriteExt;
+            self.active.fetch_add(1, SeqCst);
+            let _ = s.write_all(b"hi").await;
+            self.release.notified().await;
+            self.active.fetch_sub(1, SeqCst);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_connection_limit_is_global_across_shards() {
+        use std::sync::atomic::Ordering::SeqCst;
+        use tokio::io::AsyncReadExt;
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("HeldTcp", Transport::Tcp));
+        let handler = Arc::new(HeldTcp {
+            active: Default::default(),
+            release: Default::default(),
+        });
+        let task = tokio::spawn(run_tcp(
+            handler.clone(),
+            iface,
+            state.clone(),
+            BindMode::Specific("127.0.0.1".parse().unwrap()),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let mut clients = Vec::new();
+        for _ in 0..30 {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(handler.active.load(SeqCst), 10);
+
+        // Each release frees a slot for a waiting client, whichever shard it is on.
+        let mut served = 0;
+        for client in &mut clients {
+            let mut buf = [0u8; 2];
+            if tokio::time::timeout(Duration::from_millis(50), client.read_exact(&mut buf))
+                .await
+                .is_ok()
+            {
+                served += 1;
+            }
+        }
+        assert_eq!(served, 10);
+        handler.release.notify_waiters();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(handler.active.load(SeqCst), 10);
+        task.abort();
+    }
+
+    #[cfg(target_os = "linux")]
+    struct LimitedTcp(Arc<tokio::sync::Notify>);
+    #[cfg(target_os = "linux")]
+    #[async_trait::async_trait]
+    impl TcpHandler for LimitedTcp {
+        fn name(&self) -> &'static str {
+            "LimitedTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn max_connections(&self) -> Option<usize> {
+            Some(1)
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            use tokio::io::AsyncWriteExt;
+            let _ = s.write_all(b"hi").await;
+            self.0.notified().await;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_connection_limit_stops_accepting() {
+        use tokio::io::AsyncReadExt;
+        use tokio::time::timeout;
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let state = Arc::new(ServiceState::new("LimitedTcp", Transport::Tcp));
+        let task = tokio::spawn(run_tcp(
+            Arc::new(LimitedTcp(release.clone())),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let mut buf = [0u8; 2];
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        first.read_exact(&mut buf).await.unwrap();
+
+        // The limit is reached: the second connection stays in the backlog.
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        let pending = timeout(Duration::from_millis(200), second.read_exact(&mut buf)).await;
+        assert!(pending.is_err());
+        assert_eq!(accept_queue_depth(&addr), Some(1));
+
+        release.notify_one();
+        timeout(Duration::from_secs(1), second.read_exact(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf, b"hi");
+        assert_eq!(accept_queue_depth(&addr), Some(0));
+        task.abort();
+    }
+
+    struct SheddingTcp(Arc<tokio::sync::Notify>);
+    #[async_trait::async_trait]
+    impl TcpHandler for SheddingTcp {
+        fn name(&self) -> &'static str {
+            "SheddingTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn max_in_flight(&self) -> Option<usize> {
+            Some(1)
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            use tokio::io::AsyncWriteExt;
+            let _ = s.write_all(b"hi").await;
+            self.0.notified().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_in_flight_mark_sheds_connections() {
+        use tokio::io::AsyncReadExt;
+        use tokio::time::timeout;
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let state = Arc::new(ServiceState::new("SheddingTcp", Transport::Tcp));
+        let task = tokio::spawn(run_tcp(
+            Arc::new(SheddingTcp(release.clone())),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let mut buf = [0u8; 2];
+        let mut first = TcpStream::connect(addr).await.unwrap();
+        first.read_exact(&mut buf).await.unwrap();
+        assert_eq!(state.in_flight(), 1);
+
+        // Past the mark: the connection is accepted and closed at once.
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        let mut rest = Vec::new();
+        let n = timeout(Duration::from_secs(1), second.read_to_end(&mut rest))
+            .await
+            .unwrap()
+            .unwrap_or(0);
+        assert_eq!(n, 0);
+        assert_eq!(state.shed(), 1);
+
+        // Once the load recedes, connections are served again.
+        release.notify_one();
+        while state.in_flight() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut third = TcpStream::connect(addr).await.unwrap();
+        timeout(Duration::from_secs(1), third.read_exact(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf, b"hi");
+        assert_eq!(state.shed(), 1);
+        task.abort();
+    }
+
+    struct BootstrapTcp(Arc<tokio::sync::Notify>);
+    #[async_trait::async_trait]
+    impl TcpHandler for BootstrapTcp {
+        fn name(&self) -> &'static str {
+            "BootstrapTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn accept_deadline(&self) -> Option<Duration> {
+            Some(Duration::from_millis(200))
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            use tokio::io::AsyncWriteExt;
+            let _ = s.write_all(b"hi").await;
+            self.0.notified().await;
+            let _ = s.write_all(b"bye").await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_accept_deadline_stops_accepting() {
+        use tokio::io::AsyncReadExt;
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let release = Arc::new(tokio::sync::Notify::new());
+        let state = Arc::new(ServiceState::new("BootstrapTcp", Transport::Tcp));
+        let task = tokio::spawn(run_tcp(
+            Arc::new(BootstrapTcp(release.clone())),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let mut buf = [0u8; 2];
+        let mut early = TcpStream::connect(addr).await.unwrap();
+        early.read_exact(&mut buf).await.unwrap();
+
+        // The service exits at the deadline and its listener is closed.
+        timeout(Duration::from_secs(1), task)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(TcpStream::connect(addr).await.is_err());
+
+        // The connection accepted before the deadline carries on.
+        release.notify_one();
+        let mut rest = Vec::new();
+        early.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"bye");
+    }
+
+    struct FragileUdp {
+        policy: SocketFailurePolicy,
+        handled: std::sync::atomic::AtomicUsize,
+    }
+    #[async_trait::async_trait]
+    impl UdpHandler for FragileUdp {
+        fn name(&self) -> &'static str {
+            "FragileUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn on_socket_failure(&self) -> SocketFailurePolicy {
+            self.policy
+        }
+        async fn on_packet(
+            &self,
+            data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            assert_ne!(data, b"boom", "socket task failure");
+            self.handled
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    /// Crashes the single socket task of a service, then sends it a regular datagram.
+    /// Returns how many datagrams were handled and the service's exit, if it exited.
+    async fn crash_socket_task(policy: SocketFailurePolicy) -> (usize, Option<Result<()>>) {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let handler = Arc::new(FragileUdp {
+            policy,
+            handled: Default::default(),
+        });
+        let mut task = tokio::spawn(run_udp(
+            handler.clone(),
+            iface,
+            Arc::new(ServiceState::new("FragileUdp", Transport::Udp)),
+            BindMode::PreBound(server.into()),
+        ));
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"boom", addr).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        client.send_to(b"ok", addr).await.unwrap();
+        let exit = tokio::time::timeout(Duration::from_millis(300), &mut task).await;
+        task.abort();
+
+        let handled = handler.handled.load(std::sync::atomic::Ordering::SeqCst);
+        (handled, exit.ok().map(|res| res.unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_socket_failure_ignore() {
+        let (handled, exit) = crash_socket_task(SocketFailurePolicy::Ignore).await;
+        assert_eq!(handled, 0);
+        assert!(matches!(exit, Some(Err(Error::ServiceFailure(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_socket_failure_respawn() {
+        let (handled, exit) = crash_socket_task(SocketFailurePolicy::Respawn).await;
+        assert_eq!(handled, 1);
+        assert!(exit.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_socket_failure_fails_service() {
+        let (handled, exit) = crash_socket_task(SocketFailurePolicy::FailService).await;
+        assert_eq!(handled, 0);
+        assert!(matches!(exit, Some(Err(Error::ServiceFailure(_)))));
+    }
+
+    #[tokio::test]
+    async fn test_accept_queued_drains_backlog() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let handler = Arc::new(TtlTcp);
+        let state = Arc::new(ServiceState::new("TtlTcp", Transport::Tcp));
+
+        let mut clients = Vec::new();
+        for _ in 0..5 {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        assert_eq!(accept_queued(&listener, &handler, &state, None, 3).await, 3);
+        assert_eq!(accept_queued(&listener, &handler, &state, None, 8).await, 2);
+        assert_eq!(accept_queued(&listener, &handler, &state, None, 8).await, 0);
+
+        let limit = Arc::new(Semaphore::new(1));
+        clients.push(TcpStream::connect(addr).await.unwrap());
+        clients.push(TcpStream::connect(addr).await.unwrap());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(
+            accept_queued(&listener, &handler, &state, Some(&limit), 8).await,
+            1
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_accept_queue_depth() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert_eq!(accept_queue_depth(&addr), Some(0));
+
+        let _clients: Vec<_> = (0..3)
+            .map(|_| std::net::TcpStream::connect(addr).unwrap())
+            .collect();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert_eq!(accept_queue_depth(&addr), Some(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stalled_accept_is_detected() {
+        let addr = "127.0.0.1:8080".parse().unwrap();
+        let state = Arc::new(ServiceState::new("StalledTcp", Transport::Tcp));
+        let timeout = Duration::from_secs(2);
+        let watch = |progress: &Arc<AcceptProgress>, depth| {
+            watch_accepts(vec![addr], progress.clone(), state.clone(), timeout, depth)
+        };
+        let backlog: fn(&SocketAddr) -> Option<u32> = |_| Some(3);
+        let empty: fn(&SocketAddr) -> Option<u32> = |_| Some(0);
+
+        // Connections wait and the loop takes none: wedged.
+        let stalled = Arc::new(AcceptProgress::default());
+        let started = tokio::time::Instant::now();
+        assert_eq!(watch(&stalled, backlog).await, timeout);
+        assert!(started.elapsed() < timeout * 2);
+
+        // Idle, or waiting for a connection slot: not wedged.
+        let idle = Arc::new(AcceptProgress::default());
+        assert!(
+            tokio::time::timeout(timeout * 3, watch(&idle, empty))
+                .await
+                .is_err()
+        );
+        let holding = Arc::new(AcceptProgress::default());
+        holding.holding.fetch_add(1, Ordering::Relaxed);
+        assert!(
+            tokio::time::timeout(timeout * 3, watch(&holding, backlog))
+                .await
+                .is_err()
+        );
+
+        // Busy: the queue never empties, but the loop keeps accepting.
+        let busy = Arc::new(AcceptProgress::default());
+        let accepting = {
+            let busy = busy.clone();
+            async move {
+                loop {
+                    busy.accepted.fetch_add(1, Ordering::Relaxed);
+                    sleep(Duration::from_millis(100)).await;
+                }
+            }
+        };
+        tokio::select! {
+            _ = watch(&busy, backlog) => panic!("a busy loop was reported wedged"),
+            _ = accepting => unreachable!(),
+            _ = sleep(timeout * 3) => {}
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    struct SaturatedTcp;
+    #[cfg(target_os = "linux")]
+    #[async_trait::async_trait]
+    impl TcpHandler for SaturatedTcp {
+        fn name(&self) -> &'static str {
+            "SaturatedTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn concurrency(&self) -> crate::TcpConcurrency {
+            crate::TcpConcurrency::new(4, Some(1))
+        }
+        fn accept_stall_timeout(&self) -> Option<Duration> {
+            Some(Duration::from_millis(200))
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            std::future::pending::<()>().await;
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_saturated_shards_are_not_wedged() {
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("SaturatedTcp", Transport::Tcp));
+        let mut task = tokio::spawn(run_tcp(
+            Arc::new(SaturatedTcp),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        // One connection takes the only slot, the others queue on whichever shards
+        // they hash to, while the other shards sit idle with empty queues.
+        let mut clients = Vec::new();
+        for _ in 0..3 {
+            clients.push(TcpStream::connect(addr).await.unwrap());
+        }
+        assert!(
+            tokio::time::timeout(Duration::from_secs(1), &mut task)
+                .await
+                .is_err(),
+            "a saturated service was reported wedged"
+        );
+        assert_eq!(accept_queue_depth(&addr), Some(2));
+        task.abort();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_listen_queue_v6() {
+        let table = "  sl  local_address rem_address st tx_queue rx_queue\n   \
+            0: 00000000000000000000000001000000:1F90 00000000000000000000000000000000:0000 0A 00000000:00000002 00:00000000";
+        let addr: SocketAddr = "[::1]:8080".parse().unwrap();
+        if cfg!(target_endian = "little") {
+            assert_eq!(parse_listen_queue(table, &addr), Some(2));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_parse_listen_queue_sums_shards() {
+        let table = "  sl  local_address rem_address st tx_queue rx_queue\n   \
+            0: 0100007F:1F90 00000000:0000 0A 00000000:00000002 00:00000000\n   \
+            1: 0100007F:1F90 00000000:0000 0A 00000000:00000003 00:00000000\n   \
+            2: 0100007F:1F90 0100007F:C350 01 00000000:00000000 00:00000000";
+        let addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        if cfg!(target_endian = "little") {
+            assert_eq!(parse_listen_queue(table, &addr), Some(5));
+            assert_eq!(
+                parse_listen_queue(table, &"127.0.0.1:80".parse().unwrap()),
+                None
+            );
+        }
+    }
+
+    struct TtlTcp;
+    #[async_trait::async_trait]
+    impl TcpHandler for TtlTcp {
+        fn name(&self) -> &'static str {
+            "TtlTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn configure_socket(&self, socket: &Socket) -> std::io::Result<()> {
+            socket.set_ttl_v4(42)
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_configure_socket_callback() {
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        let listener = bind_tcp_listeners(&addrs, &SocketConfig::tcp(&TtlTcp), 1)
+            .unwrap()
+            .remove(0);
+        assert_eq!(socket2::SockRef::from(&listener).ttl_v4().unwrap(), 42);
+    }
+
+    struct TimeoutUdp;
+    #[async_trait::async_trait]
+    impl UdpHandler for TimeoutUdp {
+        fn name(&self) -> &'static str {
+            "TimeoutUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn configure_socket(&self, socket: &Socket) -> std::io::Result<()> {
+            socket.set_read_timeout(Some(Duration::from_secs(2)))?;
+            socket.set_write_timeout(Some(Duration::from_secs(3)))
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_kernel_timeouts_via_configure_socket() {
+        let addrs = ["127.0.0.1:0".parse().unwrap()];
+        let socket = bind_udp_sockets(&addrs, &SocketConfig::udp(&TimeoutUdp), 1)
+            .unwrap()
+            .remove(0);
+        let socket = socket2::SockRef::from(&socket);
+        assert_eq!(socket.read_timeout().unwrap(), Some(Duration::from_secs(2)));
+        assert_eq!(
+            socket.write_timeout().unwrap(),
+            Some(Duration::from_secs(3))
+        );
+    }
+
+    #[test]
+    fn test_shard_count_env_override() {
+        assert_eq!(shard_count(Some("2"), 64), 2);
+        assert_eq!(shard_count(Some("48"), 4), 48);
+        assert_eq!(shard_count(None, 64), MAX_UDP_SHARDS);
+        assert_eq!(shard_count(Some("nope"), 4), 4);
+        assert_eq!(shard_count(Some("0"), 4), 4);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_addrs() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let addrs = resolve_addrs(BindMode::PreferInterface, 8080, &iface, true)
+            .await
+            .unwrap();
+        assert!(!addrs.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_strict_interface_never_falls_back_to_wildcard() {
+        let iface = NetworkInterface {
+            name: "lo".into(),
+            index: 1,
+            inet: vec![],
+            inet6: vec![],
+            mac: None,
+            mac_is_generated: false,
+            is_up: true,
+            is_loopback: true,
+            supports_multicast: false,
+            mtu: None,
+        };
+        let res = resolve_addrs(BindMode::PreferInterface, 80, &iface, true).await;
+        assert!(matches!(res, Err(Error::InterfaceHasNoAddr(name)) if name == "lo"));
+
+        let addrs = resolve_addrs(BindMode::PreferInterface, 80, &iface, false)
+            .await
+            .unwrap();
+        assert_eq!(addrs, ["0.0.0.0:80".parse().unwrap()]);
+    }
+
+    #[tokio::test]
+    async fn test_family_preference_ordering() {
+        let iface = NetworkInterface {
+            name: "lo".into(),
+            index: 1,
+            inet: vec![Ipv4Addr::LOCALHOST],
+            inet6: vec![Ipv6Addr::LOCALHOST],
+            mac: None,
+            mac_is_generated: false,
+            is_up: true,
+            is_loopback: true,
+            supports_multicast: false,
+            mtu: None,
+        };
+        let mut addrs = resolve_addrs(BindMode::PreferInterface, 80, &iface, true)
+            .await
+            .unwrap();
+        let families = |addrs: &[SocketAddr]| addrs.iter().map(IpFamily::of).collect::<Vec<_>>();
+
+        order_by_family(&mut addrs, None);
+        assert_eq!(families(&addrs), [IpFamily::V4, IpFamily::V6]);
+        order_by_family(&mut addrs, Some(IpFamily::V6));
+        assert_eq!(families(&addrs), [IpFamily::V6, IpFamily::V4]);
+        order_by_family(&mut addrs, Some(IpFamily::V4));
+        assert_eq!(families(&addrs), [IpFamily::V4, IpFamily::V6]);
+    }
+
+    #[tokio::test]
+    async fn test_resolve_hostname_and_bind() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let addrs = resolve_addrs(BindMode::Hostname("localhost".into()), 0, &iface, true)
+            .await
+            .unwrap();
+        assert!(!addrs.is_empty());
+        assert!(addrs.iter().all(|a| a.ip().is_loopback()));
+
+        let handler = CountingUdp(Default::default());
+        let config = SocketConfig::udp(&handler);
+        let listener = bind_tcp_listeners(&addrs, &config, 1).unwrap().remove(0);
+        assert!(listener.local_addr().unwrap().ip().is_loopback());
+    }
+
+    #[tokio::test]
+    async fn test_dual_stack_accepts_v4_and_v6() {
+        let handler = CountingUdp(Default::default());
+        let mut config = SocketConfig::udp(&handler);
+        config.v6_only = false;
+        let listener = bind_tcp_listeners(&["[::]:0".parse().unwrap()], &config, 1)
+            .unwrap()
+            .remove(0);
+        let port = listener.local_addr().unwrap().port();
+
+        let _v4 = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let (_, peer) = listener.accept().await.unwrap();
+        assert!(peer.is_ipv6());
+        assert!(peer.ip().to_canonical().is_ipv4());
+
+        let _v6 = TcpStream::connect(("::1", port)).await.unwrap();
+        let (_, peer) = listener.accept().await.unwrap();
+        assert_eq!(peer.ip(), IpAddr::V6(Ipv6Addr::LOCALHOST));
+    }
+
+    #[tokio::test]
+    async fn test_udp_skips_unavailable_ipv6() {
+        let handler = CountingUdp(Default::default());
+        let mut config = SocketConfig::udp(&handler);
+        config.new_socket = |domain, ty, protocol| {
+            if domain == Domain::IPV6 {
+                // EAFNOSUPPORT, what Linux reports when IPv6 is disabled.
+                return Err(std::io::Error::from_raw_os_error(97));
+            }
+            Socket::new(domain, ty, protocol)
+        };
+        let addrs = ["[::1]:0".parse().unwrap(), "127.0.0.1:0".parse().unwrap()];
+        let sockets = bind_udp_sockets(&addrs, &config, 1).unwrap();
+        assert_eq!(sockets.len(), 1);
+        let addr = sockets[0].local_addr().unwrap();
+        assert!(addr.is_ipv4());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"ping", addr).await.unwrap();
+        let mut buf = [0; 4];
+        let (n, _) = tokio::time::timeout(Duration::from_secs(1), sockets[0].recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"ping");
+
+        let res = bind_udp_sockets(&addrs[..1], &config, 1);
+        assert!(matches!(res, Err(Error::AllBindsFailed(1))));
+    }
+
+    struct FamilyTcp;
+    #[async_trait::async_trait]
+    impl TcpHandler for FamilyTcp {
+        fn name(&self) -> &'static str {
+            "FamilyTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            use tokio::io::AsyncWriteExt;
+            let reply: &[u8] = if local.is_ipv6() { b"6" } else { b"4" };
+            let _ = s.write_all(reply).await;
+        }
+    }
+
+    /// Reads until its token is cancelled, then reports whether data was seen.
+    struct CancellableTcp(tokio::sync::mpsc::UnboundedSender<(CancellationToken, bool)>);
+    #[async_trait::async_trait]
+    impl TcpHandler for CancellableTcp {
+        fn name(&self) -> &'static str {
+            "CancellableTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            token: CancellationToken,
+        ) {
+            use tokio::io::AsyncReadExt;
+            let _ = self.0.send((token.clone(), false));
+            let mut buf = [0u8; 16];
+            tokio::select! {
+                _ = s.read(&mut buf) => {}
+                _ = token.cancelled() => {
+                    let _ = self.0.send((token, true));
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_token_interrupts_read() {
+        use tokio::time::timeout;
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("CancellableTcp", Transport::Tcp));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::spawn(run_tcp(
+            Arc::new(CancellableTcp(tx)),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+        let mut next = async || {
+            timeout(Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap()
+        };
+
+        // Cancelling one connection leaves the others reading.
+        let _first = TcpStream::connect(addr).await.unwrap();
+        let (first, _) = next().await;
+        let _second = TcpStream::connect(addr).await.unwrap();
+        let (second, _) = next().await;
+        first.cancel();
+        let (_, cancelled) = next().await;
+        assert!(cancelled);
+        assert!(!second.is_cancelled());
+
+        // Closing the service's connections reaches the remaining ones.
+        state.close_connections();
+        let (_, cancelled) = next().await;
+        assert!(cancelled);
+        while state.in_flight() > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        task.abort();
+    }
+
+    struct CorrelatedTcp(tokio::sync::mpsc::UnboundedSender<Option<ConnectionId>>);
+    #[async_trait::async_trait]
+    impl TcpHandler for CorrelatedTcp {
+        fn name(&self) -> &'static str {
+            "CorrelatedTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            #[cfg(feature = "tracing")]
+            info!("handling request");
+            let _ = self.0.send(ConnectionId::current());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connections_get_distinct_ids() {
+        #[cfg(feature = "tracing")]
+        let (capture, _guard) = Capture::start();
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("CorrelatedTcp", Transport::Tcp));
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let task = tokio::spawn(run_tcp(
+            Arc::new(CorrelatedTcp(tx)),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+        let addr = bound_addr(&state).await;
+
+        let mut ids = Vec::new();
+        for _ in 0..2 {
+            let _client = TcpStream::connect(addr).await.unwrap();
+            let id = tokio::time::timeout(Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap()
+                .expect("no ID in handler");
+            ids.push(id);
+        }
+        task.abort();
+        assert_ne!(ids[0], ids[1]);
+
+        #[cfg(feature = "tracing")]
+        {
+            let logs = capture.logs();
+            for id in ids {
+                let span = format!("connection{{id={id} peer=127.0.0.1:");
+                assert!(
+                    logs.lines()
+                        .any(|line| line.contains(&span) && line.contains("handling request")),
+                    "{logs}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_local_addr_reports_family() {
+        use tokio::io::AsyncReadExt;
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        for (ip, expected) in [
+            (IpAddr::V4(Ipv4Addr::LOCALHOST), b"4"),
+            (IpAddr::V6(Ipv6Addr::LOCALHOST), b"6"),
+        ] {
+            let state = Arc::new(ServiceState::new("FamilyTcp", Transport::Tcp));
+            let task = tokio::spawn(run_tcp(
+                Arc::new(FamilyTcp),
+                iface.clone(),
+                state.clone(),
+                BindMode::Specific(ip),
+            ));
+            let addr = bound_addr(&state).await;
+
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            let mut reply = Vec::new();
+            stream.read_to_end(&mut reply).await.unwrap();
+            assert_eq!(reply, expected, "{addr}");
+            task.abort();
+        }
+    }
+
+    struct PortTcp(u16);
+    #[async_trait::async_trait]
+    impl TcpHandler for PortTcp {
+        fn name(&self) -> &'static str {
+            "PortTcp"
+        }
+        fn port(&self) -> u16 {
+            self.0
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_addr_in_use_is_retried() {
+        let held = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = held.local_addr().unwrap().port();
+        assert!(matches!(
+            bind_tcp_listeners(
+                &[SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port)],
+                &SocketConfig::tcp(&PortTcp(port)),
+                1,
+            ),
+            Err(Error::AddrInUse(addr)) if addr.port() == port
+        ));
+
+        let iface = Arc::new(NetworkInterface::from_str("lo").unwrap());
+        let state = Arc::new(ServiceState::new("PortTcp", Transport::Tcp));
+        let task = tokio::spawn(run_tcp(
+            Arc::new(PortTcp(port)),
+            iface,
+            state.clone(),
+            BindMode::Specific(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+        ));
+
+        // Released while the service is still retrying, as a previous instance would.
+        tokio::time::sleep(ADDR_IN_USE_DELAY / 2).await;
+        assert!(!state.has_bound());
+        drop(held);
+
+        tokio::time::timeout(Duration::from_secs(2), async {
+            while !state.has_bound() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("bind was not retried");
+        assert!(!task.is_finished());
+        task.abort();
     }
 }
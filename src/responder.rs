@@ -0,0 +1,247 @@
+#[cfg(feature = "tracing")]
+use tracing::warn;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+use tokio::net::UdpSocket;
+
+use crate::{BindMode, PacketInfo, UdpHandler};
+
+/// A [`UdpHandler`] for request/response protocols built from a plain function.
+///
+/// The function receives each datagram and its sender and returns the reply to
+/// send back, or `None` to stay silent. Sending the reply and reporting send
+/// failures is handled by the adapter.
+///
+/// ```rust,no_run
+/// # use maestro_rs::{NetworkInterface, Responder, Supervisor};
+/// # use std::str::FromStr;
+/// # fn main() -> maestro_rs::Result<()> {
+/// let echo = Responder::new("echo", 7, |data: &[u8], _peer: &_| Some(data.to_vec()));
+/// let mut supervisor = Supervisor::new(NetworkInterface::from_str("lo")?);
+/// supervisor.add(echo);
+/// # Ok(())
+/// # }
+/// ```
+pub struct Responder<F> {
+    name: &'static str,
+    port: u16,
+    bind_mode: BindMode,
+    respond: F,
+}
+
+impl<F> fmt::Debug for Responder<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Responder")
+            .field("name", &self.name)
+            .field("port", &self.port)
+            .field("bind_mode", &self.bind_mode)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F> Responder<F>
+where
+    F: Fn(&[u8], &SocketAddr) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    /// Creates a responder serving `respond` on `port`.
+    pub fn new(name: &'static str, port: u16, respond: F) -> Self {
+        Self {
+            name,
+            port,
+            bind_mode: BindMode::PreferInterface,
+            respond,
+        }
+    }
+
+    /// Sets the binding strategy. Defaults to [`BindMode::PreferInterface`].
+    pub fn with_bind_mode(mut self, bind_mode: BindMode) -> Self {
+        self.bind_mode = bind_mode;
+        self
+    }
+}
+
+#[async_trait]
+impl<F> UdpHandler for Responder<F>
+where
+    F: Fn(&[u8], &SocketAddr) -> Option<Vec<u8>> + Send + Sync + 'static,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        self.bind_mode.clone()
+    }
+
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        _info: &PacketInfo,
+    ) {
+        let Some(reply) = (self.respond)(data, peer) else {
+            return;
+        };
+        if let Err(e) = socket.send_to(&reply, peer).await {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "UDP service `{}` failed to reply to {}: {}",
+                self.name, peer, e
+            );
+
+            #[cfg(not(feature = "tracing"))]
+            let _ = e;
+        }
+    }
+}
+
+/// A [`UdpHandler`] that answers every datagram with the same payload.
+///
+/// Meant for discovery beacons and announce-style services, usually paired with
+/// [`StaticResponder::with_multicast`].
+///
+/// ```rust,no_run
+/// # use maestro_rs::{NetworkInterface, StaticResponder, Supervisor};
+/// # use std::str::FromStr;
+/// # fn main() -> maestro_rs::Result<()> {
+/// let beacon = StaticResponder::new("beacon", 1900, &b"NOTIFY maestro"[..])
+///     .with_multicast(vec!["239.255.255.250".parse().unwrap()]);
+/// let mut supervisor = Supervisor::new(NetworkInterface::from_str("eth0")?);
+/// supervisor.add(beacon);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticResponder {
+    name: &'static str,
+    port: u16,
+    bind_mode: BindMode,
+    multicast: Vec<IpAddr>,
+    reply: Bytes,
+}
+
+impl StaticResponder {
+    /// Creates a responder answering with `reply` on `port`.
+    pub fn new(name: &'static str, port: u16, reply: impl Into<Bytes>) -> Self {
+        Self {
+            name,
+            port,
+            bind_mode: BindMode::PreferInterface,
+            multicast: Vec::new(),
+            reply: reply.into(),
+        }
+    }
+
+    /// Sets the binding strategy. Defaults to [`BindMode::PreferInterface`].
+    pub fn with_bind_mode(mut self, bind_mode: BindMode) -> Self {
+        self.bind_mode = bind_mode;
+        self
+    }
+
+    /// Sets the multicast groups to join. Defaults to none.
+    pub fn with_multicast(mut self, groups: Vec<IpAddr>) -> Self {
+        self.multicast = groups;
+        self
+    }
+}
+
+#[async_trait]
+impl UdpHandler for StaticResponder {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn port(&self) -> u16 {
+        self.port
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        self.bind_mode.clone()
+    }
+
+    fn multicast_addrs(&self) -> &[IpAddr] {
+        &self.multicast
+    }
+
+    async fn on_packet(
+        &self,
+        _data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        _info: &PacketInfo,
+    ) {
+        if let Err(e) = socket.send_to(&self.reply, peer).await {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "UDP service `{}` failed to reply to {}: {}",
+                self.name, peer, e
+            );
+
+            #[cfg(not(feature = "tracing"))]
+            let _ = e;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    #[tokio::test]
+    async fn test_reply_is_sent() {
+        let server = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let echo = Responder::new("echo", 0, |data: &[u8], _peer: &SocketAddr| {
+            (data != b"quiet").then(|| data.to_vec())
+        });
+
+        let peer = client.local_addr().unwrap();
+        echo.on_packet(b"quiet", server.clone(), &peer, &PacketInfo::new(5, addr))
+            .await;
+        echo.on_packet(b"ping", server, &peer, &PacketInfo::new(4, addr))
+            .await;
+
+        let mut buf = [0u8; 16];
+        let (n, from) = timeout(Duration::from_secs(1), client.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        assert_eq!(from, addr);
+    }
+
+    #[tokio::test]
+    async fn test_static_reply_is_sent() {
+        let server = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let beacon = StaticResponder::new("beacon", 0, &b"HERE"[..]);
+
+        let peer = client.local_addr().unwrap();
+        beacon
+            .on_packet(b"WHO", server, &peer, &PacketInfo::new(3, addr))
+            .await;
+
+        let mut buf = [0u8; 16];
+        let (n, from) = timeout(Duration::from_secs(1), client.recv_from(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"HERE");
+        assert_eq!(from, addr);
+    }
+}
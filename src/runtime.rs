@@ -0,0 +1,165 @@
+use std::sync::Arc;
+
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    NetworkInterface, Result, TcpHandler, UdpHandler,
+    network::{run_tcp, run_udp},
+    state::{ServiceState, Transport},
+};
+
+/// Runs a single TCP service without a [`Supervisor`](crate::Supervisor).
+///
+/// There are no restarts: [`serve`](Self::serve) returns as soon as the service
+/// fails, which lets callers plug in their own supervision.
+#[derive(Debug)]
+pub struct TcpRuntime<H> {
+    handler: Arc<H>,
+    iface: Arc<NetworkInterface>,
+    state: Arc<ServiceState>,
+}
+
+impl<H: TcpHandler> TcpRuntime<H> {
+    /// Creates a runtime serving `handler` on `iface`.
+    pub fn new(handler: H, iface: NetworkInterface) -> Self {
+        let state = ServiceState::new(handler.name(), Transport::Tcp);
+        Self {
+            handler: Arc::new(handler),
+            iface: Arc::new(iface),
+            state: Arc::new(state),
+        }
+    }
+
+    /// Returns the addresses the service is currently listening on.
+    pub fn listening_addrs(&self) -> Vec<std::net::SocketAddr> {
+        self.state.bound_addrs()
+    }
+
+    /// Binds and serves until `token` is cancelled or the service fails.
+    ///
+    /// Returns `Ok(())` on cancellation.
+    pub async fn serve(&self, token: CancellationToken) -> Result<()> {
+        let mode = self.handler.bind_mode();
+        let service = run_tcp(
+            self.handler.clone(),
+            self.iface.clone(),
+            self.state.clone(),
+            mode,
+        );
+        tokio::select! {
+            res = service => res,
+            _ = token.cancelled() => Ok(()),
+        }
+    }
+}
+
+/// Runs a single UDP service without a [`Supervisor`](crate::Supervisor).
+///
+/// There are no restarts: [`serve`](Self::serve) returns as soon as the service
+/// fails, which lets callers plug in their own supervision.
+#[derive(Debug)]
+pub struct UdpRuntime<H> {
+    handler: Arc<H>,
+    iface: Arc<NetworkInterface>,
+    state: Arc<ServiceState>,
+}
+
+impl<H: UdpHandler> UdpRuntime<H> {
+    /// Creates a runtime serving `handler` on `iface`.
+    pub fn new(handler: H, iface: NetworkInterface) -> Self {
+        let state = ServiceState::new(handler.name(), Transport::Udp);
+        Self {
+            handler: Arc::new(handler),
+            iface: Arc::new(iface),
+            state: Arc::new(state),
+        }
+    }
+
+    /// Returns the addresses the service is currently bound to.
+    pub fn listening_addrs(&self) -> Vec<std::net::SocketAddr> {
+        self.state.bound_addrs()
+    }
+
+    /// Binds and serves until `token` is cancelled or the service fails.
+    ///
+    /// Returns `Ok(())` on cancellation.
+    pub async fn serve(&self, token: CancellationToken) -> Result<()> {
+        let mode = self.handler.bind_mode();
+        let service = run_udp(
+            self.handler.clone(),
+            self.iface.clone(),
+            self.state.clone(),
+            mode,
+        );
+        tokio::select! {
+            res = service => res,
+            _ = token.cancelled() => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BindMode, async_trait};
+    use std::{net::SocketAddr, str::FromStr, time::Duration};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        time::{sleep, timeout},
+    };
+
+    struct HelloTcp;
+    #[async_trait]
+    impl TcpHandler for HelloTcp {
+        fn name(&self) -> &'static str {
+            "HelloTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let _ = s.write_all(b"hello").await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_standalone_tcp_runtime() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let runtime = Arc::new(TcpRuntime::new(HelloTcp, iface));
+        let token = CancellationToken::new();
+        let serve = tokio::spawn({
+            let runtime = runtime.clone();
+            let token = token.clone();
+            async move { runtime.serve(token).await }
+        });
+
+        let addr = loop {
+            if let Some(addr) = runtime.listening_addrs().first() {
+                break *addr;
+            }
+            sleep(Duration::from_millis(10)).await;
+        };
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello");
+
+        token.cancel();
+        timeout(Duration::from_secs(1), serve)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(runtime.listening_addrs().is_empty());
+    }
+}
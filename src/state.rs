@@ -0,0 +1,481 @@
+use std::{
+    any::Any,
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    sync::{
+        Arc, Mutex, MutexGuard, OnceLock, PoisonError, RwLock,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use tokio::{
+    sync::{Notify, broadcast, watch},
+    time::{Instant, sleep, sleep_until},
+};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFutureOwned};
+
+use crate::{ConnectionBudget, Isolation, SupervisionEvent, supervisor::emit};
+
+/// Transport protocol served by a service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    Tcp,
+    Udp,
+    /// Not a network service: a background task added with
+    /// [`Supervisor::add_task`](crate::Supervisor::add_task).
+    Task,
+}
+
+/// Health of a supervised service or task, for health endpoints and dashboards.
+///
+/// Read it with [`SupervisorHandle::snapshot`](crate::SupervisorHandle::snapshot);
+/// every change is also sent as [`SupervisionEvent::HealthChanged`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WorkerHealth {
+    /// An instance is starting and has not bound yet.
+    Starting,
+    /// The instance is bound and serving.
+    Healthy,
+    /// The instance serves with fewer sockets than it bound.
+    Degraded,
+    /// The instance failed and a new one is on its way.
+    Restarting,
+    /// The service exhausted its restart attempts and will not run again.
+    Dead,
+}
+
+/// Live state of a registered service, shared between its run loop and the
+/// [`SupervisorHandle`](crate::SupervisorHandle).
+#[derive(Debug)]
+pub struct ServiceState {
+    name: Arc<str>,
+    transport: Transport,
+    phase: u8,
+    labels: HashMap<String, String>,
+    isolation: Isolation,
+    bound: Mutex<Vec<SocketAddr>>,
+    has_bound: AtomicBool,
+    /// When an instance last bound, kept after it stops.
+    bound_at: Mutex<Option<Instant>>,
+    dead: AtomicBool,
+    health: watch::Sender<WorkerHealth>,
+    /// Channel of [`Supervisor::events`](crate::Supervisor::events), once supervised.
+    events: OnceLock<broadcast::Sender<SupervisionEvent>>,
+    in_flight: AtomicUsize,
+    shed: AtomicU64,
+    oversized: AtomicU64,
+    settled: Notify,
+    paused: watch::Sender<bool>,
+    active: watch::Sender<bool>,
+    /// Parent of the tokens handed to each connection.
+    connections: CancellationToken,
+    /// Cancelled to stop every connection handler at once.
+    aborted: CancellationToken,
+    idle: Notify,
+    connection_budget: OnceLock<Arc<ConnectionBudget>>,
+    /// The `RwLock<Arc<H>>` holding the handler serving new work, if swappable.
+    handler: Option<Box<dyn Any + Send + Sync>>,
+    liveness_timeout: Option<Duration>,
+    /// Milliseconds between `epoch` and the last heartbeat of the run loop.
+    heartbeat: AtomicU64,
+    epoch: Instant,
+}
+
+impl ServiceState {
+    /// Creates the state for a service with the given display name.
+    pub fn new(name: impl Into<Arc<str>>, transport: Transport) -> Self {
+        Self {
+            name: name.into(),
+            transport,
+            phase: 0,
+            labels: HashMap::new(),
+            isolation: Isolation::Shared,
+            bound: Mutex::new(Vec::new()),
+            has_bound: AtomicBool::new(false),
+            bound_at: Mutex::new(None),
+            dead: AtomicBool::new(false),
+            health: watch::Sender::new(WorkerHealth::Starting),
+            events: OnceLock::new(),
+            in_flight: AtomicUsize::new(0),
+            shed: AtomicU64::new(0),
+            oversized: AtomicU64::new(0),
+            settled: Notify::new(),
+            paused: watch::Sender::new(false),
+            active: watch::Sender::new(true),
+            connections: CancellationToken::new(),
+            aborted: CancellationToken::new(),
+            idle: Notify::new(),
+            connection_budget: OnceLock::new(),
+            handler: None,
+            liveness_timeout: None,
+            heartbeat: AtomicU64::new(0),
+            epoch: Instant::now(),
+        }
+    }
+
+    /// Sets the startup phase of the service.
+    pub fn with_phase(mut self, phase: u8) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Sets the labels used to group the service in metrics and logs.
+    pub fn with_labels(mut self, labels: HashMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets the runtime the service runs on.
+    pub fn with_isolation(mut self, isolation: Isolation) -> Self {
+        self.isolation = isolation;
+        self
+    }
+
+    /// Makes `handler` the handler of the service, replaceable with
+    /// [`replace_handler`](Self::replace_handler).
+    pub fn with_handler<H: Send + Sync + 'static>(mut self, handler: Arc<H>) -> Self {
+        self.handler = Some(Box::new(RwLock::new(handler)));
+        self
+    }
+
+    /// Returns the handler that new connections or datagrams go to: the one set
+    /// last, or `fallback` if the service has no replaceable handler of type `H`.
+    pub fn current_handler<H: Send + Sync + 'static>(&self, fallback: &Arc<H>) -> Arc<H> {
+        match self.handler_slot::<H>() {
+            Some(slot) => slot.read().unwrap_or_else(PoisonError::into_inner).clone(),
+            None => fallback.clone(),
+        }
+    }
+
+    /// Replaces the handler of the service. Work already in progress finishes
+    /// with the previous handler.
+    ///
+    /// Returns `false` if the service has no replaceable handler of type `H`.
+    pub fn replace_handler<H: Send + Sync + 'static>(&self, handler: H) -> bool {
+        let Some(slot) = self.handler_slot::<H>() else {
+            return false;
+        };
+        *slot.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(handler);
+        true
+    }
+
+    fn handler_slot<H: Send + Sync + 'static>(&self) -> Option<&RwLock<Arc<H>>> {
+        self.handler.as_ref()?.downcast_ref()
+    }
+
+    /// Sets how long the run loop may go without a heartbeat before the service
+    /// is considered hung.
+    pub fn with_liveness_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.liveness_timeout = timeout;
+        self
+    }
+
+    /// Records that the run loop is making progress.
+    pub fn heartbeat(&self) {
+        let elapsed = self.epoch.elapsed().as_millis();
+        self.heartbeat.store(
+            u64::try_from(elapsed).unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    fn last_heartbeat(&self) -> Instant {
+        self.epoch + Duration::from_millis(self.heartbeat.load(Ordering::Relaxed))
+    }
+
+    /// Awaits `idle`, a wait for outside work such as the next connection, while
+    /// heartbeating: a run loop waiting for work is idle, not hung.
+    pub async fn idle<F: Future>(&self, idle: F) -> F::Output {
+        let Some(timeout) = self.liveness_timeout else {
+            return idle.await;
+        };
+        let mut idle = std::pin::pin!(idle);
+        loop {
+            self.heartbeat();
+            tokio::select! {
+                out = &mut idle => {
+                    self.heartbeat();
+                    return out;
+                }
+                _ = sleep(timeout / 2) => {}
+            }
+        }
+    }
+
+    /// Resolves once the run loop has gone without a heartbeat for the liveness
+    /// timeout, and returns that timeout. Never resolves without a timeout.
+    ///
+    /// The wait starts with a fresh heartbeat, so each instance gets a full timeout.
+    pub async fn wait_unresponsive(&self) -> Duration {
+        let Some(timeout) = self.liveness_timeout else {
+            return std::future::pending().await;
+        };
+        self.heartbeat();
+        loop {
+            let deadline = self.last_heartbeat() + timeout;
+            if Instant::now() >= deadline {
+                return timeout;
+            }
+            sleep_until(deadline).await;
+        }
+    }
+
+    /// Returns the labels of the service.
+    pub fn labels(&self) -> &HashMap<String, String> {
+        &self.labels
+    }
+
+    /// Returns the runtime the service runs on.
+    pub fn isolation(&self) -> Isolation {
+        self.isolation
+    }
+
+    /// Returns the startup phase of the service.
+    pub fn phase(&self) -> u8 {
+        self.phase
+    }
+
+    /// Returns the display name of the service.
+    pub fn name(&self) -> &Arc<str> {
+        &self.name
+    }
+
+    /// Returns the transport protocol of the service.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns the addresses the service is currently bound to.
+    pub fn bound_addrs(&self) -> Vec<SocketAddr> {
+        lock(&self.bound).clone()
+    }
+
+    /// Returns whether any instance of the service has bound successfully.
+    pub fn has_bound(&self) -> bool {
+        self.has_bound.load(Ordering::Relaxed)
+    }
+
+    /// Returns when an instance of the service last bound, if one ever did.
+    pub fn bound_at(&self) -> Option<Instant> {
+        *lock(&self.bound_at)
+    }
+
+    /// Returns whether the service gave up after exhausting its restart attempts.
+    pub fn is_dead(&self) -> bool {
+        self.dead.load(Ordering::Relaxed)
+    }
+
+    /// Marks the service as dead: it will not be restarted again.
+    pub fn mark_dead(&self) {
+        self.dead.store(true, Ordering::Relaxed);
+        self.set_health(WorkerHealth::Dead);
+        self.settled.notify_waiters();
+    }
+
+    /// Returns the current health of the service.
+    pub fn health(&self) -> WorkerHealth {
+        *self.health.borrow()
+    }
+
+    /// Moves the service to `health`, sending
+    /// [`SupervisionEvent::HealthChanged`] if that changes it.
+    pub fn set_health(&self, health: WorkerHealth) {
+        if self.health.send_replace(health) != health {
+            emit(self.events.get(), || SupervisionEvent::HealthChanged {
+                service: self.name.to_string(),
+                health,
+            });
+        }
+    }
+
+    /// Sends health changes to the supervisor's event channel. Only the first
+    /// channel set is kept.
+    pub fn set_events(&self, events: broadcast::Sender<SupervisionEvent>) {
+        let _ = self.events.set(events);
+    }
+
+    /// Waits until the service has bound once, is dead, or is inactive.
+    pub async fn wait_settled(&self) {
+        loop {
+            let settled = self.settled.notified();
+            if self.has_bound() || self.is_dead() || !self.is_active() {
+                return;
+            }
+            settled.await;
+        }
+    }
+
+    /// Sets whether the service starts right away, or waits for
+    /// [`activate`](Self::activate). Services are active by default.
+    pub fn with_active(self, active: bool) -> Self {
+        self.active.send_replace(active);
+        self
+    }
+
+    /// Lets an inactive service bind and start.
+    pub fn activate(&self) {
+        self.active.send_replace(true);
+    }
+
+    /// Returns whether the service has been activated.
+    pub fn is_active(&self) -> bool {
+        *self.active.borrow()
+    }
+
+    /// Waits until the service is active.
+    pub async fn wait_active(&self) {
+        let _ = self.active.subscribe().wait_for(|active| *active).await;
+    }
+
+    /// Stops the service from taking new work until [`resume`](Self::resume) is called.
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+    }
+
+    /// Lets a paused service take new work again.
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+    }
+
+    /// Returns whether the service is paused.
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Waits until the service is not paused.
+    pub async fn wait_resumed(&self) {
+        if self.is_paused() {
+            let _ = self.paused.subscribe().wait_for(|paused| !paused).await;
+        }
+    }
+
+    /// Waits until the service is paused.
+    pub async fn wait_paused(&self) {
+        let _ = self.paused.subscribe().wait_for(|paused| *paused).await;
+    }
+
+    /// Returns a token for a new connection, cancelled by
+    /// [`close_connections`](Self::close_connections) or by cancelling it directly.
+    pub fn connection_token(&self) -> CancellationToken {
+        self.connections.child_token()
+    }
+
+    /// Asks every open connection to wind down, by cancelling their tokens.
+    pub fn close_connections(&self) {
+        self.connections.cancel();
+    }
+
+    /// Stops every connection handler at its next suspension point, whether or
+    /// not it watches its token.
+    pub fn abort_connections(&self) {
+        self.aborted.cancel();
+    }
+
+    /// Resolves once [`abort_connections`](Self::abort_connections) is called.
+    pub fn connections_aborted(&self) -> WaitForCancellationFutureOwned {
+        self.aborted.clone().cancelled_owned()
+    }
+
+    /// Waits until no connection or datagram is being handled.
+    pub async fn wait_idle(&self) {
+        loop {
+            let idle = self.idle.notified();
+            if self.in_flight() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+
+    /// Shares a supervisor-wide connection budget with this service. Only the
+    /// first budget set is kept.
+    pub fn set_connection_budget(&self, budget: Arc<ConnectionBudget>) {
+        let _ = self.connection_budget.set(budget);
+    }
+
+    /// Returns the connection budget shared with other services, if any.
+    pub fn connection_budget(&self) -> Option<&Arc<ConnectionBudget>> {
+        self.connection_budget.get()
+    }
+
+    /// Returns how many connections or datagrams are being handled.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many connections or datagrams were rejected by load shedding.
+    pub fn shed(&self) -> u64 {
+        self.shed.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many datagrams were dropped for exceeding the maximum datagram size.
+    pub fn oversized(&self) -> u64 {
+        self.oversized.load(Ordering::Relaxed)
+    }
+
+    /// Counts a datagram dropped for exceeding the maximum datagram size.
+    pub fn record_oversized(&self) {
+        self.oversized.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts one connection or datagram as in flight until the returned guard is dropped.
+    ///
+    /// Returns `None`, and counts the work as shed, when `max` units are already
+    /// in flight.
+    pub fn enter(self: &Arc<Self>, max: Option<usize>) -> Option<InFlightGuard> {
+        let previous = self.in_flight.fetch_add(1, Ordering::Relaxed);
+        if max.is_some_and(|max| previous >= max) {
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            self.shed.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(InFlightGuard {
+            state: self.clone(),
+        })
+    }
+
+    /// Records the addresses of a freshly bound instance.
+    ///
+    /// They are cleared again when the returned guard is dropped, i.e. when the
+    /// instance stops and its sockets are closed.
+    pub fn track_bound(self: &Arc<Self>, addrs: Vec<SocketAddr>) -> BoundGuard {
+        *lock(&self.bound) = addrs;
+        *lock(&self.bound_at) = Some(Instant::now());
+        self.has_bound.store(true, Ordering::Relaxed);
+        self.set_health(WorkerHealth::Healthy);
+        self.settled.notify_waiters();
+        BoundGuard {
+            state: self.clone(),
+        }
+    }
+}
+
+/// Clears a service's bound addresses when dropped.
+pub struct BoundGuard {
+    state: Arc<ServiceState>,
+}
+
+impl Drop for BoundGuard {
+    fn drop(&mut self) {
+        lock(&self.state.bound).clear();
+    }
+}
+
+/// Counts a unit of work as in flight for as long as it lives.
+pub struct InFlightGuard {
+    state: Arc<ServiceState>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        if self.state.in_flight.fetch_sub(1, Ordering::Relaxed) == 1 {
+            self.state.idle.notify_waiters();
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
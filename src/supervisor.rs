@@ -1,37 +1,125 @@
 #[cfg(feature = "tracing")]
-use tracing::{error, info, warn};
+use tracing::{Instrument, error, info, info_span, warn};
 
-use std::{future::Future, pin::Pin, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        Arc, PoisonError, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
 use tokio::{
-    task::JoinSet,
-    time::{sleep, timeout},
+    runtime,
+    sync::{Mutex, broadcast, oneshot},
+    task::{Id, JoinSet},
+    time::{Instant, MissedTickBehavior, interval, sleep, timeout},
 };
 use tokio_util::sync::CancellationToken;
 
-use crate::network::NetworkInterface;
-use crate::{Result, handler::Service};
+use crate::network::{BindMode, NetworkInterface, PreBoundSocket};
+use crate::{
+    Error, Result, Tcp, TcpHandler, Udp, UdpHandler,
+    handler::{Service, ServiceOptions},
+    network::{accept_queue_depth, wait_for_interface},
+    state::{ServiceState, Transport, WorkerHealth},
+};
 
 /// Defines how a service should be restarted upon failure.
+///
+/// With the `serde` feature, it can be read from configuration files. The delay
+/// is given in seconds and missing fields take their default value, e.g.
+/// `{ max_attempts = 3, base_delay = 0.5 }` in TOML.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct RestartPolicy {
     /// Maximum number of restart attempts. `None` means infinite.
     pub max_attempts: Option<usize>,
     /// Initial delay before the first restart.
+    #[cfg_attr(feature = "serde", serde(with = "secs"))]
     pub base_delay: Duration,
+    /// How long an instance must serve before failing for the attempt counter (and
+    /// the backoff) to start over. `None` means attempts are never forgotten.
+    ///
+    /// Serving starts once the instance is bound, so bind retries and multicast
+    /// joins do not count; tasks, which never bind, serve from their start.
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "opt_secs", skip_serializing_if = "Option::is_none")
+    )]
+    pub min_healthy_duration: Option<Duration>,
+    /// Shortest time between two starts of a service, whatever the backoff says.
+    /// Keeps a crash loop with a zero `base_delay` from spinning.
+    #[cfg_attr(feature = "serde", serde(with = "secs"))]
+    pub min_restart_interval: Duration,
+}
+
+/// (De)serializes a [`Duration`] as a number of seconds, such as `1.5`.
+#[cfg(feature = "serde")]
+mod secs {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let secs = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(secs).map_err(D::Error::custom)
+    }
+}
+
+/// Like [`secs`], for an optional [`Duration`].
+#[cfg(feature = "serde")]
+mod opt_secs {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::secs::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?
+            .map(|secs| Duration::try_from_secs_f64(secs).map_err(D::Error::custom))
+            .transpose()
+    }
 }
 
+/// Default [`RestartPolicy::min_restart_interval`]: at most 100 starts per second.
+const DEFAULT_MIN_RESTART_INTERVAL: Duration = Duration::from_millis(10);
+
 impl Default for RestartPolicy {
     fn default() -> Self {
         Self {
             max_attempts: Some(5),
             base_delay: Duration::from_secs(1),
+            min_healthy_duration: None,
+            min_restart_interval: DEFAULT_MIN_RESTART_INTERVAL,
         }
     }
 }
 
 impl RestartPolicy {
-    /// Calculates the delay for a specific attempt using exponential backoff.
-    fn delay(&self, attempt: usize) -> Duration {
+    /// Calculates the delay before retrying after `attempt` failures.
+    ///
+    /// The delay doubles with every attempt, starting at `base_delay`, and is
+    /// capped at 60 seconds.
+    pub fn delay_for_attempt(&self, attempt: usize) -> Duration {
         let factor = 2u32.saturating_pow(attempt.saturating_sub(1) as u32);
         (self.base_delay * factor).min(Duration::from_secs(60))
     }
@@ -47,8 +135,119 @@ impl RestartPolicy {
         self.base_delay = delay;
         self
     }
+
+    /// Forgets past attempts when an instance fails after serving for at least
+    /// `duration`, counted from when it bound.
+    ///
+    /// Only an instance that stayed up that long counts as healthy: one that binds
+    /// and then crashes right away keeps using up its attempts, so a flapping
+    /// service is still abandoned after [`max_attempts`](Self::max_attempts).
+    pub fn with_min_healthy_duration(mut self, duration: Duration) -> Self {
+        self.min_healthy_duration = Some(duration);
+        self
+    }
+
+    /// Sets the shortest time between two starts of a service. Defaults to 10ms.
+    ///
+    /// It applies on top of the backoff, so that even a zero delay with unlimited
+    /// attempts restarts a crashing service at a bounded rate.
+    pub fn with_min_restart_interval(mut self, interval: Duration) -> Self {
+        self.min_restart_interval = interval;
+        self
+    }
+}
+
+/// When a task added with [`Supervisor::add_interval`] runs.
+#[derive(Copy, Clone, Debug)]
+pub struct Schedule {
+    /// Time between two runs.
+    pub period: Duration,
+    /// Whether a failed run is logged and the next one awaited, instead of
+    /// restarting the worker.
+    pub skip_errors: bool,
+    /// What to do with the runs missed while a run (or the whole process) stalled.
+    pub missed_tick: MissedTickBehavior,
+}
+
+impl Schedule {
+    /// Runs the task every `period`. Errors restart the worker and missed runs are
+    /// skipped ([`MissedTickBehavior::Skip`]).
+    pub fn every(period: Duration) -> Self {
+        Self {
+            period,
+            skip_errors: false,
+            missed_tick: MissedTickBehavior::Skip,
+        }
+    }
+
+    /// Sets how missed runs are caught up.
+    ///
+    /// `Burst` runs them back to back, `Delay` shifts every later run, and `Skip`
+    /// runs once and then waits for the next multiple of the period. Bursting is
+    /// rarely wanted for health checks, hence the `Skip` default.
+    pub fn with_missed_tick_behavior(mut self, behavior: MissedTickBehavior) -> Self {
+        self.missed_tick = behavior;
+        self
+    }
+
+    /// Sets whether failed runs are skipped rather than restarting the worker.
+    pub fn with_skip_errors(mut self, skip_errors: bool) -> Self {
+        self.skip_errors = skip_errors;
+        self
+    }
+}
+
+impl From<Duration> for Schedule {
+    fn from(period: Duration) -> Self {
+        Self::every(period)
+    }
+}
+
+/// Which runtime a service or task runs on.
+///
+/// Isolated services get a Tokio runtime of their own, running on dedicated
+/// threads, so that one that starves its executor (blocking calls, long CPU-bound
+/// polls) does not delay the others. Their connection handlers and any task they
+/// spawn run on that runtime too. The supervisor still restarts and shuts them
+/// down like the others, but cannot stop a future stuck without yielding.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Isolation {
+    /// Runs on the runtime of the supervisor.
+    #[default]
+    Shared,
+    /// Runs on a dedicated single-threaded runtime.
+    CurrentThread,
+    /// Runs on a dedicated multi-threaded runtime with `worker_threads` threads
+    /// (at least one).
+    MultiThread { worker_threads: usize },
+}
+
+impl Isolation {
+    /// Returns a builder for the dedicated runtime, or `None` for [`Isolation::Shared`].
+    fn builder(self) -> Option<runtime::Builder> {
+        let mut builder = match self {
+            Self::Shared => return None,
+            Self::CurrentThread => runtime::Builder::new_current_thread(),
+            Self::MultiThread { worker_threads } => {
+                let mut builder = runtime::Builder::new_multi_thread();
+                builder.worker_threads(worker_threads.max(1));
+                builder
+            }
+        };
+        builder.enable_all();
+        Some(builder)
+    }
 }
 
+/// Default time granted to services to stop after a shutdown is requested.
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Default time connections get to wind down once the grace period has expired.
+const DEFAULT_KILL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Number of supervision events kept for subscribers that fall behind.
+const EVENT_CAPACITY: usize = 256;
+
 /// The supervisor orchestrates the lifecycle of multiple services.
 ///
 /// It handles startup, graceful shutdown, and automatic restarts based on the
@@ -56,17 +255,23 @@ impl RestartPolicy {
 pub struct Supervisor {
     iface: Arc<NetworkInterface>,
     policy: RestartPolicy,
+    grace_period: Duration,
+    kill_timeout: Duration,
+    budget: Option<Arc<RestartBudget>>,
+    connections: Option<Arc<ConnectionBudget>>,
+    on_force_shutdown: Option<ForceShutdownHook>,
+    control: SupervisorHandle,
+    events: broadcast::Sender<SupervisionEvent>,
     tasks: Vec<Box<dyn Task>>,
 }
 
+/// A callback receiving the names of the services about to be aborted.
+type ForceShutdownHook = Box<dyn Fn(&[String]) + Send + Sync>;
+
 impl Supervisor {
     /// Creates a new supervisor bound to the specified network interface.
     pub fn new(iface: NetworkInterface) -> Self {
-        Self {
-            iface: Arc::new(iface),
-            policy: RestartPolicy::default(),
-            tasks: Vec::new(),
-        }
+        Self::with_policy(iface, RestartPolicy::default())
     }
 
     /// Creates a new supervisor using a custom [`RestartPolicy`].
@@ -74,10 +279,96 @@ impl Supervisor {
         Self {
             iface: Arc::new(network_interface),
             policy: restart_policy,
+            grace_period: DEFAULT_GRACE_PERIOD,
+            kill_timeout: DEFAULT_KILL_TIMEOUT,
+            budget: None,
+            connections: None,
+            on_force_shutdown: None,
+            control: SupervisorHandle::new(),
+            events: broadcast::Sender::new(EVENT_CAPACITY),
             tasks: Vec::new(),
         }
     }
 
+    /// Sets how long services are given to stop gracefully before being aborted.
+    ///
+    /// Services stop accepting right away, then wait for their open connections
+    /// to end. Defaults to 5 seconds.
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.grace_period = grace_period;
+        self
+    }
+
+    /// Sets how long connections still open when the grace period expires are
+    /// given to react to their cancelled token before being aborted.
+    ///
+    /// Like systemd's `TimeoutStopSec` followed by `SIGKILL`, shutdown escalates in
+    /// two stages: the token passed to
+    /// [`on_connection`](crate::TcpHandler::on_connection) is cancelled once the
+    /// grace period expires, and whatever is still running after `kill_timeout`
+    /// is aborted. Defaults to 1 second.
+    pub fn with_kill_timeout(mut self, kill_timeout: Duration) -> Self {
+        self.kill_timeout = kill_timeout;
+        self
+    }
+
+    /// Limits restarts across all services to at most `max` within any `per` window.
+    ///
+    /// Per-service backoff still applies; once the shared budget is exhausted,
+    /// pending restarts wait until it refills. This prevents a correlated failure
+    /// from making every service restart at once.
+    pub fn with_restart_budget(mut self, max: usize, per: Duration) -> Self {
+        self.budget = Some(Arc::new(RestartBudget::new(max, per)));
+        self
+    }
+
+    /// Caps the connections open at once across all TCP services.
+    ///
+    /// Keep a clone of `budget` to observe how many connections are open and how
+    /// many were rejected. [`ConnectionBudget::from_fd_limit`] derives the cap
+    /// from the process file descriptor limit.
+    pub fn with_connection_budget(mut self, budget: Arc<ConnectionBudget>) -> Self {
+        self.connections = Some(budget);
+        self
+    }
+
+    /// Sets a callback invoked when the grace period and the
+    /// [kill timeout](Self::with_kill_timeout) have expired, right before the
+    /// services that are still running are aborted.
+    ///
+    /// It receives the names of those services, which makes it the place to log
+    /// or count which services hung during shutdown.
+    pub fn on_force_shutdown<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&[String]) + Send + Sync + 'static,
+    {
+        self.on_force_shutdown = Some(Box::new(hook));
+        self
+    }
+
+    /// Returns a handle that can be used to control the supervisor while it runs.
+    pub fn handle(&self) -> SupervisorHandle {
+        self.control.clone()
+    }
+
+    /// Subscribes to the lifecycle events of the services, as they happen.
+    ///
+    /// Each call returns a new receiver that gets the events sent after it was
+    /// created. A receiver that falls too far behind does not slow the supervisor
+    /// down: it misses the oldest events and gets [`broadcast::error::RecvError::Lagged`].
+    pub fn events(&self) -> broadcast::Receiver<SupervisionEvent> {
+        self.events.subscribe()
+    }
+
+    /// Returns a future that resolves once every service added so far has bound.
+    ///
+    /// The future does not borrow the supervisor, so it can be awaited while
+    /// [`run`](Self::run) is in progress. See [`SupervisorHandle::ready`].
+    pub fn ready(&self) -> impl Future<Output = Result<()>> + Send + 'static {
+        let control = self.control.clone();
+        async move { control.ready().await }
+    }
+
     /// Adds a service (TCP or UDP) to the supervisor.
     ///
     /// The service will be converted into a supervised task governed by the
@@ -86,125 +377,1142 @@ impl Supervisor {
     where
         S: Service<K>,
     {
-        let task = service.into_task(self.iface.clone(), self.policy);
+        self.add_with_options(service, ServiceOptions::default());
+    }
+
+    /// Adds one handler serving both TCP and UDP, as DNS does.
+    ///
+    /// The handler is shared by two services named `<name>/tcp` and `<name>/udp`,
+    /// so state kept in it is common to both transports. Each side binds, restarts
+    /// and reports independently, and [`on_started`](crate::TcpHandler::on_started)
+    /// and [`on_shutdown`](crate::TcpHandler::on_shutdown) run once per side.
+    pub fn add_dual<S>(&mut self, service: S)
+    where
+        S: TcpHandler + UdpHandler,
+    {
+        let shared = Arc::new(service);
+        let name = TcpHandler::name(&*shared);
+        self.add_named::<Tcp, _>(format!("{name}/tcp"), shared.clone());
+        self.add_named::<Udp, _>(format!("{name}/udp"), shared);
+    }
+
+    /// Adds a service under a custom display name.
+    ///
+    /// The name overrides the handler's own [`name`](crate::TcpHandler::name) in
+    /// logs, which allows several instances of the same handler type to be told apart.
+    pub fn add_named<K, S>(&mut self, name: impl Into<String>, service: S)
+    where
+        S: Service<K>,
+    {
+        let options = ServiceOptions {
+            name: Some(name.into()),
+            ..Default::default()
+        };
+        self.add_with_options(service, options);
+    }
+
+    /// Adds a service that serves on a socket bound by the caller.
+    ///
+    /// The handler's own [`port`](crate::TcpHandler::port) and bind mode are ignored.
+    /// See [`PreBoundSocket`] for ownership semantics.
+    pub fn add_prebound<K, S>(&mut self, service: S, socket: impl Into<PreBoundSocket>)
+    where
+        S: Service<K>,
+    {
+        let options = ServiceOptions {
+            bind_mode: Some(BindMode::PreBound(socket.into())),
+            ..Default::default()
+        };
+        self.add_with_options(service, options);
+    }
+
+    /// Adds a service with labels (e.g. `tier=edge`, `team=net`).
+    ///
+    /// Labels are reported in [`SupervisorHandle::snapshot`] and attached to the
+    /// service's lifecycle logs, so that dashboards can aggregate services along
+    /// arbitrary dimensions.
+    pub fn add_with_labels<K, S>(&mut self, service: S, labels: HashMap<String, String>)
+    where
+        S: Service<K>,
+    {
+        let options = ServiceOptions {
+            labels,
+            ..Default::default()
+        };
+        self.add_with_options(service, options);
+    }
+
+    /// Adds a service to a startup phase.
+    ///
+    /// Phases start in ascending order: services of a phase are started only once
+    /// every service of the earlier phases has bound its socket (or given up).
+    /// Services added without a phase are in phase `0`. Use this when a service
+    /// consumes something another one sets up at boot.
+    pub fn add_in_phase<K, S>(&mut self, phase: u8, service: S)
+    where
+        S: Service<K>,
+    {
+        let options = ServiceOptions {
+            phase,
+            ..Default::default()
+        };
+        self.add_with_options(service, options);
+    }
+
+    /// Adds a service that is not bound until it is activated with
+    /// [`SupervisorHandle::activate`].
+    ///
+    /// Use it for endpoints behind a feature flag, or secondary ports that may
+    /// never see traffic, so that they hold no socket until turned on. Until then
+    /// the service does not hold back later startup phases nor
+    /// [`ready`](Self::ready).
+    pub fn add_inactive<K, S>(&mut self, service: S)
+    where
+        S: Service<K>,
+    {
+        let options = ServiceOptions {
+            inactive: true,
+            ..Default::default()
+        };
+        self.add_with_options(service, options);
+    }
+
+    /// Adds a service running on a runtime of its own, see [`Isolation`].
+    pub fn add_isolated<K, S>(&mut self, service: S, isolation: Isolation)
+    where
+        S: Service<K>,
+    {
+        let options = ServiceOptions {
+            isolation,
+            ..Default::default()
+        };
+        self.add_with_options(service, options);
+    }
+
+    /// Adds one service per interface of the system, built by `factory`.
+    ///
+    /// Only interfaces that are up and not loopback are used. Each service binds
+    /// the addresses of its own interface (see [`BindMode::PreferInterface`]) and is
+    /// labelled with `interface=<name>`. Returns how many services were added.
+    ///
+    /// Give the services distinct names if they need to be addressed through a
+    /// [`SupervisorHandle`].
+    ///
+    /// # Errors
+    /// Returns an error if the interfaces cannot be listed.
+    pub fn add_on_all_interfaces<K, S, F>(&mut self, factory: F) -> Result<usize>
+    where
+        S: Service<K>,
+        F: FnMut(&NetworkInterface) -> S,
+    {
+        let ifaces = NetworkInterface::list()?;
+        Ok(self.add_on_interfaces(ifaces, factory))
+    }
+
+    /// Adds a background task that is not a network service, such as a migration
+    /// or a cache warmup.
+    ///
+    /// `task` is run once. It is run again, following the restart policy, only if it
+    /// returns an error, and it is cancelled with the services on shutdown. For
+    /// [`ready`](Self::ready) and startup phases it counts as up as soon as it starts.
+    pub fn add_task<F, Fut>(&mut self, name: impl Into<String>, task: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<()>> + Send + 'static,
+    {
+        self.add_isolated_task(name, Isolation::Shared, task);
+    }
+
+    /// Adds a background task like [`add_task`](Self::add_task), running on a
+    /// runtime of its own, see [`Isolation`].
+    pub fn add_isolated_task<F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        isolation: Isolation,
+        task: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<()>> + Send + 'static,
+    {
+        self.add_worker(name.into(), isolation, move || {
+            let future = task();
+            Box::pin(async move { Ok(future.await?) })
+        });
+    }
+
+    /// Adds a background task run on a [`Schedule`], such as a metrics flush or a
+    /// health poll.
+    ///
+    /// `schedule` is either a period or a [`Schedule`]. The first run happens right
+    /// away. An error stops the ticks and restarts the worker following the restart
+    /// policy, unless the schedule skips errors. Like [`add_task`](Self::add_task),
+    /// the worker is cancelled on shutdown.
+    pub fn add_interval<F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        schedule: impl Into<Schedule>,
+        task: F,
+    ) where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = io::Result<()>> + Send + 'static,
+    {
+        let name = name.into();
+        let schedule = schedule.into();
+        let task = Arc::new(task);
+        #[cfg(feature = "tracing")]
+        let worker = name.clone();
+        self.add_worker(name, Isolation::Shared, move || {
+            let task = task.clone();
+            #[cfg(feature = "tracing")]
+            let worker = worker.clone();
+            Box::pin(async move {
+                let mut ticks = interval(schedule.period);
+                ticks.set_missed_tick_behavior(schedule.missed_tick);
+                loop {
+                    ticks.tick().await;
+                    match task().await {
+                        Ok(()) => {}
+                        Err(e) if schedule.skip_errors => {
+                            #[cfg(feature = "tracing")]
+                            warn!("[{}] Periodic task failed: {}", worker, e);
+                            #[cfg(not(feature = "tracing"))]
+                            let _ = e;
+                        }
+                        Err(e) => return Err(e.into()),
+                    }
+                }
+            })
+        });
+    }
+
+    /// Registers a supervised worker that serves no socket.
+    fn add_worker<F>(&mut self, name: String, isolation: Isolation, factory: F)
+    where
+        F: Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync + 'static,
+    {
+        let state = ServiceState::new(name, Transport::Task).with_isolation(isolation);
+        let state = Arc::new(state);
+        let worker_state = state.clone();
+        let task = SupervisedTask::new(state, self.policy, move || {
+            let state = worker_state.clone();
+            let future = factory();
+            Box::pin(async move {
+                let _started = state.track_bound(Vec::new());
+                future.await
+            })
+        });
+        self.register(Box::new(task));
+    }
+
+    fn add_on_interfaces<K, S, F>(&mut self, ifaces: Vec<NetworkInterface>, mut factory: F) -> usize
+    where
+        S: Service<K>,
+        F: FnMut(&NetworkInterface) -> S,
+    {
+        let mut added = 0;
+        for iface in ifaces {
+            if !iface.is_up || iface.is_loopback {
+                continue;
+            }
+            let service = factory(&iface);
+            let options = ServiceOptions {
+                bind_mode: Some(BindMode::PreferInterface),
+                labels: HashMap::from([("interface".to_string(), iface.name.clone())]),
+                ..Default::default()
+            };
+            self.register(service.into_task(options, Arc::new(iface), self.policy));
+            added += 1;
+        }
+        added
+    }
+
+    fn add_with_options<K, S>(&mut self, service: S, options: ServiceOptions)
+    where
+        S: Service<K>,
+    {
+        let task = service.into_task(options, self.iface.clone(), self.policy);
+        self.register(task);
+    }
+
+    fn register(&mut self, task: Box<dyn Task>) {
+        self.control.register(task.state().clone());
         self.tasks.push(task);
     }
 
     /// Runs all registered services.
     ///
-    /// This method blocks until a termination signal (Ctrl+C) is received.
-    /// It ensures a graceful shutdown of all services within a 5-second timeout.
+    /// This method blocks until a termination signal (Ctrl+C) is received or a
+    /// shutdown is requested through a [`SupervisorHandle`].
+    /// It ensures a graceful shutdown of all services within the configured grace period.
+    ///
+    /// On Unix, `SIGUSR1` drains every service (see [`SupervisorHandle::drain`])
+    /// without stopping the supervisor.
+    ///
+    /// # Errors
+    /// Returns [`Error::ServiceFailure`] if a service exhausted its restart attempts
+    /// without ever binding its socket, so that CLI tools can exit non-zero when a
+    /// service never came up.
     pub async fn run(self) -> Result<()> {
+        let report = self.run_with_report().await?;
+        match report.never_started.first() {
+            Some(name) => Err(Error::ServiceFailure(name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs all registered services like [`run`](Self::run), and reports how they ended.
+    ///
+    /// The report can be turned into a process exit status with
+    /// [`ShutdownReport::exit_code`].
+    pub async fn run_with_report(self) -> Result<ShutdownReport> {
         let token = CancellationToken::new();
         let mut set = JoinSet::new();
+        let mut running = HashMap::new();
+        let control = self.control;
 
         if self.tasks.is_empty() {
             #[cfg(feature = "tracing")]
             warn!("Supervisor started with no services. Exiting immediately.");
-            return Ok(());
+            return Ok(ShutdownReport::default());
         }
 
         #[cfg(feature = "tracing")]
         info!("Supervisor starting {} services...", self.tasks.len());
 
+        let mut phases: BTreeMap<u8, Vec<Box<dyn Task>>> = BTreeMap::new();
         for task in self.tasks {
-            let t = token.child_token();
-            set.spawn(async move { task.run(t).await });
+            phases.entry(task.state().phase()).or_default().push(task);
         }
 
-        tokio::signal::ctrl_c().await?;
-        println!();
-        #[cfg(feature = "tracing")]
-        info!("Shutdown signal received. Stopping all services...");
+        let mut drain_signal = DrainSignal::new()?;
+        {
+            // Starts each phase once every service of the previous one has bound (or died).
+            let startup = async {
+                let mut previous: Vec<Arc<ServiceState>> = Vec::new();
+                for (_phase, tasks) in phases {
+                    for state in previous.drain(..) {
+                        state.wait_settled().await;
+                    }
+                    #[cfg(feature = "tracing")]
+                    info!("Starting phase {} ({} services)...", _phase, tasks.len());
+                    for task in tasks {
+                        let ctx = TaskContext {
+                            token: token.child_token(),
+                            budget: self.budget.clone(),
+                            connections: self.connections.clone(),
+                            events: Some(self.events.clone()),
+                        };
+                        let state = task.state().clone();
+                        let handle = set.spawn(run_task(task, ctx));
+                        running.insert(handle.id(), state.clone());
+                        previous.push(state);
+                    }
+                }
+                std::future::pending::<()>().await;
+            };
+
+            tokio::pin!(startup);
+            loop {
+                tokio::select! {
+                    _ = &mut startup => {}
+                    res = tokio::signal::ctrl_c() => {
+                        res?;
+                        println!();
+                        #[cfg(feature = "tracing")]
+                        info!("Shutdown signal received. Stopping all services...");
+                    }
+                    _ = drain_signal.recv() => {
+                        #[cfg(feature = "tracing")]
+                        info!("Drain signal received. Services stop accepting new work.");
+                        control.drain();
+                        continue;
+                    }
+                    _ = control.shutdown.cancelled() => {
+                        #[cfg(feature = "tracing")]
+                        info!("Shutdown requested. Stopping all services...");
+                    }
+                    _ = control.abort.cancelled() => {}
+                }
+                break;
+            }
+        }
+        emit(Some(&self.events), || SupervisionEvent::ShutdownRequested);
         token.cancel();
+        let services: Vec<_> = running.values().cloned().collect();
 
-        let shutdown_future = async { while set.join_next().await.is_some() {} };
+        let mut drained = !control.abort.is_cancelled()
+            && tokio::select! {
+                res = timeout(self.grace_period, join_all(&mut set, &mut running)) => res.is_ok(),
+                _ = control.abort.cancelled() => false,
+            };
+        if !drained && !control.abort.is_cancelled() {
+            #[cfg(feature = "tracing")]
+            warn!("Grace period exceeded! Cancelling the remaining connections.");
+            for state in running.values() {
+                state.close_connections();
+            }
+            drained = tokio::select! {
+                res = timeout(self.kill_timeout, join_all(&mut set, &mut running)) => res.is_ok(),
+                _ = control.abort.cancelled() => false,
+            };
+        }
 
-        if timeout(Duration::from_secs(5), shutdown_future)
-            .await
-            .is_err()
-        {
+        if drained {
             #[cfg(feature = "tracing")]
-            error!("Grace period exceeded! Forcing shutdown of remaining services.");
-            set.abort_all();
+            info!("All services shut down gracefully.");
         } else {
             #[cfg(feature = "tracing")]
-            info!("All services shut down gracefully.");
+            if control.abort.is_cancelled() {
+                warn!("Immediate shutdown requested! Aborting all services.");
+            } else {
+                error!("Kill timeout exceeded! Forcing shutdown of remaining services.");
+            }
+            while let Some(res) = set.try_join_next_with_id() {
+                running.remove(&res.map_or_else(|e| e.id(), |(id, ())| id));
+            }
+            if !control.abort.is_cancelled()
+                && let Some(hook) = &self.on_force_shutdown
+            {
+                let names: Vec<String> = running.values().map(|s| s.name().to_string()).collect();
+                hook(&names);
+            }
+            set.abort_all();
+            for state in &services {
+                state.abort_connections();
+            }
         }
 
-        Ok(())
+        Ok(control.report(running.values()))
     }
 }
 
-/// Internal trait representing a runnable task.
-pub trait Task: Send + Sync {
-    /// Executes the task, respecting the cancellation token.
-    fn run(&self, token: CancellationToken) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+/// Returns the accept queue depth of `service`, listening on `addrs`, as
+/// reported by [`SupervisorHandle::accept_queue_depth`].
+fn queue_depth(service: &ServiceState, addrs: &[SocketAddr]) -> Option<u32> {
+    if service.transport() != Transport::Tcp || addrs.is_empty() {
+        return None;
+    }
+    addrs.iter().map(accept_queue_depth).sum()
 }
 
-/// A generic task that runs a factory closure with restart logic.
-pub struct SupervisedTask<F> {
-    #[cfg_attr(not(feature = "tracing"), allow(dead_code))]
-    name: &'static str,
-    policy: RestartPolicy,
-    factory: Arc<F>,
-}
+/// Runs `task`, on a dedicated runtime if its [`Isolation`] asks for one.
+///
+/// Dropping the returned future, e.g. when the supervisor aborts its tasks, stops
+/// the dedicated runtime.
+async fn run_task(task: Box<dyn Task>, ctx: TaskContext) {
+    let state = task.state().clone();
+    let future = task.run(ctx);
+    let Some(mut builder) = state.isolation().builder() else {
+        return future.await;
+    };
+    let thread = format!("maestro-{}", state.name());
+    let runtime = match builder.thread_name(&thread).build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            #[cfg(feature = "tracing")]
+            warn!(
+                "[{}] Cannot build a dedicated runtime, running on the shared one: {}",
+                state.name(),
+                e
+            );
+            #[cfg(not(feature = "tracing"))]
+            let _ = e;
+            return future.await;
+        }
+    };
 
-impl<F> SupervisedTask<F>
-where
-    F: Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync,
-{
-    /// Creates a new supervised task instance.
-    pub fn new(name: &'static str, policy: RestartPolicy, factory: F) -> Self {
-        Self {
-            name,
-            policy,
-            factory: Arc::new(factory),
+    let stop = CancellationToken::new();
+    let _stop = stop.clone().drop_guard();
+    let (done, finished) = oneshot::channel();
+    let spawned = std::thread::Builder::new().name(thread).spawn(move || {
+        runtime.block_on(async {
+            tokio::select! {
+                _ = future => {}
+                _ = stop.cancelled() => {}
+            }
+        });
+        let _ = done.send(());
+    });
+    match spawned {
+        Ok(_) => {
+            let _ = finished.await;
+        }
+        Err(e) => {
+            #[cfg(feature = "tracing")]
+            error!("[{}] Cannot spawn the runtime thread: {}", state.name(), e);
+            #[cfg(not(feature = "tracing"))]
+            let _ = e;
+            state.mark_dead();
         }
     }
 }
 
-impl<F> Task for SupervisedTask<F>
-where
-    F: Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync + 'static,
-{
-    fn run(&self, token: CancellationToken) -> Pin<Box<dyn Future<Output = ()> + Send>> {
-        #[cfg(feature = "tracing")]
-        let name = self.name;
-        let policy = self.policy;
-        let factory = self.factory.clone();
+/// Waits for every task of `set` to end, removing each from `running` as it does.
+async fn join_all(set: &mut JoinSet<()>, running: &mut HashMap<Id, Arc<ServiceState>>) {
+    while let Some(res) = set.join_next_with_id().await {
+        running.remove(&res.map_or_else(|e| e.id(), |(id, ())| id));
+    }
+}
 
-        Box::pin(async move {
-            let mut attempts = 0;
+/// Summary of how the services of a [`Supervisor`] ended.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShutdownReport {
+    dead: Vec<String>,
+    never_started: Vec<String>,
+    aborted: Vec<String>,
+}
 
-            loop {
-                #[cfg(feature = "tracing")]
-                info!("[{}] Starting service instance...", name);
-                let future = factory();
+impl ShutdownReport {
+    /// Services that exhausted their restart attempts.
+    pub fn dead(&self) -> &[String] {
+        &self.dead
+    }
 
-                tokio::select! {
-                    res = future => {
-                        match res {
-                            Ok(_) => {
-                                #[cfg(feature = "tracing")]
-                                info!("[{}] Service exited normally.", name);
-                                break;
-                            },
-                            Err(e) => {
-                                #[cfg(feature = "tracing")]
-                                error!("[{}] Service crashed: {}", name, e);
-                                #[cfg(not(feature = "tracing"))]
-                                let _ = e;
-                            }
-                        }
-                    }
-                    _ = token.cancelled() => {
-                        #[cfg(feature = "tracing")]
-                        info!("[{}] Cancellation requested. Stopping.", name);
-                        break;
-                    }
+    /// Services that exhausted their restart attempts without ever binding.
+    pub fn never_started(&self) -> &[String] {
+        &self.never_started
+    }
+
+    /// Services that were still running when the supervisor aborted them, either
+    /// because the grace period and the kill timeout expired or
+    /// [`SupervisorHandle::shutdown_now`] was called.
+    pub fn aborted(&self) -> &[String] {
+        &self.aborted
+    }
+
+    /// Returns whether every service stopped on its own or gracefully.
+    pub fn is_graceful(&self) -> bool {
+        self.dead.is_empty() && self.aborted.is_empty()
+    }
+
+    /// Maps the outcome to a conventional process exit status.
+    ///
+    /// Returns `0` if every service stopped gracefully, `1` if any service died
+    /// permanently, and `2` if services had to be force-aborted.
+    pub fn exit_code(&self) -> i32 {
+        if !self.dead.is_empty() {
+            1
+        } else if !self.aborted.is_empty() {
+            2
+        } else {
+            0
+        }
+    }
+}
+
+/// A cloneable handle for controlling a running [`Supervisor`].
+#[derive(Clone, Debug)]
+pub struct SupervisorHandle {
+    shutdown: CancellationToken,
+    abort: CancellationToken,
+    services: Arc<RwLock<Vec<Arc<ServiceState>>>>,
+}
+
+impl SupervisorHandle {
+    fn new() -> Self {
+        Self {
+            shutdown: CancellationToken::new(),
+            abort: CancellationToken::new(),
+            services: Arc::default(),
+        }
+    }
+
+    fn register(&self, state: Arc<ServiceState>) {
+        self.services
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(state);
+    }
+
+    /// Builds the report of a finished run, given the services that had to be aborted.
+    fn report<'a>(&self, aborted: impl Iterator<Item = &'a Arc<ServiceState>>) -> ShutdownReport {
+        let services = self.services.read().unwrap_or_else(PoisonError::into_inner);
+        let names = |pred: fn(&ServiceState) -> bool| {
+            services
+                .iter()
+                .filter(|s| pred(s))
+                .map(|s| s.name().to_string())
+                .collect()
+        };
+        ShutdownReport {
+            dead: names(|s| s.is_dead()),
+            never_started: names(|s| s.is_dead() && !s.has_bound()),
+            aborted: aborted.map(|s| s.name().to_string()).collect(),
+        }
+    }
+
+    fn service(&self, name: &str) -> Result<Arc<ServiceState>> {
+        self.services
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .find(|s| s.name().as_ref() == name)
+            .cloned()
+            .ok_or_else(|| Error::UnknownService(name.to_string()))
+    }
+
+    /// Waits until every registered service has bound its socket. Services
+    /// that are not [active](Self::activate) yet are skipped.
+    ///
+    /// Use it to sequence work after startup, e.g. announcing the process to a
+    /// service registry only once it can take traffic.
+    ///
+    /// # Errors
+    /// Returns [`Error::ServiceFailure`] if a service gave up without ever binding,
+    /// or if shutdown was requested before every service was ready.
+    pub async fn ready(&self) -> Result<()> {
+        let services = self
+            .services
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for service in services {
+            tokio::select! {
+                _ = service.wait_settled() => {}
+                _ = self.shutdown.cancelled() => {}
+            }
+            if !service.has_bound() && service.is_active() {
+                return Err(Error::ServiceFailure(service.name().to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a point-in-time view of every registered service, for metrics export.
+    pub fn snapshot(&self) -> Vec<ServiceSnapshot> {
+        self.services
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|s| {
+                let listening = s.bound_addrs();
+                ServiceSnapshot {
+                    name: s.name().to_string(),
+                    labels: s.labels().clone(),
+                    accept_queue_depth: queue_depth(s, &listening),
+                    listening,
+                    paused: s.is_paused(),
+                    active: s.is_active(),
+                    dead: s.is_dead(),
+                    health: s.health(),
+                    in_flight: s.in_flight(),
+                    shed: s.shed(),
+                    oversized: s.oversized(),
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the addresses a service is currently listening on.
+    ///
+    /// The list reflects the live instance: it is empty while the service is
+    /// restarting and is updated after every rebind.
+    pub fn listening_addrs(&self, name: &str) -> Result<Vec<SocketAddr>> {
+        Ok(self.service(name)?.bound_addrs())
+    }
+
+    /// Returns how many connections are waiting in a TCP service's accept queue.
+    ///
+    /// Comparing this with the listen backlog shows whether the backlog should be
+    /// raised. Returns `None` for UDP services, while the service is not bound,
+    /// or on platforms other than Linux.
+    pub fn accept_queue_depth(&self, name: &str) -> Result<Option<u32>> {
+        let service = self.service(name)?;
+        Ok(queue_depth(&service, &service.bound_addrs()))
+    }
+
+    /// Pauses a service: it stops accepting connections or receiving datagrams,
+    /// but keeps its sockets bound.
+    ///
+    /// New connections and datagrams wait in the kernel queues (up to the listen
+    /// backlog or receive buffer) until [`resume`](Self::resume) is called.
+    /// Connections already being handled are not affected.
+    pub fn pause(&self, name: &str) -> Result<()> {
+        self.service(name)?.pause();
+        Ok(())
+    }
+
+    /// Resumes a service paused with [`pause`](Self::pause).
+    pub fn resume(&self, name: &str) -> Result<()> {
+        self.service(name)?.resume();
+        Ok(())
+    }
+
+    /// Binds and starts a service added with [`Supervisor::add_inactive`]. Does
+    /// nothing if it is already active.
+    pub fn activate(&self, name: &str) -> Result<()> {
+        self.service(name)?.activate();
+        Ok(())
+    }
+
+    /// Drains every service: they stop accepting connections and receiving
+    /// datagrams, while connections in progress are left to finish.
+    ///
+    /// This is [`pause`](Self::pause) applied to all services, typically ahead of a
+    /// deploy. Follow it with [`shutdown`](Self::shutdown) to stop, or
+    /// [`resume_all`](Self::resume_all) to serve again. Sending `SIGUSR1` to the
+    /// process has the same effect on Unix.
+    pub fn drain(&self) {
+        for service in self
+            .services
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            service.pause();
+        }
+    }
+
+    /// Resumes every paused or drained service.
+    pub fn resume_all(&self) {
+        for service in self
+            .services
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+        {
+            service.resume();
+        }
+    }
+
+    /// Replaces the handler of a running service without unbinding its sockets,
+    /// e.g. to load a new ruleset.
+    ///
+    /// New connections and datagrams go to `handler`, while those in progress
+    /// finish with the previous one. The handler also serves later restarts.
+    ///
+    /// # Errors
+    /// Returns [`Error::HandlerMismatch`] if `handler` is not of the type the service
+    /// was added with. Wrappers count: a service added as `IpFilter<H>` must be
+    /// given an `IpFilter<H>`.
+    pub fn replace_handler<H>(&self, name: &str, handler: H) -> Result<()>
+    where
+        H: Send + Sync + 'static,
+    {
+        if self.service(name)?.replace_handler(handler) {
+            Ok(())
+        } else {
+            Err(Error::HandlerMismatch(name.to_string()))
+        }
+    }
+
+    /// Requests a graceful shutdown, equivalent to receiving Ctrl+C.
+    ///
+    /// Services are cancelled and given the grace period to stop, then their
+    /// remaining connections get the kill timeout, before being aborted.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    /// Aborts every service immediately, skipping the grace period.
+    ///
+    /// This is intended for situations where the process must exit fast. Services
+    /// are stopped at their next suspension point, so in-flight connections are cut
+    /// and any unflushed data is lost. It may also be called while a graceful
+    /// shutdown is already draining to cut it short.
+    pub fn shutdown_now(&self) {
+        self.abort.cancel();
+    }
+}
+
+/// Listens for the drain signal, `SIGUSR1`. Never fires on non-Unix platforms.
+struct DrainSignal {
+    #[cfg(unix)]
+    inner: tokio::signal::unix::Signal,
+}
+
+impl DrainSignal {
+    fn new() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            inner: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())?,
+        })
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.inner.recv().await;
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
+
+/// State of one service at the time of a [`SupervisorHandle::snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceSnapshot {
+    /// Display name of the service.
+    pub name: String,
+    /// Labels attached with [`Supervisor::add_with_labels`].
+    pub labels: HashMap<String, String>,
+    /// Addresses the service is listening on; empty while it is not bound.
+    pub listening: Vec<SocketAddr>,
+    /// Connections waiting in the accept queue of a TCP service, see
+    /// [`SupervisorHandle::accept_queue_depth`].
+    pub accept_queue_depth: Option<u32>,
+    /// Whether the service is paused.
+    pub paused: bool,
+    /// Whether the service is active, i.e. was not added with
+    /// [`Supervisor::add_inactive`] or has been activated since.
+    pub active: bool,
+    /// Whether the service gave up after exhausting its restart attempts.
+    pub dead: bool,
+    /// Health of the service.
+    pub health: WorkerHealth,
+    /// Connections or datagrams being handled.
+    pub in_flight: usize,
+    /// Connections or datagrams rejected so far because
+    /// [`max_in_flight`](crate::TcpHandler::max_in_flight) was reached.
+    pub shed: u64,
+    /// Datagrams dropped so far because they exceeded
+    /// [`max_datagram_size`](crate::UdpHandler::max_datagram_size).
+    pub oversized: u64,
+}
+
+/// A change in the lifecycle of a service, sent to [`Supervisor::events`] subscribers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SupervisionEvent {
+    /// An instance of the service is starting, initially or after a restart.
+    Started { service: String },
+    /// An instance of the service failed.
+    Crashed { service: String, error: String },
+    /// The service will be restarted after `delay`.
+    RestartScheduled {
+        service: String,
+        attempt: usize,
+        delay: Duration,
+    },
+    /// The service exhausted its restart attempts and will not run again.
+    Abandoned { service: String },
+    /// The health of the service changed.
+    HealthChanged {
+        service: String,
+        health: WorkerHealth,
+    },
+    /// The supervisor is shutting every service down.
+    ShutdownRequested,
+}
+
+/// Sends the event built by `event`, if anyone is listening.
+pub(crate) fn emit(
+    events: Option<&broadcast::Sender<SupervisionEvent>>,
+    event: impl FnOnce() -> SupervisionEvent,
+) {
+    if let Some(events) = events
+        && events.receiver_count() > 0
+    {
+        let _ = events.send(event());
+    }
+}
+
+/// Limits the rate of restarts shared by every task of a supervisor.
+///
+/// Allows at most `max` restarts within any sliding window of `per`.
+pub struct RestartBudget {
+    max: usize,
+    per: Duration,
+    restarts: Mutex<VecDeque<Instant>>,
+}
+
+impl RestartBudget {
+    /// Creates a budget of `max` restarts per `per` window. `max` is at least 1.
+    pub fn new(max: usize, per: Duration) -> Self {
+        Self {
+            max: max.max(1),
+            per,
+            restarts: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Waits until a restart is allowed, then consumes one unit of the budget.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut restarts = self.restarts.lock().await;
+                let now = Instant::now();
+                while restarts
+                    .front()
+                    .is_some_and(|t| now.duration_since(*t) >= self.per)
+                {
+                    restarts.pop_front();
+                }
+                match restarts.front() {
+                    Some(oldest) if restarts.len() >= self.max => {
+                        self.per.saturating_sub(now.duration_since(*oldest))
+                    }
+                    _ => {
+                        restarts.push_back(now);
+                        return;
+                    }
+                }
+            };
+            sleep(wait).await;
+        }
+    }
+}
+
+/// Caps the connections open at once across every TCP service of a supervisor,
+/// so that together they stay clear of the process file descriptor limit.
+///
+/// A connection accepted while the budget is spent is closed right away, instead
+/// of letting the process reach the limit and fail every `accept` with `EMFILE`.
+#[derive(Debug)]
+pub struct ConnectionBudget {
+    max: usize,
+    open: AtomicUsize,
+    rejected: AtomicU64,
+}
+
+impl ConnectionBudget {
+    /// Creates a budget of `max` open connections.
+    pub fn new(max: usize) -> Self {
+        Self {
+            max,
+            open: AtomicUsize::new(0),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Creates a budget from the soft `RLIMIT_NOFILE` of the process, keeping
+    /// `headroom` descriptors free for listeners, log files and the like.
+    ///
+    /// # Errors
+    /// Fails if the limit cannot be read. It is read from `/proc/self/limits`,
+    /// so this is only supported on Linux.
+    pub fn from_fd_limit(headroom: usize) -> io::Result<Self> {
+        let limits = std::fs::read_to_string("/proc/self/limits")?;
+        let limit = parse_fd_limit(&limits).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "no open files limit found")
+        })?;
+        Ok(Self::new(limit.saturating_sub(headroom)))
+    }
+
+    /// Returns the maximum number of open connections.
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// Returns how many connections are open.
+    pub fn open(&self) -> usize {
+        self.open.load(Ordering::Relaxed)
+    }
+
+    /// Returns how many connections were closed because the budget was spent.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Counts one connection as open until the returned permit is dropped.
+    ///
+    /// Returns `None`, and counts the connection as rejected, when the budget is spent.
+    pub fn try_acquire(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        let acquired = self
+            .open
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |open| {
+                (open < self.max).then_some(open + 1)
+            });
+        if acquired.is_err() {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return None;
+        }
+        Some(ConnectionPermit {
+            budget: self.clone(),
+        })
+    }
+}
+
+/// One open connection of a [`ConnectionBudget`], released when dropped.
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    budget: Arc<ConnectionBudget>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        self.budget.open.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Reads the soft limit from the `Max open files` line of `/proc/<pid>/limits`.
+fn parse_fd_limit(limits: &str) -> Option<usize> {
+    let soft = limits
+        .lines()
+        .find_map(|line| line.strip_prefix("Max open files"))?
+        .split_whitespace()
+        .next()?;
+    match soft {
+        "unlimited" => Some(usize::MAX),
+        soft => soft.parse().ok(),
+    }
+}
+
+/// Supervisor-wide state handed to a task when it is started.
+#[derive(Clone, Default)]
+pub struct TaskContext {
+    /// Cancelled when the supervisor shuts down.
+    pub token: CancellationToken,
+    /// Restart budget shared by all tasks, if configured.
+    pub budget: Option<Arc<RestartBudget>>,
+    /// Connection budget shared by all TCP services, if configured.
+    pub connections: Option<Arc<ConnectionBudget>>,
+    /// Channel of [`Supervisor::events`], if the task runs under a supervisor.
+    pub events: Option<broadcast::Sender<SupervisionEvent>>,
+}
+
+/// Internal trait representing a runnable task.
+pub trait Task: Send + Sync {
+    /// Returns the live state shared with the supervisor handle.
+    fn state(&self) -> &Arc<ServiceState>;
+
+    /// Returns the display name of the task.
+    fn name(&self) -> &str {
+        self.state().name()
+    }
+
+    /// Executes the task, respecting the context's cancellation token.
+    fn run(&self, ctx: TaskContext) -> Pin<Box<dyn Future<Output = ()> + Send>>;
+}
+
+/// An async callback invoked when a task is cancelled.
+type ShutdownHook = Arc<dyn Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+
+/// A generic task that runs a factory closure with restart logic.
+pub struct SupervisedTask<F> {
+    state: Arc<ServiceState>,
+    policy: RestartPolicy,
+    factory: Arc<F>,
+    on_shutdown: Option<ShutdownHook>,
+}
+
+impl<F> SupervisedTask<F>
+where
+    F: Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync,
+{
+    /// Creates a new supervised task instance.
+    pub fn new(state: Arc<ServiceState>, policy: RestartPolicy, factory: F) -> Self {
+        Self {
+            state,
+            policy,
+            factory: Arc::new(factory),
+            on_shutdown: None,
+        }
+    }
+
+    /// Sets a callback awaited once when the task observes cancellation.
+    ///
+    /// It is not invoked when the task stops on its own (clean exit or exhausted restarts).
+    pub fn with_shutdown_hook<G>(mut self, hook: G) -> Self
+    where
+        G: Fn() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync + 'static,
+    {
+        self.on_shutdown = Some(Arc::new(hook));
+        self
+    }
+}
+
+impl<F> Task for SupervisedTask<F>
+where
+    F: Fn() -> Pin<Box<dyn Future<Output = Result<()>> + Send>> + Send + Sync + 'static,
+{
+    fn state(&self) -> &Arc<ServiceState> {
+        &self.state
+    }
+
+    fn run(&self, ctx: TaskContext) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        #[cfg(feature = "tracing")]
+        let name = self.state.name().clone();
+        let state = self.state.clone();
+        let policy = self.policy;
+        let factory = self.factory.clone();
+        let on_shutdown = self.on_shutdown.clone();
+        let TaskContext {
+            token,
+            budget,
+            connections,
+            events,
+        } = ctx;
+        if let Some(connections) = connections {
+            state.set_connection_budget(connections);
+        }
+        if let Some(events) = &events {
+            state.set_events(events.clone());
+        }
+        #[cfg(feature = "tracing")]
+        let span = info_span!("service", labels = ?self.state.labels());
+
+        let task = async move {
+            tokio::select! {
+                _ = state.wait_active() => {}
+                _ = token.cancelled() => return,
+            }
+            let mut attempts = 0;
+
+            let cancelled = loop {
+                #[cfg(feature = "tracing")]
+                info!("[{}] Starting service instance...", name);
+                emit(events.as_ref(), || SupervisionEvent::Started {
+                    service: state.name().to_string(),
+                });
+                state.set_health(WorkerHealth::Starting);
+                let future = factory();
+                let started = Instant::now();
+
+                let res = tokio::select! {
+                    res = future => res,
+                    timeout = state.wait_unresponsive() => {
+                        Err(Error::Unresponsive(state.name().to_string(), timeout))
+                    }
+                    _ = token.cancelled() => {
+                        #[cfg(feature = "tracing")]
+                        info!("[{}] Cancellation requested. Stopping.", name);
+                        break true;
+                    }
+                };
+                match res {
+                    Ok(_) => {
+                        #[cfg(feature = "tracing")]
+                        info!("[{}] Service exited normally.", name);
+                        break false;
+                    }
+                    Err(Error::InterfaceGone(iface)) => {
+                        #[cfg(feature = "tracing")]
+                        warn!(
+                            "[{}] Interface `{}` is gone. Waiting for it to reappear...",
+                            name, iface
+                        );
+                        state.set_health(WorkerHealth::Restarting);
+                        tokio::select! {
+                            _ = wait_for_interface(&iface) => {}
+                            _ = token.cancelled() => break true,
+                        }
+                        #[cfg(feature = "tracing")]
+                        info!("[{}] Interface `{}` is back.", name, iface);
+                        continue;
+                    }
+                    Err(e) => {
+                        #[cfg(feature = "tracing")]
+                        error!("[{}] Service crashed: {}", name, e);
+                        emit(events.as_ref(), || SupervisionEvent::Crashed {
+                            service: state.name().to_string(),
+                            error: e.to_string(),
+                        });
+                    }
                 }
 
+                // Only time spent bound counts as serving. Tasks never bind, so
+                // they serve from the start.
+                let serving_since = if state.transport() == Transport::Task {
+                    Some(started)
+                } else {
+                    state.bound_at().filter(|at| *at >= started)
+                };
+                if policy
+                    .min_healthy_duration
+                    .zip(serving_since)
+                    .is_some_and(|(min, since)| since.elapsed() >= min)
+                {
+                    attempts = 0;
+                }
                 attempts += 1;
                 if let Some(max) = policy.max_attempts
                     && attempts >= max
@@ -214,10 +1522,24 @@ where
                         "[{}] Max restart attempts ({}) reached. Service is DEAD.",
                         name, max
                     );
-                    break;
+                    state.mark_dead();
+                    emit(events.as_ref(), || SupervisionEvent::Abandoned {
+                        service: state.name().to_string(),
+                    });
+                    break false;
                 }
 
-                let delay = policy.delay(attempts);
+                let delay = policy.delay_for_attempt(attempts).max(
+                    policy
+                        .min_restart_interval
+                        .saturating_sub(started.elapsed()),
+                );
+                state.set_health(WorkerHealth::Restarting);
+                emit(events.as_ref(), || SupervisionEvent::RestartScheduled {
+                    service: state.name().to_string(),
+                    attempt: attempts,
+                    delay,
+                });
                 #[cfg(feature = "tracing")]
                 warn!(
                     "[{}] Will restart in {:.1}s (Attempt {}/{:?})",
@@ -229,9 +1551,1498 @@ where
 
                 tokio::select! {
                     _ = sleep(delay) => {},
-                    _ = token.cancelled() => break,
+                    _ = token.cancelled() => break true,
                 }
+
+                if let Some(budget) = &budget {
+                    tokio::select! {
+                        _ = budget.acquire() => {},
+                        _ = token.cancelled() => break true,
+                    }
+                }
+            };
+
+            if cancelled {
+                if let Some(hook) = on_shutdown {
+                    hook().await;
+                }
+                state.wait_idle().await;
             }
+        };
+
+        #[cfg(feature = "tracing")]
+        let task = task.instrument(span);
+        Box::pin(task)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BindMode, PacketInfo, async_trait};
+    use std::{
+        net::SocketAddr,
+        str::FromStr,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpStream, UdpSocket},
+    };
+
+    struct MockTcp;
+    #[async_trait]
+    impl TcpHandler for MockTcp {
+        fn name(&self) -> &'static str {
+            "MockTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+        }
+    }
+
+    #[test]
+    fn test_add_named_instances() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+
+        supervisor.add_named("dns-internal", MockTcp);
+        supervisor.add_named("dns-external", MockTcp);
+        supervisor.add(MockTcp);
+
+        let names: Vec<&str> = supervisor.tasks.iter().map(|t| t.name()).collect();
+        assert_eq!(names, ["dns-internal", "dns-external", "MockTcp"]);
+    }
+
+    fn state(name: &str) -> Arc<ServiceState> {
+        Arc::new(ServiceState::new(name, Transport::Tcp))
+    }
+
+    /// An instance that binds after `bind_delay`, then fails once it has served
+    /// for `uptime`.
+    fn flapping(
+        state: &Arc<ServiceState>,
+        bind_delay: Duration,
+        uptime: Duration,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send>> {
+        let state = state.clone();
+        Box::pin(async move {
+            sleep(bind_delay).await;
+            let _bound = state.track_bound(Vec::new());
+            sleep(uptime).await;
+            Err(Error::ServiceFailure("boom".into()))
         })
     }
+
+    /// A task that ignores cancellation entirely.
+    struct HungTask(Arc<ServiceState>);
+    impl Task for HungTask {
+        fn state(&self) -> &Arc<ServiceState> {
+            &self.0
+        }
+        fn run(&self, _ctx: TaskContext) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_now_skips_grace_period() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface).with_grace_period(Duration::from_secs(30));
+        supervisor.tasks.push(Box::new(HungTask(state("HungTask"))));
+        let handle = supervisor.handle();
+
+        let started = std::time::Instant::now();
+        let run = tokio::spawn(supervisor.run());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.shutdown();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        handle.shutdown_now();
+
+        timeout(Duration::from_secs(2), run)
+            .await
+            .expect("supervisor did not stop")
+            .unwrap()
+            .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_only_healthy_runs_reset_attempts() {
+        let policy = RestartPolicy::default()
+            .with_max_attempts(3)
+            .with_delay(Duration::from_millis(1))
+            .with_min_healthy_duration(Duration::from_secs(1));
+        let token = CancellationToken::new();
+        let mut set = JoinSet::new();
+        let mut starts = Vec::new();
+
+        // One service crashes shortly after starting, the other after staying up.
+        for uptime in [Duration::from_millis(100), Duration::from_secs(2)] {
+            let count = Arc::new(AtomicUsize::new(0));
+            starts.push(count.clone());
+            let name = format!("up-{}ms", uptime.as_millis());
+            let state = state(&name);
+            let task = SupervisedTask::new(state.clone(), policy, move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                flapping(&state, Duration::ZERO, uptime)
+            });
+            let ctx = TaskContext {
+                token: token.child_token(),
+                ..Default::default()
+            };
+            set.spawn(async move { task.run(ctx).await });
+        }
+
+        sleep(Duration::from_secs(20)).await;
+        token.cancel();
+        while set.join_next().await.is_some() {}
+
+        assert_eq!(starts[0].load(Ordering::SeqCst), 3);
+        assert!(starts[1].load(Ordering::SeqCst) > 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_zero_delay_restarts_are_rate_limited() {
+        let policy = RestartPolicy {
+            max_attempts: None,
+            base_delay: Duration::ZERO,
+            ..Default::default()
+        }
+        .with_min_restart_interval(Duration::from_millis(50));
+        let starts = Arc::new(AtomicUsize::new(0));
+        let count = starts.clone();
+        let task = SupervisedTask::new(state("spinning"), policy, move || {
+            count.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Err(Error::ServiceFailure("boom".into())) })
+        });
+        let token = CancellationToken::new();
+        let ctx = TaskContext {
+            token: token.clone(),
+            ..Default::default()
+        };
+        let run = tokio::spawn(async move { task.run(ctx).await });
+
+        sleep(Duration::from_millis(1025)).await;
+        token.cancel();
+        run.await.unwrap();
+
+        // Started at 0ms, then every 50ms up to 1000ms.
+        assert_eq!(starts.load(Ordering::SeqCst), 21);
+    }
+
+    /// Uptime is measured on the tokio clock, so paused time pins the boundary.
+    #[tokio::test(start_paused = true)]
+    async fn test_min_healthy_duration_boundary() {
+        let policy = RestartPolicy::default()
+            .with_max_attempts(2)
+            .with_delay(Duration::from_millis(1))
+            .with_min_healthy_duration(Duration::from_secs(1));
+
+        for (uptime, abandoned) in [
+            (Duration::from_millis(999), true),
+            (Duration::from_secs(1), false),
+        ] {
+            let starts = Arc::new(AtomicUsize::new(0));
+            let count = starts.clone();
+            let state = state("flapping");
+            let task = SupervisedTask::new(state.clone(), policy, move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                flapping(&state, Duration::ZERO, uptime)
+            });
+            let token = CancellationToken::new();
+            let ctx = TaskContext {
+                token: token.clone(),
+                ..Default::default()
+            };
+            let run = tokio::spawn(async move { task.run(ctx).await });
+
+            sleep(Duration::from_secs(10)).await;
+            assert_eq!(run.is_finished(), abandoned, "{uptime:?}");
+            token.cancel();
+            run.await.unwrap();
+            if abandoned {
+                assert_eq!(starts.load(Ordering::SeqCst), 2);
+            }
+        }
+    }
+
+    /// Time spent binding is not time spent serving.
+    #[tokio::test(start_paused = true)]
+    async fn test_uptime_counts_from_bind() {
+        let policy = RestartPolicy::default()
+            .with_max_attempts(2)
+            .with_delay(Duration::from_millis(1))
+            .with_min_healthy_duration(Duration::from_secs(1));
+        let starts = Arc::new(AtomicUsize::new(0));
+        let count = starts.clone();
+        let state = state("slow-bind");
+        let task = SupervisedTask::new(state.clone(), policy, move || {
+            count.fetch_add(1, Ordering::SeqCst);
+            flapping(
+                &state,
+                Duration::from_millis(900),
+                Duration::from_millis(500),
+            )
+        });
+        let token = CancellationToken::new();
+        let ctx = TaskContext {
+            token: token.clone(),
+            ..Default::default()
+        };
+        let run = tokio::spawn(async move { task.run(ctx).await });
+
+        sleep(Duration::from_secs(10)).await;
+        assert!(run.is_finished());
+        assert_eq!(starts.load(Ordering::SeqCst), 2);
+        token.cancel();
+        run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_restart_budget_throttles_workers() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let budget = Arc::new(RestartBudget::new(2, Duration::from_secs(30)));
+        let policy = RestartPolicy {
+            max_attempts: None,
+            base_delay: Duration::from_millis(1),
+            min_healthy_duration: None,
+            min_restart_interval: Duration::ZERO,
+        };
+        let token = CancellationToken::new();
+        let mut set = JoinSet::new();
+
+        for i in 0..5 {
+            let starts = starts.clone();
+            let task = SupervisedTask::new(state(&format!("failing-{i}")), policy, move || {
+                let starts = starts.clone();
+                Box::pin(async move {
+                    starts.fetch_add(1, Ordering::SeqCst);
+                    Err(Error::ServiceFailure("boom".into()))
+                })
+            });
+            let ctx = TaskContext {
+                token: token.child_token(),
+                budget: Some(budget.clone()),
+                ..Default::default()
+            };
+            set.spawn(async move { task.run(ctx).await });
+        }
+
+        sleep(Duration::from_millis(300)).await;
+        token.cancel();
+        while set.join_next().await.is_some() {}
+
+        // Five initial starts, then only two restarts fit in the shared budget.
+        assert_eq!(starts.load(Ordering::SeqCst), 7);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hung_service_is_restarted() {
+        let policy = RestartPolicy::default().with_delay(Duration::from_millis(1));
+        let token = CancellationToken::new();
+        let mut set = JoinSet::new();
+        let mut starts = Vec::new();
+
+        for hangs in [true, false] {
+            let state = Arc::new(
+                ServiceState::new(format!("hangs-{hangs}"), Transport::Tcp)
+                    .with_liveness_timeout(Some(Duration::from_millis(100))),
+            );
+            let count = Arc::new(AtomicUsize::new(0));
+            starts.push(count.clone());
+            let beating = state.clone();
+            let task = SupervisedTask::new(state, policy, move || {
+                count.fetch_add(1, Ordering::SeqCst);
+                let beating = beating.clone();
+                Box::pin(async move {
+                    loop {
+                        if !hangs {
+                            beating.heartbeat();
+                        }
+                        sleep(Duration::from_millis(30)).await;
+                    }
+                })
+            });
+            let ctx = TaskContext {
+                token: token.child_token(),
+                ..Default::default()
+            };
+            set.spawn(async move { task.run(ctx).await });
+        }
+
+        sleep(Duration::from_millis(350)).await;
+        token.cancel();
+        while set.join_next().await.is_some() {}
+
+        assert_eq!(starts[0].load(Ordering::SeqCst), 4);
+        assert_eq!(starts[1].load(Ordering::SeqCst), 1);
+    }
+
+    struct HelloTcp;
+    #[async_trait]
+    impl TcpHandler for HelloTcp {
+        fn name(&self) -> &'static str {
+            "HelloTcp"
+        }
+        fn port(&self) -> u16 {
+            1
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let _ = s.write_all(b"hello").await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prebound_listener_is_served() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add_prebound(HelloTcp, listener);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        timeout(Duration::from_secs(2), stream.read_to_end(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(buf, b"hello");
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_hook_runs_only_on_cancel() {
+        let hooks = Arc::new(AtomicUsize::new(0));
+        let hook = {
+            let hooks = hooks.clone();
+            move || {
+                let hooks = hooks.clone();
+                Box::pin(async move {
+                    hooks.fetch_add(1, Ordering::SeqCst);
+                }) as Pin<Box<dyn Future<Output = ()> + Send>>
+            }
+        };
+
+        let clean = SupervisedTask::new(state("clean"), RestartPolicy::default(), || {
+            Box::pin(async { Ok(()) })
+        })
+        .with_shutdown_hook(hook.clone());
+        clean.run(TaskContext::default()).await;
+        assert_eq!(hooks.load(Ordering::SeqCst), 0);
+
+        let serving = SupervisedTask::new(state("serving"), RestartPolicy::default(), || {
+            Box::pin(std::future::pending())
+        })
+        .with_shutdown_hook(hook);
+        let ctx = TaskContext::default();
+        let token = ctx.token.clone();
+        let run = tokio::spawn(serving.run(ctx));
+        sleep(Duration::from_millis(20)).await;
+        token.cancel();
+        run.await.unwrap();
+        assert_eq!(hooks.load(Ordering::SeqCst), 1);
+    }
+
+    struct LocalTcp;
+    #[async_trait]
+    impl TcpHandler for LocalTcp {
+        fn name(&self) -> &'static str {
+            "LocalTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let _ = s.write_all(b"hello").await;
+        }
+    }
+
+    /// Answers both transports with how many requests it served so far.
+    #[derive(Default)]
+    struct CountingDual(AtomicUsize);
+    impl CountingDual {
+        fn next(&self) -> String {
+            (self.0.fetch_add(1, Ordering::SeqCst) + 1).to_string()
+        }
+    }
+    #[async_trait]
+    impl TcpHandler for CountingDual {
+        fn name(&self) -> &'static str {
+            "dual"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let _ = s.write_all(self.next().as_bytes()).await;
+        }
+    }
+    #[async_trait]
+    impl UdpHandler for CountingDual {
+        fn name(&self) -> &'static str {
+            "dual"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            socket: Arc<UdpSocket>,
+            peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            let _ = socket.send_to(self.next().as_bytes(), peer).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dual_handler_serves_both_transports() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add_dual(CountingDual::default());
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+        timeout(Duration::from_secs(2), handle.ready())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let tcp = handle.listening_addrs("dual/tcp").unwrap()[0];
+        let mut stream = TcpStream::connect(tcp).await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"1");
+
+        let udp = handle.listening_addrs("dual/udp").unwrap()[0];
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.send_to(b"?", udp).await.unwrap();
+        let mut buf = [0u8; 8];
+        let n = timeout(Duration::from_secs(1), client.recv(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf[..n], b"2");
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    struct RewriteTcp {
+        upper: bool,
+    }
+    #[async_trait]
+    impl TcpHandler for RewriteTcp {
+        fn name(&self) -> &'static str {
+            "RewriteTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let mut buf = [0u8; 64];
+            while let Ok(n) = s.read(&mut buf).await
+                && n > 0
+            {
+                if self.upper {
+                    buf[..n].make_ascii_uppercase();
+                }
+                let _ = s.write_all(&buf[..n]).await;
+            }
+        }
+    }
+
+    async fn exchange(stream: &mut TcpStream, data: &[u8]) -> Vec<u8> {
+        stream.write_all(data).await.unwrap();
+        let mut buf = vec![0u8; data.len()];
+        stream.read_exact(&mut buf).await.unwrap();
+        buf
+    }
+
+    #[tokio::test]
+    async fn test_replace_handler() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(RewriteTcp { upper: false });
+        let handle = supervisor.handle();
+        let ready = supervisor.ready();
+        let run = tokio::spawn(supervisor.run());
+        ready.await.unwrap();
+        let addr = handle.listening_addrs("RewriteTcp").unwrap()[0];
+
+        let mut old = TcpStream::connect(addr).await.unwrap();
+        assert_eq!(exchange(&mut old, b"hi").await, b"hi");
+
+        assert!(matches!(
+            handle.replace_handler("RewriteTcp", MockTcp),
+            Err(Error::HandlerMismatch(_))
+        ));
+        handle
+            .replace_handler("RewriteTcp", RewriteTcp { upper: true })
+            .unwrap();
+
+        let mut new = TcpStream::connect(addr).await.unwrap();
+        assert_eq!(exchange(&mut new, b"hi").await, b"HI");
+        assert_eq!(exchange(&mut old, b"hi").await, b"hi");
+        assert_eq!(handle.listening_addrs("RewriteTcp").unwrap(), [addr]);
+
+        drop((old, new));
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_listening_addrs() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(LocalTcp);
+        let handle = supervisor.handle();
+
+        assert!(handle.listening_addrs("LocalTcp").unwrap().is_empty());
+        assert!(matches!(
+            handle.listening_addrs("Missing"),
+            Err(Error::UnknownService(_))
+        ));
+
+        let run = tokio::spawn(supervisor.run());
+        let addrs = timeout(Duration::from_secs(2), async {
+            loop {
+                let addrs = handle.listening_addrs("LocalTcp").unwrap();
+                if !addrs.is_empty() {
+                    break addrs;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(addrs.len(), 1);
+        assert_ne!(addrs[0].port(), 0);
+        let mut stream = TcpStream::connect(addrs[0]).await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello");
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+        assert!(handle.listening_addrs("LocalTcp").unwrap().is_empty());
+    }
+
+    struct PortTcp(u16);
+    #[async_trait]
+    impl TcpHandler for PortTcp {
+        fn name(&self) -> &'static str {
+            "PortTcp"
+        }
+        fn port(&self) -> u16 {
+            self.0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            _s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_fails_when_service_never_binds() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = taken.local_addr().unwrap().port();
+
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let policy = RestartPolicy::default()
+            .with_max_attempts(2)
+            .with_delay(Duration::from_millis(1));
+        let mut supervisor = Supervisor::with_policy(iface, policy);
+        supervisor.add(PortTcp(port));
+        supervisor.add(LocalTcp);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        // Each attempt retries the taken port briefly before giving up.
+        sleep(Duration::from_millis(1000)).await;
+        handle.shutdown();
+        let res = run.await.unwrap();
+        assert!(matches!(res, Err(Error::ServiceFailure(name)) if name == "PortTcp"));
+    }
+
+    #[tokio::test]
+    async fn test_report_exit_codes() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface.clone());
+        supervisor.add(LocalTcp);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run_with_report());
+        sleep(Duration::from_millis(50)).await;
+        handle.shutdown();
+        let report = run.await.unwrap().unwrap();
+        assert!(report.is_graceful());
+        assert_eq!(report.exit_code(), 0);
+
+        let mut supervisor = Supervisor::new(iface).with_grace_period(Duration::from_millis(50));
+        supervisor.add(LocalTcp);
+        supervisor.tasks.push(Box::new(HungTask(state("HungTask"))));
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run_with_report());
+        sleep(Duration::from_millis(50)).await;
+        handle.shutdown();
+        let report = run.await.unwrap().unwrap();
+        assert_eq!(report.aborted(), ["HungTask"]);
+        assert_ne!(report.exit_code(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_force_shutdown_hook_names_hung_services() {
+        let hung = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface)
+            .with_grace_period(Duration::from_millis(50))
+            .on_force_shutdown({
+                let hung = hung.clone();
+                move |names| hung.lock().unwrap().extend_from_slice(names)
+            });
+        supervisor.add(LocalTcp);
+        supervisor.tasks.push(Box::new(HungTask(state("HungTask"))));
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+        sleep(Duration::from_millis(50)).await;
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+
+        assert_eq!(*hung.lock().unwrap(), ["HungTask"]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_interface_holds_instead_of_restarting() {
+        let starts = Arc::new(AtomicUsize::new(0));
+        let policy = RestartPolicy::default()
+            .with_max_attempts(1)
+            .with_delay(Duration::from_millis(1));
+        let task = SupervisedTask::new(state("gone"), policy, {
+            let starts = starts.clone();
+            move || {
+                let starts = starts.clone();
+                Box::pin(async move {
+                    starts.fetch_add(1, Ordering::SeqCst);
+                    Err(Error::InterfaceGone("maestro-gone0".into()))
+                })
+            }
+        });
+        let task_state = task.state().clone();
+        let ctx = TaskContext::default();
+        let token = ctx.token.clone();
+        let run = tokio::spawn(task.run(ctx));
+
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(starts.load(Ordering::SeqCst), 1);
+        assert!(!task_state.is_dead());
+
+        token.cancel();
+        timeout(Duration::from_secs(1), run).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_holds_connections_until_resume() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(LocalTcp);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        let addr = timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(addr) = handle.listening_addrs("LocalTcp").unwrap().first() {
+                    break *addr;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        handle.pause("LocalTcp").unwrap();
+        sleep(Duration::from_millis(20)).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0u8; 5];
+        assert!(
+            timeout(Duration::from_millis(200), stream.read_exact(&mut buf))
+                .await
+                .is_err()
+        );
+        assert_eq!(handle.listening_addrs("LocalTcp").unwrap(), [addr]);
+
+        handle.resume("LocalTcp").unwrap();
+        timeout(Duration::from_secs(2), stream.read_exact(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(&buf, b"hello");
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    /// A task recording whether `LocalTcp` was listening when it started.
+    struct ProbeTask {
+        state: Arc<ServiceState>,
+        handle: SupervisorHandle,
+        saw_listening: Arc<std::sync::Mutex<Option<bool>>>,
+    }
+    impl Task for ProbeTask {
+        fn state(&self) -> &Arc<ServiceState> {
+            &self.state
+        }
+        fn run(&self, _ctx: TaskContext) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+            let listening = !self.handle.listening_addrs("LocalTcp").unwrap().is_empty();
+            *self.saw_listening.lock().unwrap() = Some(listening);
+            Box::pin(std::future::pending())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_phases_start_after_earlier_phases_bind() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        let handle = supervisor.handle();
+        let saw_listening = Arc::new(std::sync::Mutex::new(None));
+        supervisor.tasks.push(Box::new(ProbeTask {
+            state: Arc::new(ServiceState::new("Probe", Transport::Tcp).with_phase(2)),
+            handle: handle.clone(),
+            saw_listening: saw_listening.clone(),
+        }));
+        supervisor.add_in_phase(1, LocalTcp);
+        let run = tokio::spawn(supervisor.run());
+
+        sleep(Duration::from_millis(100)).await;
+        assert_eq!(*saw_listening.lock().unwrap(), Some(true));
+
+        handle.shutdown_now();
+        run.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_labels_in_snapshot() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        let labels = HashMap::from([
+            ("tier".to_string(), "edge".to_string()),
+            ("team".to_string(), "net".to_string()),
+        ]);
+        supervisor.add_with_labels(MockTcp, labels.clone());
+        supervisor.add_named("plain", MockTcp);
+
+        let snapshot = supervisor.handle().snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].name, "MockTcp");
+        assert_eq!(snapshot[0].labels, labels);
+        assert!(snapshot[0].listening.is_empty());
+        assert!(snapshot[1].labels.is_empty());
+    }
+
+    #[test]
+    fn test_policy_debug_lists_fields() {
+        let policy = RestartPolicy::default().with_max_attempts(3);
+        assert_eq!(
+            format!("{policy:?}"),
+            "RestartPolicy { max_attempts: Some(3), base_delay: 1s, min_healthy_duration: None, \
+             min_restart_interval: 10ms }"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ready_after_bind() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(LocalTcp);
+        let handle = supervisor.handle();
+        let ready = supervisor.ready();
+        let run = tokio::spawn(supervisor.run());
+
+        timeout(Duration::from_secs(2), ready)
+            .await
+            .unwrap()
+            .unwrap();
+        let addr = handle.listening_addrs("LocalTcp").unwrap()[0];
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut buf = Vec::new();
+        stream.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, b"hello");
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_one_shot_task_is_not_restarted() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let policy = RestartPolicy::default().with_delay(Duration::from_millis(10));
+        let mut supervisor = Supervisor::with_policy(iface, policy);
+        let runs = Arc::new(AtomicUsize::new(0));
+        supervisor.add_task("warmup", {
+            let runs = runs.clone();
+            move || {
+                let runs = runs.clone();
+                async move {
+                    runs.fetch_add(1, Ordering::SeqCst);
+                    Ok(())
+                }
+            }
+        });
+        let handle = supervisor.handle();
+        let ready = supervisor.ready();
+        let run = tokio::spawn(supervisor.run());
+
+        timeout(Duration::from_secs(2), ready)
+            .await
+            .unwrap()
+            .unwrap();
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(!handle.snapshot()[0].dead);
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    /// Runs the last task added to `supervisor` until the (paused) clock reaches `until`.
+    async fn run_last_task(mut supervisor: Supervisor, until: Duration) {
+        let task = supervisor.tasks.pop().unwrap();
+        let token = CancellationToken::new();
+        let ctx = TaskContext {
+            token: token.clone(),
+            ..Default::default()
+        };
+        let run = tokio::spawn(async move { task.run(ctx).await });
+        sleep(until).await;
+        token.cancel();
+        run.await.unwrap();
+    }
+
+    fn counting_task(
+        runs: &Arc<AtomicUsize>,
+        fail: bool,
+    ) -> impl Fn() -> std::future::Ready<io::Result<()>> + Send + Sync + 'static {
+        let runs = runs.clone();
+        move || {
+            runs.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(if fail {
+                Err(io::Error::other("flush failed"))
+            } else {
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_interval_fires_every_period() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        let runs = Arc::new(AtomicUsize::new(0));
+        supervisor.add_interval(
+            "flush",
+            Duration::from_secs(10),
+            counting_task(&runs, false),
+        );
+
+        // Runs at 0s, 10s, 20s and 30s.
+        run_last_task(supervisor, Duration::from_secs(35)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_missed_ticks_skipped_or_burst() {
+        async fn runs_after_stall(behavior: MissedTickBehavior) -> usize {
+            let iface = NetworkInterface::from_str("lo").unwrap();
+            let mut supervisor = Supervisor::new(iface);
+            let runs = Arc::new(AtomicUsize::new(0));
+            let schedule =
+                Schedule::every(Duration::from_secs(10)).with_missed_tick_behavior(behavior);
+            supervisor.add_interval("health", schedule, {
+                let runs = runs.clone();
+                move || {
+                    // The first run stalls past three periods.
+                    let stall = runs.fetch_add(1, Ordering::SeqCst) == 0;
+                    async move {
+                        if stall {
+                            sleep(Duration::from_secs(35)).await;
+                        }
+                        Ok(())
+                    }
+                }
+            });
+            run_last_task(supervisor, Duration::from_secs(41)).await;
+            runs.load(Ordering::SeqCst)
+        }
+
+        // Burst catches up on 10s, 20s and 30s at 35s, then runs at 40s.
+        assert_eq!(runs_after_stall(MissedTickBehavior::Burst).await, 5);
+        // Skip runs once at 35s, then at 40s.
+        assert_eq!(runs_after_stall(MissedTickBehavior::Skip).await, 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_interval_errors_restart_unless_skipped() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let policy = RestartPolicy::default().with_delay(Duration::from_secs(60));
+
+        let mut supervisor = Supervisor::with_policy(iface.clone(), policy);
+        let runs = Arc::new(AtomicUsize::new(0));
+        supervisor.add_interval("poll", Duration::from_secs(10), counting_task(&runs, true));
+        run_last_task(supervisor, Duration::from_secs(35)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        let mut supervisor = Supervisor::with_policy(iface, policy);
+        let runs = Arc::new(AtomicUsize::new(0));
+        let schedule = Schedule::every(Duration::from_secs(10)).with_skip_errors(true);
+        supervisor.add_interval("poll", schedule, counting_task(&runs, true));
+        run_last_task(supervisor, Duration::from_secs(35)).await;
+        assert_eq!(runs.load(Ordering::SeqCst), 4);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_restart_policy_serde_round_trip() {
+        let policy = RestartPolicy {
+            max_attempts: Some(3),
+            base_delay: Duration::from_millis(1500),
+            min_healthy_duration: None,
+            min_restart_interval: Duration::ZERO,
+        };
+        let json = serde_json::to_string(&policy).unwrap();
+        assert_eq!(
+            json,
+            r#"{"max_attempts":3,"base_delay":1.5,"min_restart_interval":0.0}"#
+        );
+        let back: RestartPolicy = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.max_attempts, Some(3));
+        assert_eq!(back.base_delay, Duration::from_millis(1500));
+
+        let unbounded: RestartPolicy =
+            serde_json::from_str(r#"{"max_attempts":null,"base_delay":2}"#).unwrap();
+        assert_eq!(unbounded.max_attempts, None);
+        assert_eq!(unbounded.base_delay, Duration::from_secs(2));
+
+        let defaults: RestartPolicy = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.max_attempts, RestartPolicy::default().max_attempts);
+        assert_eq!(defaults.base_delay, RestartPolicy::default().base_delay);
+
+        assert!(serde_json::from_str::<RestartPolicy>(r#"{"base_delay":-1}"#).is_err());
+
+        let healthy: RestartPolicy =
+            serde_json::from_str(r#"{"min_healthy_duration":30}"#).unwrap();
+        assert_eq!(healthy.min_healthy_duration, Some(Duration::from_secs(30)));
+        let json = serde_json::to_string(&healthy).unwrap();
+        assert_eq!(
+            json,
+            r#"{"max_attempts":5,"base_delay":1.0,"min_healthy_duration":30.0,"min_restart_interval":0.01}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_isolated_tasks_run_on_their_own_runtimes() {
+        use tokio::runtime::{Handle, RuntimeFlavor};
+
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let isolations = [
+            ("single", Isolation::CurrentThread),
+            ("multi", Isolation::MultiThread { worker_threads: 2 }),
+        ];
+        for (name, isolation) in isolations {
+            let tx = tx.clone();
+            supervisor.add_isolated_task(name, isolation, move || {
+                let tx = tx.clone();
+                async move {
+                    let thread = std::thread::current().name().map(str::to_string);
+                    let _ = tx.send((name, thread, Handle::current().runtime_flavor()));
+                    std::future::pending().await
+                }
+            });
+        }
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        let mut seen = Vec::new();
+        for _ in 0..2 {
+            seen.push(
+                timeout(Duration::from_secs(1), rx.recv())
+                    .await
+                    .unwrap()
+                    .unwrap(),
+            );
+        }
+        seen.sort_by_key(|(name, ..)| *name);
+        assert_eq!(
+            seen,
+            [
+                (
+                    "multi",
+                    Some("maestro-multi".to_string()),
+                    RuntimeFlavor::MultiThread
+                ),
+                (
+                    "single",
+                    Some("maestro-single".to_string()),
+                    RuntimeFlavor::CurrentThread
+                ),
+            ]
+        );
+
+        handle.shutdown();
+        timeout(Duration::from_secs(2), run)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_empty_supervisor_returns_immediately() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let supervisor = Supervisor::new(iface);
+        let report = timeout(Duration::from_secs(1), supervisor.run_with_report())
+            .await
+            .expect("an empty supervisor must not wait for a signal")
+            .unwrap();
+        assert!(report.is_graceful());
+    }
+
+    #[tokio::test]
+    async fn test_events_follow_crash_and_restart() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let policy = RestartPolicy::default()
+            .with_max_attempts(2)
+            .with_delay(Duration::from_millis(10));
+        let mut supervisor = Supervisor::with_policy(iface, policy);
+        supervisor.add_task("migrate", || async { Err(io::Error::other("boom")) });
+        let mut events = supervisor.events();
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        let service = || "migrate".to_string();
+        let crashed = || SupervisionEvent::Crashed {
+            service: service(),
+            error: "IO operation failed: boom".to_string(),
+        };
+        let expected = [
+            SupervisionEvent::Started { service: service() },
+            crashed(),
+            SupervisionEvent::RestartScheduled {
+                service: service(),
+                attempt: 1,
+                delay: Duration::from_millis(10),
+            },
+            SupervisionEvent::Started { service: service() },
+            crashed(),
+            SupervisionEvent::Abandoned { service: service() },
+        ];
+        // Health changes are covered by `test_health_follows_crash_and_restart`.
+        let mut next = async || loop {
+            let received = timeout(Duration::from_secs(1), events.recv()).await;
+            match received.unwrap().unwrap() {
+                SupervisionEvent::HealthChanged { .. } => {}
+                event => return event,
+            }
+        };
+        for event in expected {
+            assert_eq!(next().await, event);
+        }
+
+        handle.shutdown();
+        assert_eq!(next().await, SupervisionEvent::ShutdownRequested);
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_health_follows_crash_and_restart() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let policy = RestartPolicy::default()
+            .with_max_attempts(2)
+            .with_delay(Duration::from_millis(10));
+        let mut supervisor = Supervisor::with_policy(iface, policy);
+        supervisor.add_task("migrate", || async { Err(io::Error::other("boom")) });
+        let mut events = supervisor.events();
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        let expected = [
+            WorkerHealth::Healthy,
+            WorkerHealth::Restarting,
+            WorkerHealth::Starting,
+            WorkerHealth::Healthy,
+            WorkerHealth::Dead,
+        ];
+        for expected in expected {
+            let health = loop {
+                let received = timeout(Duration::from_secs(1), events.recv()).await;
+                if let SupervisionEvent::HealthChanged { service, health } =
+                    received.unwrap().unwrap()
+                {
+                    assert_eq!(service, "migrate");
+                    break health;
+                }
+            };
+            assert_eq!(health, expected);
+        }
+        assert_eq!(handle.snapshot()[0].health, WorkerHealth::Dead);
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_add_on_eligible_interfaces() {
+        let iface = |name: &str, is_up, is_loopback| NetworkInterface {
+            name: name.into(),
+            index: 0,
+            inet: vec!["127.0.0.1".parse().unwrap()],
+            inet6: vec![],
+            mac: None,
+            mac_is_generated: false,
+            is_up,
+            is_loopback,
+            supports_multicast: false,
+            mtu: None,
+        };
+        let ifaces = vec![
+            iface("lo", true, true),
+            iface("eth0", true, false),
+            iface("eth1", false, false),
+            iface("eth2", true, false),
+        ];
+
+        let mut supervisor = Supervisor::new(NetworkInterface::from_str("lo").unwrap());
+        let mut seen = Vec::new();
+        let added = supervisor.add_on_interfaces(ifaces, |iface| {
+            seen.push(iface.name.clone());
+            MockTcp
+        });
+
+        assert_eq!(added, 2);
+        assert_eq!(seen, ["eth0", "eth2"]);
+        let labels: Vec<_> = supervisor
+            .handle()
+            .snapshot()
+            .into_iter()
+            .map(|s| s.labels["interface"].clone())
+            .collect();
+        assert_eq!(labels, ["eth0", "eth2"]);
+    }
+
+    struct EchoTcp;
+    #[async_trait]
+    impl TcpHandler for EchoTcp {
+        fn name(&self) -> &'static str {
+            "EchoTcp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            _token: CancellationToken,
+        ) {
+            let mut buf = [0u8; 64];
+            while let Ok(n @ 1..) = s.read(&mut buf).await {
+                let _ = s.write_all(&buf[..n]).await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_drain_stops_accepting_but_keeps_connections() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(EchoTcp);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+
+        let addr = timeout(Duration::from_secs(2), async {
+            loop {
+                if let Some(addr) = handle.listening_addrs("EchoTcp").unwrap().first() {
+                    break *addr;
+                }
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let mut existing = TcpStream::connect(addr).await.unwrap();
+        let mut buf = [0u8; 4];
+        existing.write_all(b"ping").await.unwrap();
+        existing.read_exact(&mut buf).await.unwrap();
+
+        handle.drain();
+        sleep(Duration::from_millis(20)).await;
+        assert!(handle.snapshot().iter().all(|s| s.paused));
+
+        existing.write_all(b"pong").await.unwrap();
+        existing.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        let mut fresh = TcpStream::connect(addr).await.unwrap();
+        fresh.write_all(b"ping").await.unwrap();
+        let pending = timeout(Duration::from_millis(200), fresh.read_exact(&mut buf)).await;
+        assert!(pending.is_err());
+
+        handle.shutdown_now();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_connection_budget_is_shared_by_services() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let budget = Arc::new(ConnectionBudget::new(2));
+        let mut supervisor = Supervisor::new(iface).with_connection_budget(budget.clone());
+        supervisor.add_named("first", EchoTcp);
+        supervisor.add_named("second", EchoTcp);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+        handle.ready().await.unwrap();
+        let first = handle.listening_addrs("first").unwrap()[0];
+        let second = handle.listening_addrs("second").unwrap()[0];
+
+        let mut buf = [0u8; 4];
+        let mut open = Vec::new();
+        for addr in [first, second] {
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream.write_all(b"ping").await.unwrap();
+            stream.read_exact(&mut buf).await.unwrap();
+            open.push(stream);
+        }
+        assert_eq!(budget.open(), 2);
+
+        // Both services share the budget: the third connection is closed at once.
+        let mut third = TcpStream::connect(first).await.unwrap();
+        let n = timeout(Duration::from_secs(1), third.read(&mut buf))
+            .await
+            .unwrap();
+        assert!(matches!(n, Ok(0) | Err(_)));
+        assert_eq!(budget.rejected(), 1);
+
+        // Closing a connection frees its slot.
+        drop(open.pop());
+        while budget.open() > 1 {
+            sleep(Duration::from_millis(10)).await;
+        }
+        let mut fourth = TcpStream::connect(second).await.unwrap();
+        fourth.write_all(b"pong").await.unwrap();
+        fourth.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"pong");
+
+        handle.shutdown_now();
+        run.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_parse_fd_limit() {
+        let limits = "Limit                     Soft Limit           Hard Limit           Units\n\
+                      Max cpu time              unlimited            unlimited            seconds\n\
+                      Max open files            1024                 524288               files\n";
+        assert_eq!(parse_fd_limit(limits), Some(1024));
+        assert_eq!(
+            parse_fd_limit(
+                "Max open files            unlimited            unlimited            files"
+            ),
+            Some(usize::MAX)
+        );
+        assert_eq!(parse_fd_limit("Max processes 10 10 processes"), None);
+
+        #[cfg(target_os = "linux")]
+        assert!(ConnectionBudget::from_fd_limit(64).is_ok());
+    }
+
+    /// Greets, then holds the connection until cancelled, unless `stubborn`.
+    struct LingeringTcp {
+        stubborn: bool,
+    }
+    #[async_trait]
+    impl TcpHandler for LingeringTcp {
+        fn name(&self) -> &'static str {
+            if self.stubborn { "Stubborn" } else { "Polite" }
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        fn bind_mode(&self) -> BindMode {
+            BindMode::Specific("127.0.0.1".parse().unwrap())
+        }
+        async fn on_connection(
+            &self,
+            mut s: TcpStream,
+            _p: &SocketAddr,
+            _local: &SocketAddr,
+            token: CancellationToken,
+        ) {
+            let _ = s.write_all(b"hi").await;
+            if self.stubborn {
+                std::future::pending::<()>().await;
+            }
+            token.cancelled().await;
+            let _ = s.write_all(b"bye").await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_escalates_from_cancel_to_abort() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface)
+            .with_grace_period(Duration::from_millis(50))
+            .with_kill_timeout(Duration::from_millis(300));
+        supervisor.add(LingeringTcp { stubborn: false });
+        supervisor.add(LingeringTcp { stubborn: true });
+        let handle = supervisor.handle();
+        let ready = supervisor.ready();
+        let run = tokio::spawn(supervisor.run_with_report());
+        ready.await.unwrap();
+
+        let mut clients = Vec::new();
+        for name in ["Polite", "Stubborn"] {
+            let addr = handle.listening_addrs(name).unwrap()[0];
+            let mut client = TcpStream::connect(addr).await.unwrap();
+            let mut buf = [0u8; 2];
+            client.read_exact(&mut buf).await.unwrap();
+            clients.push(client);
+        }
+
+        let start = Instant::now();
+        handle.shutdown();
+        let report = run.await.unwrap().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(350));
+        assert_eq!(report.aborted(), ["Stubborn"]);
+
+        // The polite handler said goodbye once its token was cancelled, while the
+        // stubborn one was cut off.
+        let mut replies = Vec::new();
+        for mut client in clients {
+            let mut reply = Vec::new();
+            timeout(Duration::from_secs(1), client.read_to_end(&mut reply))
+                .await
+                .unwrap()
+                .unwrap();
+            replies.push(reply);
+        }
+        assert_eq!(replies, [b"bye".to_vec(), Vec::new()]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_snapshot_reports_accept_queue_depth() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(LocalTcp);
+        let handle = supervisor.handle();
+        let run = tokio::spawn(supervisor.run());
+        handle.ready().await.unwrap();
+        let addr = handle.listening_addrs("LocalTcp").unwrap()[0];
+        assert_eq!(handle.snapshot()[0].accept_queue_depth, Some(0));
+
+        // A paused service leaves new connections in the accept queue.
+        handle.pause("LocalTcp").unwrap();
+        sleep(Duration::from_millis(20)).await;
+        let _clients = [
+            TcpStream::connect(addr).await.unwrap(),
+            TcpStream::connect(addr).await.unwrap(),
+        ];
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(handle.snapshot()[0].accept_queue_depth, Some(2));
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_inactive_service_binds_once_activated() {
+        let iface = NetworkInterface::from_str("lo").unwrap();
+        let mut supervisor = Supervisor::new(iface);
+        supervisor.add(EchoTcp);
+        supervisor.add_inactive(LocalTcp);
+        let handle = supervisor.handle();
+        let ready = supervisor.ready();
+        let run = tokio::spawn(supervisor.run());
+
+        // Readiness does not wait for the inactive service, which holds no socket.
+        ready.await.unwrap();
+        sleep(Duration::from_millis(50)).await;
+        assert!(handle.listening_addrs("LocalTcp").unwrap().is_empty());
+        let snapshot = handle.snapshot();
+        assert!(snapshot.iter().any(|s| s.name == "LocalTcp" && !s.active));
+
+        handle.activate("LocalTcp").unwrap();
+        handle.ready().await.unwrap();
+        let addr = handle.listening_addrs("LocalTcp").unwrap()[0];
+        TcpStream::connect(addr).await.unwrap();
+        assert!(matches!(
+            handle.activate("Missing"),
+            Err(Error::UnknownService(_))
+        ));
+
+        handle.shutdown();
+        run.await.unwrap().unwrap();
+    }
 }
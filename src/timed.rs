@@ -0,0 +1,460 @@
+use async_trait::async_trait;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, Instant},
+};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio_util::sync::CancellationToken;
+
+use crate::{
+    BindMode, ConnectionId, IpFamily, PacketInfo, ServiceContext, SocketFailurePolicy,
+    TcpConcurrency, TcpHandler, UdpHandler,
+};
+
+/// Values below this many microseconds get one bucket each.
+const LINEAR_BUCKETS: usize = 16;
+/// Sub-buckets per power of two above the linear range (3 bits, ~12% precision).
+const SUB_BUCKET_BITS: u32 = 3;
+const BUCKETS: usize = LINEAR_BUCKETS + (64 - 4) * (1 << SUB_BUCKET_BITS);
+
+/// A lock-free, log-linear histogram of durations with microsecond resolution.
+///
+/// Recorded values are accurate to within roughly 12%, which is plenty for
+/// percentile reporting without pulling in a full HDR histogram.
+#[derive(Debug)]
+pub struct LatencyHistogram {
+    buckets: Box<[AtomicU64]>,
+}
+
+/// A point-in-time summary of a [`LatencyHistogram`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySnapshot {
+    /// Number of recorded samples.
+    pub count: u64,
+    /// Median latency.
+    pub p50: Duration,
+    /// 99th percentile latency.
+    pub p99: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LatencyHistogram {
+    /// Creates an empty histogram.
+    pub fn new() -> Self {
+        Self {
+            buckets: (0..BUCKETS).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    /// Records a single duration.
+    pub fn record(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        self.buckets[bucket_index(micros)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the total number of recorded samples.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(|b| b.load(Ordering::Relaxed)).sum()
+    }
+
+    /// Returns the value at quantile `q` (between `0.0` and `1.0`), or `None` if empty.
+    pub fn percentile(&self, q: f64) -> Option<Duration> {
+        let total = self.count();
+        if total == 0 {
+            return None;
+        }
+
+        let rank = ((q.clamp(0.0, 1.0) * total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (idx, bucket) in self.buckets.iter().enumerate() {
+            seen += bucket.load(Ordering::Relaxed);
+            if seen >= rank {
+                return Some(Duration::from_micros(bucket_midpoint(idx)));
+            }
+        }
+        None
+    }
+
+    /// Returns the sample count along with p50 and p99.
+    pub fn snapshot(&self) -> LatencySnapshot {
+        LatencySnapshot {
+            count: self.count(),
+            p50: self.percentile(0.50).unwrap_or_default(),
+            p99: self.percentile(0.99).unwrap_or_default(),
+        }
+    }
+}
+
+fn bucket_index(micros: u64) -> usize {
+    if micros < LINEAR_BUCKETS as u64 {
+        return micros as usize;
+    }
+    let exp = 63 - micros.leading_zeros();
+    let sub = (micros >> (exp - SUB_BUCKET_BITS)) & ((1 << SUB_BUCKET_BITS) - 1);
+    LINEAR_BUCKETS + ((exp - 4) << SUB_BUCKET_BITS) as usize + sub as usize
+}
+
+fn bucket_midpoint(idx: usize) -> u64 {
+    if idx < LINEAR_BUCKETS {
+        return idx as u64;
+    }
+    let offset = (idx - LINEAR_BUCKETS) as u32;
+    let exp = (offset >> SUB_BUCKET_BITS) + 4;
+    let sub = (offset & ((1 << SUB_BUCKET_BITS) - 1)) as u64;
+    let width = 1u64 << (exp - SUB_BUCKET_BITS);
+    (1u64 << exp) + sub * width + width / 2
+}
+
+/// Wraps a [`TcpHandler`] or [`UdpHandler`] and records how long each
+/// `on_connection`/`on_packet` call takes.
+///
+/// Keep a reference to the histogram via [`Timed::histogram`] before handing
+/// the wrapper to the supervisor to read latencies while it runs.
+#[derive(Debug)]
+pub struct Timed<H> {
+    inner: H,
+    histogram: Arc<LatencyHistogram>,
+}
+
+impl<H> Timed<H> {
+    /// Wraps `inner` with an empty latency histogram.
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            histogram: Arc::new(LatencyHistogram::new()),
+        }
+    }
+
+    /// Returns a shared reference to the underlying histogram.
+    pub fn histogram(&self) -> Arc<LatencyHistogram> {
+        self.histogram.clone()
+    }
+
+    /// Returns the current latency summary.
+    pub fn latency(&self) -> LatencySnapshot {
+        self.histogram.snapshot()
+    }
+
+    /// Returns a reference to the wrapped handler.
+    pub fn inner(&self) -> &H {
+        &self.inner
+    }
+}
+
+#[async_trait]
+impl<H: TcpHandler> TcpHandler for Timed<H> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn port(&self) -> u16 {
+        self.inner.port()
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        self.inner.bind_mode()
+    }
+
+    fn v6_only(&self) -> bool {
+        self.inner.v6_only()
+    }
+
+    fn preferred_family(&self) -> Option<IpFamily> {
+        self.inner.preferred_family()
+    }
+
+    fn freebind(&self) -> bool {
+        self.inner.freebind()
+    }
+
+    fn transparent(&self) -> bool {
+        self.inner.transparent()
+    }
+
+    fn network_namespace(&self) -> Option<&str> {
+        self.inner.network_namespace()
+    }
+
+    fn strict_interface(&self) -> bool {
+        self.inner.strict_interface()
+    }
+
+    fn configure_socket(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        self.inner.configure_socket(socket)
+    }
+
+    fn log_rate_limit(&self) -> Option<Duration> {
+        self.inner.log_rate_limit()
+    }
+
+    fn accept_shards(&self) -> usize {
+        self.inner.accept_shards()
+    }
+
+    fn accept_batch(&self) -> usize {
+        self.inner.accept_batch()
+    }
+
+    fn max_connections(&self) -> Option<usize> {
+        self.inner.max_connections()
+    }
+
+    fn concurrency(&self) -> TcpConcurrency {
+        self.inner.concurrency()
+    }
+
+    fn max_in_flight(&self) -> Option<usize> {
+        self.inner.max_in_flight()
+    }
+
+    fn accept_deadline(&self) -> Option<Duration> {
+        self.inner.accept_deadline()
+    }
+
+    fn exit_on_accept_deadline(&self) -> bool {
+        self.inner.exit_on_accept_deadline()
+    }
+
+    fn liveness_timeout(&self) -> Option<Duration> {
+        self.inner.liveness_timeout()
+    }
+
+    fn accept_stall_timeout(&self) -> Option<Duration> {
+        self.inner.accept_stall_timeout()
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.inner.connection_id()
+    }
+
+    async fn on_connection(
+        &self,
+        stream: TcpStream,
+        peer: &SocketAddr,
+        local: &SocketAddr,
+        token: CancellationToken,
+    ) {
+        let start = Instant::now();
+        self.inner.on_connection(stream, peer, local, token).await;
+        self.histogram.record(start.elapsed());
+    }
+
+    async fn on_reject(&self, stream: TcpStream, peer: &SocketAddr) {
+        self.inner.on_reject(stream, peer).await;
+    }
+
+    async fn on_started(&self, ctx: &ServiceContext) {
+        self.inner.on_started(ctx).await;
+    }
+
+    async fn on_shutdown(&self) {
+        self.inner.on_shutdown().await;
+    }
+}
+
+#[async_trait]
+impl<H: UdpHandler> UdpHandler for Timed<H> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn port(&self) -> u16 {
+        self.inner.port()
+    }
+
+    fn bind_mode(&self) -> BindMode {
+        self.inner.bind_mode()
+    }
+
+    fn v6_only(&self) -> bool {
+        self.inner.v6_only()
+    }
+
+    fn preferred_family(&self) -> Option<IpFamily> {
+        self.inner.preferred_family()
+    }
+
+    fn freebind(&self) -> bool {
+        self.inner.freebind()
+    }
+
+    fn transparent(&self) -> bool {
+        self.inner.transparent()
+    }
+
+    fn network_namespace(&self) -> Option<&str> {
+        self.inner.network_namespace()
+    }
+
+    fn strict_interface(&self) -> bool {
+        self.inner.strict_interface()
+    }
+
+    fn configure_socket(&self, socket: &socket2::Socket) -> std::io::Result<()> {
+        self.inner.configure_socket(socket)
+    }
+
+    fn log_rate_limit(&self) -> Option<Duration> {
+        self.inner.log_rate_limit()
+    }
+
+    fn multicast_addrs(&self) -> &[IpAddr] {
+        self.inner.multicast_addrs()
+    }
+
+    fn multicast_join_required(&self) -> bool {
+        self.inner.multicast_join_required()
+    }
+
+    fn require_multicast(&self) -> bool {
+        self.inner.require_multicast()
+    }
+
+    fn broadcast(&self) -> bool {
+        self.inner.broadcast()
+    }
+
+    fn max_datagram_size(&self) -> Option<usize> {
+        self.inner.max_datagram_size()
+    }
+
+    fn max_in_flight(&self) -> Option<usize> {
+        self.inner.max_in_flight()
+    }
+
+    fn pin_shards(&self) -> bool {
+        self.inner.pin_shards()
+    }
+
+    fn buffer_from_mtu(&self) -> bool {
+        self.inner.buffer_from_mtu()
+    }
+
+    fn recv_ecn(&self) -> bool {
+        self.inner.recv_ecn()
+    }
+
+    fn concurrent_packets(&self) -> usize {
+        self.inner.concurrent_packets()
+    }
+
+    fn on_socket_failure(&self) -> SocketFailurePolicy {
+        self.inner.on_socket_failure()
+    }
+
+    fn liveness_timeout(&self) -> Option<Duration> {
+        self.inner.liveness_timeout()
+    }
+
+    fn connection_id(&self) -> ConnectionId {
+        self.inner.connection_id()
+    }
+
+    async fn on_packet(
+        &self,
+        data: &[u8],
+        socket: Arc<UdpSocket>,
+        peer: &SocketAddr,
+        info: &PacketInfo,
+    ) {
+        let start = Instant::now();
+        self.inner.on_packet(data, socket, peer, info).await;
+        self.histogram.record(start.elapsed());
+    }
+
+    async fn on_started(&self, ctx: &ServiceContext) {
+        self.inner.on_started(ctx).await;
+    }
+
+    async fn on_shutdown(&self) {
+        self.inner.on_shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_roundtrip() {
+        for micros in [0, 1, 15, 16, 31, 32, 1_000, 20_000, 1_000_000, u64::MAX] {
+            let idx = bucket_index(micros);
+            assert!(idx < BUCKETS);
+            let mid = bucket_midpoint(idx) as f64;
+            let err = (mid - micros as f64).abs() / (micros.max(1) as f64);
+            assert!(err <= 0.125, "{micros} -> {mid}");
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = LatencyHistogram::new();
+        assert_eq!(histogram.percentile(0.5), None);
+
+        for _ in 0..99 {
+            histogram.record(Duration::from_millis(1));
+        }
+        histogram.record(Duration::from_millis(500));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(snapshot.count, 100);
+        assert!(snapshot.p50 < Duration::from_millis(2));
+        assert!(snapshot.p99 < Duration::from_millis(2));
+        assert!(histogram.percentile(1.0).unwrap() > Duration::from_millis(400));
+    }
+
+    struct SleepyUdp;
+    #[async_trait]
+    impl UdpHandler for SleepyUdp {
+        fn name(&self) -> &'static str {
+            "SleepyUdp"
+        }
+        fn port(&self) -> u16 {
+            0
+        }
+        async fn on_packet(
+            &self,
+            _data: &[u8],
+            _socket: Arc<UdpSocket>,
+            _peer: &SocketAddr,
+            _info: &PacketInfo,
+        ) {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_timed_median_near_handler_duration() {
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let peer = socket.local_addr().unwrap();
+        let timed = Timed::new(SleepyUdp);
+
+        for _ in 0..5 {
+            timed
+                .on_packet(b"ping", socket.clone(), &peer, &PacketInfo::new(4, peer))
+                .await;
+        }
+
+        let latency = timed.latency();
+        assert_eq!(latency.count, 5);
+        assert!(
+            latency.p50 >= Duration::from_millis(18),
+            "{:?}",
+            latency.p50
+        );
+        assert!(
+            latency.p50 <= Duration::from_millis(40),
+            "{:?}",
+            latency.p50
+        );
+    }
+}
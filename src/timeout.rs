@@ -0,0 +1,220 @@
+use std::{
+    future::Future,
+    io::{self, IoSlice},
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    time::{Sleep, sleep},
+};
+
+/// A stream wrapper enforcing a timeout on every individual read and write.
+///
+/// Each I/O operation that makes no progress within its timeout fails with
+/// [`io::ErrorKind::TimedOut`]. Unlike a timeout around the whole connection, a
+/// slow but steady peer is never cut off, while a stalled one is.
+///
+/// ```rust,no_run
+/// # use maestro_rs::TimeoutStream;
+/// # use std::time::Duration;
+/// # async fn handle(stream: tokio::net::TcpStream) {
+/// let stream = TimeoutStream::new(stream, Duration::from_secs(30));
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct TimeoutStream<S> {
+    inner: S,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    read_deadline: Option<Pin<Box<Sleep>>>,
+    write_deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> TimeoutStream<S> {
+    /// Wraps `inner`, applying `timeout` to both reads and writes.
+    pub fn new(inner: S, timeout: Duration) -> Self {
+        Self {
+            inner,
+            read_timeout: Some(timeout),
+            write_timeout: Some(timeout),
+            read_deadline: None,
+            write_deadline: None,
+        }
+    }
+
+    /// Sets the read timeout. `None` disables it.
+    pub fn with_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    /// Sets the write timeout. `None` disables it.
+    pub fn with_write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Returns a reference to the wrapped stream.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped stream.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    /// Consumes the wrapper, returning the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+/// Resolves a pending operation against its deadline, arming it on first use.
+fn poll_deadline<T>(
+    res: Poll<io::Result<T>>,
+    deadline: &mut Option<Pin<Box<Sleep>>>,
+    timeout: Option<Duration>,
+    cx: &mut Context<'_>,
+) -> Poll<io::Result<T>> {
+    if res.is_ready() {
+        *deadline = None;
+        return res;
+    }
+    let Some(timeout) = timeout else {
+        return Poll::Pending;
+    };
+    let sleep = deadline.get_or_insert_with(|| Box::pin(sleep(timeout)));
+    match sleep.as_mut().poll(cx) {
+        Poll::Ready(()) => {
+            *deadline = None;
+            Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "I/O operation timed out",
+            )))
+        }
+        Poll::Pending => Poll::Pending,
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for TimeoutStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        poll_deadline(res, &mut this.read_deadline, this.read_timeout, cx)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for TimeoutStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+        poll_deadline(res, &mut this.write_deadline, this.write_timeout, cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        poll_deadline(res, &mut this.write_deadline, this.write_timeout, cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let res = Pin::new(&mut this.inner).poll_flush(cx);
+        poll_deadline(res, &mut this.write_deadline, this.write_timeout, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+    #[tokio::test]
+    async fn test_read_past_timeout_fails() {
+        let (client, _server) = duplex(64);
+        let mut stream = TimeoutStream::new(client, Duration::from_millis(50));
+
+        let mut buf = [0u8; 8];
+        let err = stream.read(&mut buf).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_read_within_timeout_succeeds() {
+        let (client, mut server) = duplex(64);
+        let mut stream = TimeoutStream::new(client, Duration::from_millis(500));
+
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(20)).await;
+            server.write_all(b"ping").await.unwrap();
+        });
+
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[tokio::test]
+    async fn test_write_to_stalled_peer_fails() {
+        let (client, _server) = duplex(4);
+        let mut stream =
+            TimeoutStream::new(client, Duration::from_millis(50)).with_read_timeout(None);
+
+        let err = stream.write_all(b"more than four bytes").await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[tokio::test]
+    async fn test_vectored_writes_reach_inner_stream() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let client = tokio::net::TcpStream::connect(listener.local_addr().unwrap());
+        let (client, (mut server, _)) = tokio::try_join!(client, listener.accept()).unwrap();
+        let mut stream = TimeoutStream::new(client, Duration::from_millis(500));
+        assert!(stream.is_write_vectored());
+
+        let bufs = [IoSlice::new(b"head"), IoSlice::new(b"body")];
+        let n = stream.write_vectored(&bufs).await.unwrap();
+        // A single call takes both buffers, not just the first.
+        assert_eq!(n, 8);
+
+        let mut buf = [0u8; 8];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"headbody");
+    }
+
+    #[tokio::test]
+    async fn test_vectored_write_to_stalled_peer_fails() {
+        let (client, _server) = duplex(4);
+        let mut stream = TimeoutStream::new(client, Duration::from_millis(50));
+
+        let bufs = [IoSlice::new(b"more than"), IoSlice::new(b" four bytes")];
+        let err = crate::write_all_vectored(&mut stream, &bufs)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,177 @@
+use std::{
+    future::Future,
+    io::{self, IoSlice},
+};
+
+use tokio::{
+    io::{AsyncWrite, AsyncWriteExt},
+    time::sleep,
+};
+
+use crate::RestartPolicy;
+
+/// Runs a fallible async operation, retrying it with the supervisor's backoff.
+///
+/// The operation is attempted at most [`RestartPolicy::max_attempts`] times (forever
+/// when `None`), waiting [`RestartPolicy::delay_for_attempt`] between attempts. This
+/// lets handlers retry downstream calls (database writes, upstream connects) the same
+/// way the supervisor restarts services.
+///
+/// # Errors
+/// Returns the error of the last attempt once the attempts are exhausted.
+pub async fn retry<T, E, F, Fut>(policy: RestartPolicy, mut operation: F) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempts = 0;
+    loop {
+        match operation().await {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                attempts += 1;
+                if policy.max_attempts.is_some_and(|max| attempts >= max) {
+                    return Err(e);
+                }
+            }
+        }
+        sleep(policy.delay_for_attempt(attempts)).await;
+    }
+}
+
+/// Writes every buffer of `bufs` in order using vectored writes.
+///
+/// A header and a body can be sent without first concatenating them. Unlike a
+/// single `write_vectored` call, which may stop anywhere (even in the middle of a
+/// buffer), this keeps writing until all bytes are out.
+///
+/// # Errors
+/// Returns an error of kind [`WriteZero`](io::ErrorKind::WriteZero) if the writer
+/// stops accepting bytes, or the first I/O error it reports.
+pub async fn write_all_vectored<W>(writer: &mut W, bufs: &[IoSlice<'_>]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut storage = bufs.to_vec();
+    let mut bufs = storage.as_mut_slice();
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        let n = writer.write_vectored(bufs).await?;
+        if n == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        IoSlice::advance_slices(&mut bufs, n);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use std::{
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use tokio::time::Instant;
+
+    #[tokio::test]
+    async fn test_retry_backs_off_until_success() {
+        let policy = RestartPolicy::default().with_delay(Duration::from_millis(20));
+        let mut calls = Vec::new();
+
+        let res: Result<usize, &str> = retry(policy, || {
+            calls.push(Instant::now());
+            let n = calls.len();
+            async move { if n < 3 { Err("unavailable") } else { Ok(n) } }
+        })
+        .await;
+
+        assert_eq!(res, Ok(3));
+        let first = calls[1] - calls[0];
+        let second = calls[2] - calls[1];
+        assert!(first >= Duration::from_millis(20));
+        assert!(second >= Duration::from_millis(40));
+        assert!(second > first);
+    }
+
+    #[tokio::test]
+    async fn test_retry_returns_last_error() {
+        let policy = RestartPolicy::default()
+            .with_max_attempts(3)
+            .with_delay(Duration::from_millis(1));
+        let mut calls = 0;
+
+        let res: Result<(), usize> = retry(policy, || {
+            calls += 1;
+            let n = calls;
+            async move { Err(n) }
+        })
+        .await;
+
+        assert_eq!(res, Err(3));
+    }
+
+    /// Takes at most `chunk` bytes per vectored write, spanning buffers.
+    struct ChunkedWriter {
+        chunk: usize,
+        written: Vec<u8>,
+    }
+
+    impl AsyncWrite for ChunkedWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.poll_write_vectored(cx, &[IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let mut n = 0;
+            for buf in bufs {
+                let take = buf.len().min(self.chunk - n);
+                self.written.extend_from_slice(&buf[..take]);
+                n += take;
+            }
+            Poll::Ready(Ok(n))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_write_all_vectored_resumes_partial_writes() {
+        let mut writer = ChunkedWriter {
+            chunk: 5,
+            written: Vec::new(),
+        };
+        let header = b"HTTP/1.1 200 OK\r\n\r\n";
+        let body = b"hello world";
+        let bufs = [IoSlice::new(header), IoSlice::new(b""), IoSlice::new(body)];
+
+        write_all_vectored(&mut writer, &bufs).await.unwrap();
+        assert_eq!(writer.written, [&header[..], &body[..]].concat());
+
+        let mut stalled = ChunkedWriter {
+            chunk: 0,
+            written: Vec::new(),
+        };
+        let err = write_all_vectored(&mut stalled, &bufs).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+    }
+}